@@ -0,0 +1,123 @@
+// right-to-left text is kept in the order it is read but drawn the other
+// way, which is what this works out, by a cut-down Unicode bidi algorithm:
+// no embeddings, overrides or isolates, and a row on its own is a paragraph
+
+#[derive(Clone, Copy, PartialEq)]
+enum Class {
+    Left,
+    Right,
+    Number,
+    Neutral,
+}
+
+// Hebrew and Arabic with the scripts around them, leaving out the Arabic
+// digits, which read left to right
+pub fn is_rtl(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0590}'..='\u{05ff}'
+            | '\u{0600}'..='\u{065f}'
+            | '\u{066a}'..='\u{06ef}'
+            | '\u{06fa}'..='\u{08ff}'
+            | '\u{fb1d}'..='\u{fdff}'
+            | '\u{fe70}'..='\u{fefe}'
+    )
+}
+
+fn class(c: char) -> Class {
+    if is_rtl(c) {
+        Class::Right
+    } else if c.is_ascii_digit()
+        || ('\u{0660}'..='\u{0669}').contains(&c)
+        || ('\u{06f0}'..='\u{06f9}').contains(&c)
+    {
+        Class::Number
+    } else if c.is_alphabetic() {
+        Class::Left
+    } else {
+        Class::Neutral
+    }
+}
+
+// the order to draw `chars` in, as the index in `chars` of what goes in
+// each place from the left. None for text without right-to-left characters,
+// which is drawn as it is.
+pub fn visual_order(chars: &[char]) -> Option<Vec<usize>> {
+    if !chars.iter().copied().any(is_rtl) {
+        return None;
+    }
+    let len = chars.len();
+    let mut classes: Vec<Class> = chars.iter().copied().map(class).collect();
+    // the row goes the way of its first letter
+    let base = classes
+        .iter()
+        .copied()
+        .find(|class| matches!(class, Class::Left | Class::Right))
+        .unwrap_or(Class::Left);
+    // numbers after left-to-right text are a part of it
+    let mut last_strong = base;
+    for class in &mut classes {
+        match *class {
+            Class::Left | Class::Right => last_strong = *class,
+            Class::Number if last_strong == Class::Left => *class = Class::Left,
+            _ => (),
+        }
+    }
+    // spaces and punctuation between text going the same way go that way
+    // too, numbers counting as right-to-left, and otherwise the row's way
+    let strong = |class: Class| match class {
+        Class::Left => Some(Class::Left),
+        Class::Right | Class::Number => Some(Class::Right),
+        Class::Neutral => None,
+    };
+    let mut index = 0;
+    while index < len {
+        if classes[index] != Class::Neutral {
+            index += 1;
+            continue;
+        }
+        let end = (index..len)
+            .find(|&after| classes[after] != Class::Neutral)
+            .unwrap_or(len);
+        let before = index
+            .checked_sub(1)
+            .and_then(|before| strong(classes[before]))
+            .unwrap_or(base);
+        let after = classes
+            .get(end)
+            .and_then(|&after| strong(after))
+            .unwrap_or(base);
+        let direction = if before == after { before } else { base };
+        for class in &mut classes[index..end] {
+            *class = direction;
+        }
+        index = end;
+    }
+    let levels: Vec<u8> = classes
+        .iter()
+        .map(|class| match (base, class) {
+            (Class::Left, Class::Left) => 0,
+            (Class::Left, Class::Right) | (_, Class::Right | Class::Neutral) => 1,
+            (_, Class::Number | Class::Left) => 2,
+        })
+        .collect();
+    // from the highest level down, every stretch at that level or above is
+    // reversed
+    let mut order: Vec<usize> = (0..len).collect();
+    let highest = levels.iter().copied().max().unwrap_or(0);
+    for level in (1..=highest).rev() {
+        let mut start = 0;
+        while start < len {
+            if levels[order[start]] < level {
+                start += 1;
+                continue;
+            }
+            let end = (start..len)
+                .find(|&end| levels[order[end]] < level)
+                .unwrap_or(len);
+            order[start..end].reverse();
+            start = end;
+        }
+    }
+    Some(order)
+}
@@ -0,0 +1,24 @@
+use crate::{Document, Position};
+
+// an open document that isn't on screen, with where its view was left
+pub struct Buffer {
+    pub document: Document,
+    pub cursor_position: Position,
+    pub offset: Position,
+}
+
+impl Buffer {
+    pub fn from(document: Document) -> Self {
+        Self {
+            document,
+            cursor_position: Position::default(),
+            offset: Position::default(),
+        }
+    }
+}
+
+// a closed buffer, remembered so it can be opened again where it was left
+pub struct Closed {
+    pub file_name: String,
+    pub cursor_position: Position,
+}
@@ -0,0 +1,130 @@
+use serde::de::value::{Error, StrDeserializer};
+use serde::Deserialize;
+
+use crate::config::{self, Command};
+use crate::document::Order;
+use crate::messages::message;
+
+// the command line's own commands, besides every command a key can be
+// bound to under its name in the `[keys]` table
+pub const NAMES: [&str; 11] = [
+    "w", "q", "wq", "goto", "set", "setlocal", "theme", "help", "sort", "uniq", "undo",
+];
+
+// what a line typed at the command line asks for
+pub enum Action {
+    Run(Command),
+    Write,
+    Quit,
+    WriteQuit,
+    // to a line, counting from 1
+    Goto(usize),
+    // a setting as `:set` takes it, see Config::set
+    Set(String),
+    // the same for the current buffer only
+    SetLocal(String),
+    Theme(String),
+    // at a topic, or at the top for none
+    Help(String),
+    // the selected rows, or all of them without a selection
+    Sort(Order),
+    Uniq,
+    // to a state of the undo tree, or None to list where its branches end
+    Undo(Option<usize>),
+}
+
+// `w`, `goto 120`, `set wrap`, `sort n`, a bound command like `split`, or
+// just a line number
+pub fn parse(line: &str) -> Result<Action, String> {
+    let line = line.trim().trim_start_matches(':');
+    let (name, argument) = line
+        .split_once(' ')
+        .map_or((line, ""), |(name, argument)| (name, argument.trim()));
+    match name {
+        "w" => Ok(Action::Write),
+        "q" => Ok(Action::Quit),
+        "wq" | "x" => Ok(Action::WriteQuit),
+        "goto" | "g" => {
+            let line = argument
+                .parse()
+                .map_err(|_| message!("needs_line_number", name = name))?;
+            Ok(Action::Goto(line))
+        }
+        "set" | "setlocal" | "setl" if argument.is_empty() => {
+            Err(message!("needs_setting", name = name))
+        }
+        "set" => Ok(Action::Set(argument.to_string())),
+        "setlocal" | "setl" => Ok(Action::SetLocal(argument.to_string())),
+        "theme" if argument.is_empty() => Err(message!("needs_theme_name", name = name)),
+        "theme" => Ok(Action::Theme(argument.to_string())),
+        "help" | "h" => Ok(Action::Help(argument.to_string())),
+        "sort" => match argument {
+            "" => Ok(Action::Sort(Order::Ascending)),
+            "r" => Ok(Action::Sort(Order::Descending)),
+            "n" => Ok(Action::Sort(Order::Numeric)),
+            _ => Err(message!("sort_argument", name = name)),
+        },
+        "uniq" => Ok(Action::Uniq),
+        "undo" | "u" if argument.is_empty() => Ok(Action::Undo(None)),
+        "undo" | "u" => argument
+            .parse()
+            .map(|state| Action::Undo(Some(state)))
+            .map_err(|_| message!("needs_state_number", name = name)),
+        _ => {
+            if let Ok(line) = name.parse() {
+                return Ok(Action::Goto(line));
+            }
+            Command::deserialize(StrDeserializer::<Error>::new(name))
+                .map(Action::Run)
+                .map_err(|_| message!("not_a_command", name = name))
+        }
+    }
+}
+
+// the lines that Tab cycles through for what is typed so far: command
+// names, then settings after `set` and `setlocal`, themes after `theme` and topics after
+// `help`
+pub fn complete(line: &str, themes: &[String]) -> Vec<String> {
+    let (prefix, candidates): (&str, Vec<String>) = match line.split_once(' ') {
+        Some((name @ ("set" | "setlocal"), _)) => {
+            let switches_off = config::SETTINGS
+                .iter()
+                .filter(|setting| !setting.ends_with('='))
+                .map(|setting| format!("no{setting}"));
+            let settings = config::SETTINGS.iter().map(ToString::to_string);
+            let prefix = if name == "set" { "set " } else { "setlocal " };
+            (prefix, settings.chain(switches_off).collect())
+        }
+        Some(("theme", _)) => ("theme ", themes.to_vec()),
+        Some(("help", _)) => {
+            let settings = config::SETTINGS
+                .iter()
+                .map(|setting| setting.trim_end_matches('=').to_string());
+            (
+                "help ",
+                config::commands()
+                    .map(Command::name)
+                    .chain(settings)
+                    .collect(),
+            )
+        }
+        Some(_) => return Vec::new(),
+        None => (
+            "",
+            NAMES
+                .iter()
+                .map(ToString::to_string)
+                .chain(config::commands().map(Command::name))
+                .collect(),
+        ),
+    };
+    let typed = line.strip_prefix(prefix).unwrap_or(line);
+    let mut completions: Vec<String> = candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(typed))
+        .map(|candidate| format!("{prefix}{candidate}"))
+        .collect();
+    completions.sort();
+    completions.dedup();
+    completions
+}
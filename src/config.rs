@@ -0,0 +1,706 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use termion::color;
+use termion::event::Key;
+
+use crate::document::OnSave;
+use crate::glyphs::{self, Glyphs};
+use crate::language;
+use crate::messages::message;
+use crate::search::Case;
+use crate::ui;
+
+const DEFAULT_TAB_WIDTH: usize = 4;
+const DEFAULT_SCROLLOFF: usize = 3;
+// rows longer than this (in graphemes) are cut off and drawn without highlighting
+const DEFAULT_MAX_LINE_LENGTH: usize = 5_000;
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+const DEFAULT_TIME_FORMAT: &str = "%H:%M";
+const DEFAULT_UNDO_PAUSE_MS: u64 = 1_000;
+const DEFAULT_REFLOW_COLUMN: usize = 80;
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+const DEFAULT_DICTIONARY: &str = "en_US";
+// what a row ends in for the row after it to be indented a level more, by
+// file extension
+const DEFAULT_INDENT_AFTER: [(&str, &str); 12] = [
+    ("rs", "{(["),
+    ("c", "{(["),
+    ("h", "{(["),
+    ("cpp", "{(["),
+    ("go", "{(["),
+    ("java", "{(["),
+    ("js", "{(["),
+    ("ts", "{(["),
+    ("json", "{["),
+    ("css", "{"),
+    ("py", ":{(["),
+    ("yaml", ":"),
+];
+// lines that redacting masks: assignments to things named like secrets, AWS
+// and GitHub keys, and private key blocks
+const DEFAULT_SECRET_PATTERNS: [&str; 4] = [
+    r#"(?i)(password|passwd|secret|token|api[_-]?key)\w*["']?\s*[:=]"#,
+    r"\bAKIA[0-9A-Z]{16}\b",
+    r"\bgh[pousr]_[A-Za-z0-9]{36,}\b",
+    r"-----BEGIN [A-Z ]*PRIVATE KEY-----",
+];
+// what `:set` takes, see Config::set
+pub const SETTINGS: [&str; 24] = [
+    "wrap",
+    "smooth_scroll",
+    "persist_search_highlight",
+    "mark_suspicious",
+    "color_swatches",
+    "watch",
+    "search_wrap",
+    "recovery",
+    "swap_files",
+    "modal",
+    "screen_reader",
+    "auto_indent",
+    "ascii",
+    "auto_close",
+    "trim_trailing_whitespace",
+    "final_newline",
+    "show_invisibles",
+    "spell_check",
+    "word_count",
+    "tab_width=",
+    "scrolloff=",
+    "max_line_length=",
+    "undo_pause_ms=",
+    "wrap_column=",
+];
+const DEFAULT_KEYS: [(Command, Key); 56] = [
+    (Command::Quit, Key::Ctrl('q')),
+    (Command::Copy, Key::Ctrl('c')),
+    (Command::Cut, Key::Ctrl('x')),
+    (Command::Paste, Key::Ctrl('v')),
+    (Command::DeleteRow, Key::Ctrl('d')),
+    (Command::Find, Key::Ctrl('f')),
+    (Command::FindNext, Key::Alt('j')),
+    (Command::FindPrevious, Key::Alt('k')),
+    (Command::Replace, Key::Ctrl('t')),
+    (Command::InspectGrapheme, Key::Ctrl('g')),
+    (Command::ToggleWarnings, Key::Ctrl('u')),
+    (Command::ToggleInvisibles, Key::Alt('i')),
+    (Command::Save, Key::Ctrl('s')),
+    (Command::Undo, Key::Ctrl('z')),
+    (Command::Redo, Key::Ctrl('y')),
+    (Command::InsertFile, Key::Ctrl('r')),
+    (Command::Open, Key::Ctrl('o')),
+    (Command::NextBuffer, Key::Ctrl('b')),
+    (Command::SelectionBytes, Key::Ctrl('l')),
+    (Command::Split, Key::Ctrl('w')),
+    (Command::NextPane, Key::Ctrl('n')),
+    (Command::LineNumbers, Key::Ctrl('k')),
+    (Command::Wrap, Key::Ctrl('e')),
+    (Command::InsertVariable, Key::Ctrl('a')),
+    (Command::CloseBuffer, Key::Alt('w')),
+    (Command::ReopenBuffer, Key::Alt('t')),
+    (Command::Theme, Key::Alt('c')),
+    (Command::ExpandSelection, Key::Alt('=')),
+    (Command::ShrinkSelection, Key::Alt('-')),
+    (Command::SaveSession, Key::Alt('s')),
+    (Command::LoadSession, Key::Alt('o')),
+    (Command::ShowPath, Key::Alt('p')),
+    (Command::Redact, Key::Alt('r')),
+    (Command::UiTheme, Key::Alt('u')),
+    (Command::LineEnding, Key::Alt('l')),
+    (Command::Locations, Key::Alt('e')),
+    (Command::NextLocation, Key::Alt('n')),
+    (Command::PreviousLocation, Key::Alt('b')),
+    (Command::Execute, Key::Alt('x')),
+    (Command::Reflow, Key::Alt('q')),
+    (Command::SetMark, Key::Alt('m')),
+    (Command::GoToMark, Key::Alt('\'')),
+    (Command::Help, Key::F(1)),
+    (Command::MatchBracket, Key::Alt(']')),
+    // terminals send Ctrl-/ as Ctrl-_, which termion reads as Ctrl-7
+    (Command::ToggleComment, Key::Ctrl('7')),
+    (Command::SpellSuggest, Key::Alt('z')),
+    (Command::ProjectSearch, Key::Alt('g')),
+    (Command::FileTree, Key::Alt('f')),
+    (Command::ExpandSnippet, Key::Alt('/')),
+    (Command::GoToDefinition, Key::Alt('.')),
+    (Command::SearchTags, Key::Alt('a')),
+    (Command::JumpBack, Key::Alt(',')),
+    (Command::Outline, Key::Alt('d')),
+    (Command::CargoCheck, Key::F(5)),
+    (Command::NextDiagnostic, Key::F(8)),
+    (Command::PreviousDiagnostic, Key::F(7)),
+];
+
+// what a key binding does, named as in the `[keys]` table of the config
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Command {
+    Quit,
+    Copy,
+    // cuts the selection, or without one starts moving rows
+    Cut,
+    Paste,
+    DeleteRow,
+    Find,
+    // the last search's next and previous matches, around the ends
+    FindNext,
+    FindPrevious,
+    Replace,
+    InspectGrapheme,
+    ToggleWarnings,
+    // tabs and trailing spaces drawn as glyphs, see `show_invisibles`
+    ToggleInvisibles,
+    Save,
+    Undo,
+    Redo,
+    InsertFile,
+    Open,
+    NextBuffer,
+    CloseBuffer,
+    // opens the most recently closed buffer again
+    ReopenBuffer,
+    SelectionBytes,
+    Split,
+    NextPane,
+    LineNumbers,
+    Wrap,
+    InsertVariable,
+    Theme,
+    // the colors around the text, see ui_theme
+    UiTheme,
+    // to the word, string, brackets, line and so on around the selection
+    ExpandSelection,
+    ShrinkSelection,
+    // the open files of the project under a name, to come back to later
+    SaveSession,
+    LoadSession,
+    // the full path of the file, which the status bar shortens
+    ShowPath,
+    // masks the lines that look like they hold secrets, the text itself
+    // stays as it is
+    Redact,
+    // switches the file between LF and CRLF line endings
+    LineEnding,
+    // commands by name, as in `:goto 120`, also on `:` in Normal mode
+    Execute,
+    // fills the paragraph at the cursor out to the wrap column
+    Reflow,
+    // names the cursor's place by the letter pressed next, like `m` in
+    // Normal mode
+    SetMark,
+    // back to the place a letter names, like `'` in Normal mode
+    GoToMark,
+    // the file:line:column places in the buffer, e.g. pasted compiler
+    // output, or else in the clipboard, going to the first
+    Locations,
+    NextLocation,
+    PreviousLocation,
+    // searches the files under the working directory, apart from what
+    // .gitignore leaves out, listing the matching lines in a buffer
+    ProjectSearch,
+    // to where the tags file says the word at the cursor is defined, or a
+    // list of the places when it names more than one
+    GoToDefinition,
+    // lists the tags whose names have the text asked for in them
+    SearchTags,
+    // back to where the cursor was before the last jump to a tag
+    JumpBack,
+    // shows the tree of files under the working directory beside the text
+    // and gives it the keys, or hides it when it has them
+    FileTree,
+    // shows the functions, types and so on declared in the file beside the
+    // text and gives it the keys, or hides it when it has them
+    Outline,
+    // runs `cargo check` in the background, marking what it finds in the
+    // gutter and the text of the files
+    CargoCheck,
+    // to the next and previous things the check found, around the ends
+    NextDiagnostic,
+    PreviousDiagnostic,
+    // to the bracket pairing with the one at the cursor, or just before it,
+    // like `%` in Normal mode
+    MatchBracket,
+    // comments out the cursor's row or the selected rows, or takes their
+    // comments out if they all are, as the file's language writes comments
+    ToggleComment,
+    // replaces the misspelled word at the cursor with the dictionary's
+    // suggestions for it, the next one each time
+    SpellSuggest,
+    // replaces the word before the cursor with the snippet it names, then
+    // Tab and Shift-Tab go through the snippet's fields
+    ExpandSnippet,
+    // a read-only buffer listing the keys as they are bound and the
+    // commands and settings
+    Help,
+}
+
+impl Command {
+    // as it is named in the `[keys]` table
+    pub fn name(self) -> String {
+        toml::Value::try_from(self)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_default()
+    }
+}
+
+// where text goes across a window wider than the wrap column
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WrapAlign {
+    // at the left, the rest of the window left empty
+    Left,
+    Center,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineNumbers {
+    Off,
+    Absolute,
+    // distance from the cursor line, which shows its own number
+    Relative,
+}
+
+// a color written as `[r, g, b]`
+#[derive(Clone, Copy, Deserialize)]
+#[serde(from = "[u8; 3]")]
+pub struct Rgb(pub color::Rgb);
+
+impl From<[u8; 3]> for Rgb {
+    fn from([r, g, b]: [u8; 3]) -> Self {
+        Self(color::Rgb(r, g, b))
+    }
+}
+
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub tab_width: usize,
+    // lines of context kept above and below the cursor when scrolling
+    pub scrolloff: usize,
+    pub max_line_length: usize,
+    pub mark_suspicious: bool,
+    pub color_swatches: bool,
+    // reload the open documents when their files change on disk, as the
+    // system's file events tell
+    pub watch: bool,
+    // animate page and search jumps
+    pub smooth_scroll: bool,
+    // keep highlighting the matches after a search, until Esc
+    pub persist_search_highlight: bool,
+    // "smart" ignores case unless the query has an uppercase letter, or
+    // "sensitive" or "insensitive"
+    pub search_case: Case,
+    // a search goes on from the top after the bottom match, or the other way
+    pub search_wrap: bool,
+    // snapshot documents with unsaved edits for `--recover`
+    pub recovery: bool,
+    // keep the unsaved text of each file in `.<name>.swp` beside it while
+    // editing, to recover from if the editor doesn't exit cleanly
+    pub swap_files: bool,
+    // typing is undone a burst at a time, a burst ending at a pause this
+    // long or a move of the cursor; 0 undoes each character on its own
+    pub undo_pause_ms: u64,
+    pub line_numbers: LineNumbers,
+    // break long rows over several screen lines instead of scrolling sideways
+    pub wrap: bool,
+    // the column that wrapping and reflowing break lines at, the same however
+    // wide the window is; 0 wraps at the window's width and reflows at 80
+    pub wrap_column: usize,
+    // "left" or "center", for a window wider than the wrap column
+    pub wrap_align: WrapAlign,
+    // vim-style Normal, Insert and Visual modes, starting in Normal
+    pub modal: bool,
+    // for terminal screen readers: no decoration or animation, redrawing
+    // only on change, and the line the cursor moves to said in the message
+    // bar, with what its colors would tell
+    pub screen_reader: bool,
+    // the UI drawn in ASCII only, with the characters in `ascii_glyphs`
+    pub ascii: bool,
+    pub ascii_glyphs: Glyphs,
+    // a syntect theme, or one from a .tmTheme file in the themes directory
+    pub theme: String,
+    // the colors of the status bar, gutter and so on, from a file in the
+    // ui-themes directory
+    pub ui_theme: String,
+    // the status bar under UI themes that leave it to the syntax theme
+    pub status_bg_color: Option<Rgb>,
+    pub status_fg_color: Option<Rgb>,
+    // characters that word motions treat as part of a word, besides letters,
+    // digits and `_`, by file extension, e.g. `css = "-"`
+    pub word_chars: HashMap<String, String>,
+    // Enter indents the new row like the one it breaks
+    pub auto_indent: bool,
+    // on top of the defaults, what a row ends in for Enter to indent a level
+    // more, by file extension, e.g. `lua = "{("`
+    pub indent_after: HashMap<String, String>,
+    // settings as `:set` takes them for the files of a type, by extension
+    // or whole name, e.g. `md = ["wrap", "spell_check"]`, over the ones
+    // above
+    filetype: HashMap<String, Vec<String>>,
+    // typing (, [, { or " puts the closer after the cursor too
+    pub auto_close: bool,
+    // saving takes spaces and tabs off the ends of rows
+    pub trim_trailing_whitespace: bool,
+    // tabs, spaces at the ends of rows and non-breaking spaces are drawn as
+    // the glyphs for them, dimmed
+    pub show_invisibles: bool,
+    // saving ends the file in exactly one newline, unless the project's
+    // .byron-editor.toml says to have none
+    pub final_newline: bool,
+    // misspelled words are underlined, in prose files throughout and in
+    // code only in comments
+    pub spell_check: bool,
+    // a hunspell .dic file or word list, or the name of one like `en_US` in
+    // the dictionaries directory or the system's hunspell directories
+    pub spell_dictionary: String,
+    // where the file tree moves what it deletes, for u to take it back out;
+    // empty is the trash directory beside this file
+    pub trash_dir: String,
+    // the status bar counts the words and characters of the document
+    pub word_count: bool,
+    // for the ${date} and ${time} variables, in strftime format
+    pub date_format: String,
+    pub time_format: String,
+    pub author: String,
+    // the language of the messages, like `de` or `pt_BR`, from a file in the
+    // locales directory; empty takes it from LC_ALL, LC_MESSAGES or LANG
+    pub locale: String,
+    // regexes for the lines that redacting hides, e.g. while sharing the
+    // screen
+    secret_patterns: Vec<String>,
+    #[serde(skip)]
+    secrets: Vec<Regex>,
+    // command to key, e.g. `save = "ctrl-s"`, on top of the defaults
+    keys: HashMap<Command, String>,
+    #[serde(skip)]
+    bindings: HashMap<Key, Command>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tab_width: DEFAULT_TAB_WIDTH,
+            scrolloff: DEFAULT_SCROLLOFF,
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            mark_suspicious: true,
+            color_swatches: true,
+            watch: true,
+            smooth_scroll: true,
+            persist_search_highlight: false,
+            search_case: Case::Smart,
+            search_wrap: true,
+            recovery: true,
+            swap_files: true,
+            undo_pause_ms: DEFAULT_UNDO_PAUSE_MS,
+            line_numbers: LineNumbers::Off,
+            wrap: false,
+            wrap_column: 0,
+            wrap_align: WrapAlign::Left,
+            modal: false,
+            screen_reader: false,
+            ascii: false,
+            ascii_glyphs: Glyphs::default(),
+            theme: DEFAULT_THEME.to_string(),
+            ui_theme: ui::DEFAULT.to_string(),
+            status_bg_color: None,
+            status_fg_color: None,
+            word_chars: HashMap::new(),
+            auto_indent: true,
+            indent_after: default_indent_after(),
+            filetype: HashMap::new(),
+            auto_close: false,
+            trim_trailing_whitespace: false,
+            final_newline: false,
+            show_invisibles: false,
+            spell_check: false,
+            spell_dictionary: DEFAULT_DICTIONARY.to_string(),
+            trash_dir: String::new(),
+            word_count: false,
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            time_format: DEFAULT_TIME_FORMAT.to_string(),
+            author: String::new(),
+            locale: String::new(),
+            secret_patterns: DEFAULT_SECRET_PATTERNS
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            secrets: DEFAULT_SECRET_PATTERNS
+                .iter()
+                .map(|pattern| Regex::new(pattern).expect("invalid secret pattern"))
+                .collect(),
+            keys: HashMap::new(),
+            bindings: default_bindings(),
+        }
+    }
+}
+
+impl Config {
+    // reads ~/.config/byron-editor/config.toml. A missing file means the
+    // defaults; a broken one means the defaults and a message saying why.
+    pub fn load() -> (Self, Option<String>) {
+        let Some(path) = path() else {
+            return (Self::default(), None);
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return (Self::default(), None);
+            }
+            Err(error) => {
+                let message = message!("cannot_read_config", file = path.display(), error = error);
+                return (Self::default(), Some(message));
+            }
+        };
+        match Self::parse(&text) {
+            Ok(config) => (config, None),
+            Err(error) => {
+                let message = message!("config_error", file = path.display(), error = error);
+                (Self::default(), Some(message))
+            }
+        }
+    }
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config: Self = toml::from_str(text).map_err(|error| error.message().to_string())?;
+        let mut bindings = default_bindings();
+        // a rebound command gives up its default key, a taken key goes to
+        // the command it is bound to here
+        for (command, name) in &config.keys {
+            let key = parse_key(name).ok_or_else(|| message!("unknown_config_key", key = name))?;
+            bindings.retain(|_, bound| bound != command);
+            bindings.insert(key, *command);
+        }
+        config.bindings = bindings;
+        let mut indent_after = default_indent_after();
+        indent_after.extend(config.indent_after.drain());
+        config.indent_after = indent_after;
+        config.secrets = config
+            .secret_patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|error| {
+                    message!("invalid_secret_pattern", pattern = pattern, error = error)
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        for (name, settings) in &config.filetype {
+            let mut layered = config.clone();
+            for setting in settings {
+                layered
+                    .set(setting)
+                    .map_err(|error| format!("filetype.{name}: {error}"))?;
+            }
+        }
+        Ok(config)
+    }
+    // what holds for `file_name`: these settings, its type's over them,
+    // and the `:setlocal` ones of its buffer over those
+    pub fn layered(&self, file_name: Option<&str>, local: &[String]) -> Self {
+        let mut config = self.clone();
+        let own = language::type_names(file_name)
+            .into_iter()
+            .find_map(|name| self.filetype.get(&name));
+        for setting in own.into_iter().flatten().chain(local) {
+            // the filetype ones were checked when loaded, the local ones
+            // when typed
+            config.set(setting).ok();
+        }
+        config
+    }
+    pub fn is_secret(&self, line: &str) -> bool {
+        self.secrets.iter().any(|secret| secret.is_match(line))
+    }
+    // a switch as `wrap` to turn it on or `nowrap` to turn it off, a number
+    // as `tab_width=8`
+    pub fn set(&mut self, setting: &str) -> Result<(), String> {
+        if let Some((name, value)) = setting.split_once('=') {
+            let number = value
+                .trim()
+                .parse()
+                .map_err(|_| message!("not_a_number", value = value))?;
+            match name.trim() {
+                "tab_width" => self.tab_width = number,
+                "scrolloff" => self.scrolloff = number,
+                "max_line_length" => self.max_line_length = number,
+                "undo_pause_ms" => self.undo_pause_ms = u64::try_from(number).unwrap_or(u64::MAX),
+                "wrap_column" => self.wrap_column = number,
+                _ => return Err(message!("no_number_setting", name = name)),
+            }
+            return Ok(());
+        }
+        let (name, on) = setting
+            .strip_prefix("no")
+            .map_or((setting, true), |name| (name, false));
+        let switch = match name {
+            "wrap" => &mut self.wrap,
+            "smooth_scroll" => &mut self.smooth_scroll,
+            "persist_search_highlight" => &mut self.persist_search_highlight,
+            "mark_suspicious" => &mut self.mark_suspicious,
+            "color_swatches" => &mut self.color_swatches,
+            "watch" => &mut self.watch,
+            "search_wrap" => &mut self.search_wrap,
+            "recovery" => &mut self.recovery,
+            "swap_files" => &mut self.swap_files,
+            "modal" => &mut self.modal,
+            "screen_reader" => &mut self.screen_reader,
+            "auto_indent" => &mut self.auto_indent,
+            "ascii" => &mut self.ascii,
+            "auto_close" => &mut self.auto_close,
+            "trim_trailing_whitespace" => &mut self.trim_trailing_whitespace,
+            "final_newline" => &mut self.final_newline,
+            "show_invisibles" => &mut self.show_invisibles,
+            "spell_check" => &mut self.spell_check,
+            "word_count" => &mut self.word_count,
+            _ => return Err(message!("no_setting", name = name)),
+        };
+        *switch = on;
+        Ok(())
+    }
+    pub fn on_save(&self) -> OnSave {
+        OnSave {
+            trim_trailing_whitespace: self.trim_trailing_whitespace,
+            final_newline: self.final_newline,
+        }
+    }
+    // where the file tree moves what it deletes
+    pub fn trash(&self) -> Option<PathBuf> {
+        if self.trash_dir.is_empty() {
+            trash_dir()
+        } else {
+            Some(PathBuf::from(&self.trash_dir))
+        }
+    }
+    // where Reflow breaks lines
+    pub fn reflow_column(&self) -> usize {
+        if self.wrap_column == 0 {
+            DEFAULT_REFLOW_COLUMN
+        } else {
+            self.wrap_column
+        }
+    }
+    // what the UI draws besides the text
+    pub fn glyphs(&self) -> &Glyphs {
+        if self.ascii {
+            &self.ascii_glyphs
+        } else {
+            glyphs::unicode()
+        }
+    }
+    // the characters that indent the row after a level more in a file named
+    // `file_name`
+    pub fn indent_after(&self, file_name: Option<&str>) -> &str {
+        file_name
+            .and_then(|name| Path::new(name).extension())
+            .and_then(|extension| self.indent_after.get(&*extension.to_string_lossy()))
+            .map_or("", String::as_str)
+    }
+    pub fn command(&self, key: Key) -> Option<Command> {
+        self.bindings.get(&key).copied()
+    }
+    // how the key bound to `command` is written in help text, e.g. Ctrl-S
+    pub fn key_name(&self, command: Command) -> String {
+        let key = self
+            .bindings
+            .iter()
+            .find_map(|(key, bound)| (*bound == command).then_some(*key));
+        match key {
+            Some(Key::Ctrl('7')) => "Ctrl-/".to_string(),
+            Some(Key::Ctrl(c)) => format!("Ctrl-{}", c.to_ascii_uppercase()),
+            Some(Key::Alt(c)) => format!("Alt-{c}"),
+            Some(Key::F(number)) => format!("F{number}"),
+            _ => "unbound".to_string(),
+        }
+    }
+}
+
+// the name of the setting `setting` is for, as Config::set takes it
+pub fn setting_name(setting: &str) -> &str {
+    match setting.split_once('=') {
+        Some((name, _)) => name.trim(),
+        None => setting.strip_prefix("no").unwrap_or(setting),
+    }
+}
+
+// where .tmTheme files are picked up from
+pub fn themes_dir() -> Option<PathBuf> {
+    Some(path()?.with_file_name("themes"))
+}
+
+// where translations of the messages are picked up from, see messages::init
+pub fn locales_dir() -> Option<PathBuf> {
+    Some(path()?.with_file_name("locales"))
+}
+
+// where the queries searched for are kept, see search::History
+pub fn search_history_path() -> Option<PathBuf> {
+    Some(path()?.with_file_name("search_history"))
+}
+
+// where dictionaries named by spell_dictionary are looked for first
+pub fn dictionaries_dir() -> Option<PathBuf> {
+    Some(path()?.with_file_name("dictionaries"))
+}
+
+// where the file tree moves what it deletes unless trash_dir says
+pub fn trash_dir() -> Option<PathBuf> {
+    Some(path()?.with_file_name("trash"))
+}
+
+// the snippets by file extension, see snippets::find
+pub fn snippets_path() -> Option<PathBuf> {
+    Some(path()?.with_file_name("snippets.toml"))
+}
+
+// every command, as each has a key by default
+fn default_indent_after() -> HashMap<String, String> {
+    DEFAULT_INDENT_AFTER
+        .iter()
+        .map(|(extension, chars)| ((*extension).to_string(), (*chars).to_string()))
+        .collect()
+}
+
+pub fn commands() -> impl Iterator<Item = Command> {
+    DEFAULT_KEYS.iter().map(|&(command, _)| command)
+}
+
+fn default_bindings() -> HashMap<Key, Command> {
+    DEFAULT_KEYS
+        .iter()
+        .map(|&(command, key)| (key, command))
+        .collect()
+}
+
+// `ctrl-s`, `alt-x` or `f1`; other keys are left to the editor
+pub fn parse_key(name: &str) -> Option<Key> {
+    if let Some(number) = name
+        .strip_prefix(['f', 'F'])
+        .and_then(|number| number.parse().ok())
+    {
+        return (1..=12).contains(&number).then_some(Key::F(number));
+    }
+    let (modifier, key) = name.split_once('-')?;
+    let mut chars = key.chars();
+    let c = chars.next()?.to_ascii_lowercase();
+    if chars.next().is_some() {
+        return None;
+    }
+    match modifier.to_ascii_lowercase().as_str() {
+        "ctrl" if c.is_ascii_lowercase() => Some(Key::Ctrl(c)),
+        "ctrl" if c == '/' => Some(Key::Ctrl('7')),
+        "alt" => Some(Key::Alt(c)),
+        _ => None,
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    let home = env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(".config/byron-editor/config.toml"))
+}
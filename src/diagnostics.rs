@@ -0,0 +1,202 @@
+use std::env;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use serde::Deserialize;
+use termion::color;
+
+use crate::highlighting;
+use crate::messages::message;
+use crate::Position;
+
+// worst first, so the least of those on a row is the one its sign shows
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    // rustc's notes and help, and anything else it doesn't call an error or
+    // a warning
+    Note,
+}
+
+impl Severity {
+    fn from_level(level: &str) -> Self {
+        if level.starts_with("error") {
+            Severity::Error
+        } else if level == "warning" {
+            Severity::Warning
+        } else {
+            Severity::Note
+        }
+    }
+    pub fn to_color(self) -> color::Rgb {
+        match self {
+            Severity::Error => highlighting::Type::Error,
+            Severity::Warning => highlighting::Type::Warning,
+            Severity::Note => highlighting::Type::Note,
+        }
+        .to_color()
+    }
+    // the id of the message naming it
+    pub fn id(self) -> &'static str {
+        match self {
+            Severity::Error => "severity_error",
+            Severity::Warning => "severity_warning",
+            Severity::Note => "severity_note",
+        }
+    }
+}
+
+// what a checker said about the text from `start` up to `end`
+#[derive(Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub start: Position,
+    pub end: Position,
+    pub message: String,
+}
+
+impl Diagnostic {
+    // whether `at` is in the range or just after it, the cursor being
+    // after the last of a word it typed
+    pub fn covers(&self, at: Position) -> bool {
+        (self.start.y, self.start.x) <= (at.y, at.x) && (at.y, at.x) <= (self.end.y, self.end.x)
+    }
+    // the columns of row `y`, `len` graphemes long, the range takes up, at
+    // least one where it is empty
+    pub fn columns(&self, y: usize, len: usize) -> Option<Range<usize>> {
+        if y < self.start.y || y > self.end.y {
+            return None;
+        }
+        let start = if y == self.start.y { self.start.x } else { 0 };
+        let end = if y == self.end.y { self.end.x } else { len };
+        Some(start..end.max(start.saturating_add(1)))
+    }
+}
+
+// what `cargo check` found, each diagnostic with its file named from the
+// working directory, or why it couldn't be run
+pub type Found = Result<Vec<(String, Diagnostic)>, String>;
+
+// runs `cargo check` on a thread, it takes a while and the editor goes on
+// meanwhile
+pub fn check() -> Receiver<Found> {
+    let (sender, found) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(run());
+    });
+    found
+}
+
+// a line of `cargo check --message-format=json`, the parts of it looked at
+#[derive(Deserialize)]
+struct Line {
+    reason: String,
+    message: Option<CompilerMessage>,
+}
+
+#[derive(Deserialize)]
+struct CompilerMessage {
+    level: String,
+    message: String,
+    spans: Vec<Span>,
+}
+
+// lines and columns count from 1, columns in characters, and the end is the
+// column after the range
+#[derive(Deserialize)]
+struct Span {
+    file_name: String,
+    line_start: usize,
+    line_end: usize,
+    column_start: usize,
+    column_end: usize,
+    is_primary: bool,
+    label: Option<String>,
+}
+
+fn run() -> Found {
+    let root = workspace_root()?;
+    let output = Command::new("cargo")
+        .args(["check", "--message-format=json", "--quiet"])
+        .output()
+        .map_err(|error| message!("cannot_run_cargo", error = error))?;
+    let cwd = env::current_dir().unwrap_or_default();
+    let mut found: Vec<(String, Diagnostic)> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| parse(line, &root, &cwd))
+        .collect();
+    if found.is_empty() && !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let error = stderr
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .map_or_else(
+                || message!("cargo_check_failed"),
+                |line| line.trim().to_string(),
+            );
+        return Err(error);
+    }
+    // a library and a binary of the same crate both report what they share
+    let mut seen = Vec::new();
+    found.retain(|diagnostic| {
+        let new = !seen.contains(diagnostic);
+        if new {
+            seen.push(diagnostic.clone());
+        }
+        new
+    });
+    Ok(found)
+}
+
+// the directory of the workspace the working directory is in, which the
+// file names of the spans start from
+fn workspace_root() -> Result<PathBuf, String> {
+    let output = Command::new("cargo")
+        .args(["locate-project", "--workspace", "--message-format", "plain"])
+        .output()
+        .map_err(|error| message!("cannot_run_cargo", error = error))?;
+    if !output.status.success() {
+        return Err(message!("no_cargo_toml"));
+    }
+    let manifest = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    Ok(manifest.parent().map(Path::to_path_buf).unwrap_or_default())
+}
+
+// a compiler message at its primary span, with the span's label after
+// the message where it has one. Messages without a place, like the count
+// of errors at the end, are left out.
+fn parse(line: &str, root: &Path, cwd: &Path) -> Option<(String, Diagnostic)> {
+    let line: Line = serde_json::from_str(line).ok()?;
+    if line.reason != "compiler-message" {
+        return None;
+    }
+    let message = line.message?;
+    let span = message.spans.iter().find(|span| span.is_primary)?;
+    let text = match span.label.as_deref() {
+        Some(label) if !label.is_empty() => format!("{}: {label}", message.message),
+        _ => message.message.clone(),
+    };
+    let path = root.join(&span.file_name);
+    let file_name = path
+        .strip_prefix(cwd)
+        .unwrap_or(&path)
+        .to_string_lossy()
+        .into_owned();
+    let diagnostic = Diagnostic {
+        severity: Severity::from_level(&message.level),
+        start: Position {
+            x: span.column_start.saturating_sub(1),
+            y: span.line_start.saturating_sub(1),
+        },
+        end: Position {
+            x: span.column_end.saturating_sub(1),
+            y: span.line_end.saturating_sub(1),
+        },
+        message: text,
+    };
+    Some((file_name, diagnostic))
+}
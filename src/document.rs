@@ -1,97 +1,1302 @@
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::encoding::{self, Encoding};
+use crate::highlighting::Highlighted;
+use crate::indent::{self, Indent};
+use crate::policy::{LineEnding, Policy};
+use crate::recovery::{self, Snapshot};
+use crate::snippets::Snippet;
+use crate::swap;
+use crate::undo::{History, Operation};
+use crate::{Position, Row};
+use regex::Regex;
 use std::{
+    cmp,
+    collections::BTreeMap,
     fs,
-    io::{Error, Write},
+    io::{BufRead, BufReader, Error, Read, Seek, SeekFrom, Write},
+    mem,
+    ops::Range,
+    path::Path,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    sync::OnceLock,
+    thread,
+    time::{Duration, Instant, SystemTime},
 };
-use crate::{Position, Row};
+use syntect::highlighting::{Highlighter, Theme};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use unicode_segmentation::UnicodeSegmentation;
+
+// files bigger than this are read on a thread, the first rows right away so
+// they can be looked at while the rest is coming
+const LAZY_OPEN_SIZE: u64 = 16 * 1024 * 1024;
+// how much of such a file tells its line ending
+const LINE_ENDING_SAMPLE: u64 = 64 * 1024;
+const FIRST_ROWS: usize = 1_000;
+const BATCH_ROWS: usize = 10_000;
+// how many rows tell how a file is indented
+const INDENT_SAMPLE: usize = 1_000;
+
+// the files git and other tools open the editor on to have a message written,
+// where lines starting with # are guidance that is left out
+const MESSAGE_FILES: [&str; 6] = [
+    "COMMIT_EDITMSG",
+    "MERGE_MSG",
+    "SQUASH_MSG",
+    "TAG_EDITMSG",
+    "EDIT_DESCRIPTION",
+    "git-rebase-todo",
+];
+
+// how Document::sort_rows orders rows
+#[derive(Clone, Copy)]
+pub enum Order {
+    Ascending,
+    Descending,
+    // by the first number in each row, rows without one first
+    Numeric,
+}
+
+// what saving does to the text before writing it, as the config says
+#[derive(Clone, Copy, Default)]
+pub struct OnSave {
+    pub trim_trailing_whitespace: bool,
+    // one newline at the end, and no blank rows before it
+    pub final_newline: bool,
+}
+
+// the words and characters of rows, line breaks not counted, kept by
+// Document::counts
+#[derive(Clone, Copy, Default)]
+pub struct Counts {
+    pub words: usize,
+    pub characters: usize,
+}
+
+impl Counts {
+    fn of<'a>(rows: impl Iterator<Item = &'a Row>) -> Self {
+        rows.fold(Self::default(), |counts, row| Self {
+            words: counts
+                .words
+                .saturating_add(row.as_str().split_whitespace().count()),
+            characters: counts.characters.saturating_add(row.len()),
+        })
+    }
+    // with the counts of some rows taken out and of what replaced them put in
+    fn replace(&mut self, old: Self, new: Self) {
+        self.words = self
+            .words
+            .saturating_sub(old.words)
+            .saturating_add(new.words);
+        self.characters = self
+            .characters
+            .saturating_sub(old.characters)
+            .saturating_add(new.characters);
+    }
+}
 
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Default)]
 pub struct Document {
     rows: Vec<Row>,
     pub file_name: Option<String>,
+    // the name of the syntax it was found to be in, see syntax::remember
+    pub syntax: Option<String>,
     dirty: bool,
+    // what the file is written in, and saved back in
+    encoding: Encoding,
+    line_ending: LineEnding,
+    // the last line has no line ending after it, which saving keeps that way.
+    // New files get one.
+    no_final_newline: bool,
+    // how the file indents, None until it is known from an indented row
+    indent: Option<Indent>,
+    // when the file was last read or written by us
+    modified: Option<SystemTime>,
+    history: History,
+    // the file can't be written, edits need the user's go-ahead first
+    read_only: bool,
+    edits_allowed: bool,
+    // edited since the last recovery snapshot
+    snapshot_due: bool,
+    swap: Swap,
+    // edited since the swap file was written
+    swap_due: bool,
+    loading: Option<Loading>,
+    // reading the file failed part way, only the rows before that are here
+    incomplete: bool,
+    // the changes each subscriber hasn't taken yet, None where one left
+    subscribers: Vec<Option<Vec<Change>>>,
+    // places named by a letter, which move with their row as rows go in and
+    // out above it
+    marks: BTreeMap<char, Position>,
+    // counted the first time they are asked for, then kept up to date by
+    // each edit from the rows it touches
+    counts: Option<Counts>,
+    // lists places in files, like the results of a project search, which
+    // Enter goes to: the rows as listed, which edits to them are written
+    // back from
+    listing: Option<Vec<String>>,
+    // lists undo states, which Enter goes to, of the buffer of the file
+    // `states_of` names or of one without a name
+    undo_tree: bool,
+    states_of: Option<String>,
+    // the file changed on disk while there were edits here, which saving
+    // overwrites
+    disk_conflict: bool,
+    // the snippet expanded last while Tab is still going through its fields
+    pub snippet: Option<Snippet>,
+    // what the last check said about the file, its rows moving like the
+    // marks', and which check that was
+    diagnostics: Vec<Diagnostic>,
+    checked: usize,
+    // what `:setlocal` set for this buffer, over the config and its
+    // filetype's settings
+    pub local_settings: Vec<String>,
 }
 
-impl Document {
-    pub fn open(filename: &str) -> Result<Self, std::io::Error> {
-        let file = fs::read_to_string(filename)?;
-        let mut rows = Vec::new();
-        for line in file.lines() {
-            let line = Row::from(line);
-            rows.push(line)
+// what an edit did to the rows, for whatever keeps state about them
+#[derive(Clone, Copy, PartialEq)]
+pub enum Change {
+    // the text of a row changed
+    Edited(usize),
+    // `count` rows went in at row `at`
+    Inserted { at: usize, count: usize },
+    // the `count` rows from row `at` on are gone
+    Removed { at: usize, count: usize },
+}
+
+impl Change {
+    // the first row the change touches
+    pub fn first_row(&self) -> usize {
+        match *self {
+            Self::Edited(y) | Self::Inserted { at: y, .. } | Self::Removed { at: y, .. } => y,
         }
+    }
+    // where the text on row `y` is after the change, the first row after a
+    // removal for the rows that went with it
+    pub fn shift(&self, y: usize) -> usize {
+        match *self {
+            Self::Inserted { at, count } if y >= at => y.saturating_add(count),
+            Self::Removed { at, count } if y >= at => cmp::max(y.saturating_sub(count), at),
+            _ => y,
+        }
+    }
+}
+
+// the swap file beside the document's file, see swap::path
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Swap {
+    #[default]
+    None,
+    // written by us, removed once the edits are saved or the editor exits
+    Written,
+    // there when the file was opened, left by an editor that didn't exit
+    // cleanly, until the user says what to do with it
+    Found,
+    // one the user chose to leave alone, which is neither written nor
+    // removed
+    Kept,
+}
+
+// how a file's text is written, which saving keeps to
+struct Text {
+    encoding: Encoding,
+    line_ending: LineEnding,
+    no_final_newline: bool,
+}
+
+// a consumer's queue of changes, see Document::subscribe
+#[derive(Clone, Copy)]
+pub struct Subscription(usize);
+
+// the rest of a big file, in batches of rows from the thread reading it
+struct Loading {
+    batches: Receiver<Result<(Vec<String>, u64), Error>>,
+    size: u64,
+    read: u64,
+}
 
+impl Document {
+    pub fn open(filename: &str) -> Result<Self, std::io::Error> {
+        let size = fs::metadata(filename)?.len();
+        // big files are taken to be UTF-8, reading one that isn't stops
+        // with an error part way
+        let (rows, text, loading) = if size > LAZY_OPEN_SIZE {
+            let mut sample = Vec::new();
+            fs::File::open(filename)?
+                .take(LINE_ENDING_SAMPLE)
+                .read_to_end(&mut sample)?;
+            let line_ending = LineEnding::detect(&String::from_utf8_lossy(&sample));
+            let (mut rows, loading) = open_lazily(filename, size)?;
+            let encoding = if encoding::has_utf8_bom(&sample) {
+                if let Some(first) = rows.first_mut() {
+                    *first = Row::from(first.as_str().trim_start_matches('\u{feff}'));
+                }
+                Encoding::Utf8Bom
+            } else {
+                Encoding::Utf8
+            };
+            let text = Text {
+                encoding,
+                line_ending,
+                no_final_newline: !ends_with_newline(filename)?,
+            };
+            (rows, text, Some(loading))
+        } else {
+            let (file, encoding) = encoding::decode(&fs::read(filename)?)?;
+            let text = Text {
+                encoding,
+                line_ending: LineEnding::detect(&file),
+                no_final_newline: !file.is_empty() && !file.ends_with('\n'),
+            };
+            (file.lines().map(Row::from).collect(), text, None)
+        };
+        let indent = indent::detect(rows.iter().take(INDENT_SAMPLE).map(Row::as_str));
         Ok(Self {
             rows,
             file_name: Some(filename.to_string()),
+            syntax: None,
             dirty: false,
+            encoding: text.encoding,
+            line_ending: text.line_ending,
+            no_final_newline: text.no_final_newline,
+            indent,
+            modified: modified_time(filename),
+            history: History::default(),
+            read_only: fs::OpenOptions::new().append(true).open(filename).is_err(),
+            edits_allowed: false,
+            snapshot_due: false,
+            swap: if swap::exists(filename) {
+                Swap::Found
+            } else {
+                Swap::None
+            },
+            swap_due: false,
+            loading,
+            incomplete: false,
+            subscribers: Vec::new(),
+            marks: BTreeMap::new(),
+            counts: None,
+            listing: None,
+            undo_tree: false,
+            states_of: None,
+            disk_conflict: false,
+            snippet: None,
+            diagnostics: Vec::new(),
+            checked: 0,
+            local_settings: Vec::new(),
         })
     }
+    // text that isn't from a file, like the tutorial
+    pub fn scratch(text: &str) -> Self {
+        Self {
+            rows: text.lines().map(Row::from).collect(),
+            ..Self::default()
+        }
+    }
+    // text to read, like the help, edited only when that is asked for
+    pub fn read_only(text: &str) -> Self {
+        Self {
+            read_only: true,
+            ..Self::scratch(text)
+        }
+    }
+    // a read-only list of `path:line:column` places
+    pub fn listing(text: &str) -> Self {
+        Self {
+            listing: Some(text.lines().map(str::to_string).collect()),
+            ..Self::read_only(text)
+        }
+    }
+    // a read-only list of the undo states of the buffer of `of`
+    pub fn undo_tree(text: &str, of: Option<String>) -> Self {
+        Self {
+            undo_tree: true,
+            states_of: of,
+            ..Self::read_only(text)
+        }
+    }
+    pub fn is_undo_tree(&self) -> bool {
+        self.undo_tree
+    }
+    pub fn states_of(&self) -> Option<&str> {
+        self.states_of.as_deref()
+    }
+    pub fn is_listing(&self) -> bool {
+        self.listing.is_some()
+    }
+    // the rows of a listing as they were before any edits to them
+    pub fn listed(&self) -> Option<&[String]> {
+        self.listing.as_deref()
+    }
+    // takes the rows of a listing as they are now to be the listed ones,
+    // once its edits are written back
+    pub fn relist(&mut self) {
+        if self.listing.is_some() {
+            self.listing = Some(
+                self.rows
+                    .iter()
+                    .map(|row| row.as_str().to_string())
+                    .collect(),
+            );
+            self.dirty = false;
+        }
+    }
+    // adds the rows read since the last call, for about `budget`. True when
+    // there were any.
+    pub fn load(&mut self, budget: Duration) -> Result<bool, Error> {
+        let Some(loading) = &mut self.loading else {
+            return Ok(false);
+        };
+        let started = Instant::now();
+        let at = self.rows.len();
+        let mut result = Ok(false);
+        while started.elapsed() < budget {
+            match loading.batches.try_recv() {
+                Ok(Ok((lines, bytes))) => {
+                    let first = self.rows.len();
+                    self.rows
+                        .extend(lines.iter().map(|line| Row::from(line.as_str())));
+                    if let Some(counts) = &mut self.counts {
+                        counts.replace(Counts::default(), Counts::of(self.rows[first..].iter()));
+                    }
+                    loading.read = loading.read.saturating_add(bytes);
+                    result = Ok(true);
+                }
+                Ok(Err(error)) => {
+                    self.loading = None;
+                    self.incomplete = true;
+                    result = Err(error);
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.loading = None;
+                    break;
+                }
+            }
+        }
+        let count = self.rows.len().saturating_sub(at);
+        self.notify(&[Change::Inserted { at, count }]);
+        result
+    }
+    // how much of the file is read, in percent, while it is loading
+    pub fn loading_progress(&self) -> Option<u64> {
+        let loading = self.loading.as_ref()?;
+        Some(loading.read.saturating_mul(100) / cmp::max(loading.size, 1))
+    }
+    // still loading or stopped short, which saving would cut the file down to
+    pub fn is_incomplete(&self) -> bool {
+        self.loading.is_some() || self.incomplete
+    }
+    // a snapshot's text under the name of the file it was taken from, as
+    // unsaved edits, so the file is only replaced once the user saves
+    pub fn recover(snapshot: &Snapshot) -> Result<Self, std::io::Error> {
+        let path = snapshot.path.to_string_lossy();
+        let mut document = Self::open(&path)?;
+        document.file_name.clone_from(&snapshot.file_name);
+        document.dirty = true;
+        if let Some(file_name) = &snapshot.file_name {
+            // snapshots are UTF-8 with LF line endings, saving goes back to
+            // the file's own
+            let file = fs::read(file_name).and_then(|bytes| encoding::decode(&bytes));
+            if let Ok((text, encoding)) = file {
+                document.encoding = encoding;
+                document.line_ending = LineEnding::detect(&text);
+                document.no_final_newline = !text.is_empty() && !text.ends_with('\n');
+            }
+            document.modified = modified_time(file_name);
+            document.read_only = fs::metadata(file_name).is_ok()
+                && fs::OpenOptions::new().append(true).open(file_name).is_err();
+        }
+        Ok(document)
+    }
+    // the text of the swap file under the name of its file, as unsaved edits
+    // like Document::recover. The swap file stays until they are saved.
+    pub fn recover_swap(&self) -> Result<Self, std::io::Error> {
+        let file_name = self.file_name.as_deref().unwrap_or_default();
+        let mut document = Self::open(&swap::path(file_name).to_string_lossy())?;
+        document.file_name = Some(file_name.to_string());
+        document.dirty = true;
+        document.modified = self.modified;
+        document.read_only = self.read_only;
+        document.encoding = self.encoding;
+        document.line_ending = self.line_ending;
+        document.no_final_newline = self.no_final_newline;
+        document.swap = Swap::Written;
+        Ok(document)
+    }
+    // a swap file was there when the file was opened, and is still waiting
+    // to be recovered, discarded or kept
+    pub fn found_swap(&self) -> bool {
+        self.swap == Swap::Found
+    }
+    pub fn keep_swap(&mut self) {
+        self.swap = Swap::Kept;
+    }
+    // also the one that was found
+    pub fn discard_swap(&mut self) -> Result<(), Error> {
+        if let (Some(file_name), Swap::Written | Swap::Found) = (&self.file_name, self.swap) {
+            swap::remove(file_name)?;
+        }
+        self.swap = Swap::None;
+        Ok(())
+    }
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+    pub fn indent(&self) -> Option<Indent> {
+        self.indent
+    }
+    // what saving writes from now on, which is an unsaved change to the file
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+        self.dirty = true;
+        self.snapshot_due = true;
+        self.swap_due = true;
+    }
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+    pub fn is_commit_message(&self) -> bool {
+        self.file_name
+            .as_deref()
+            .and_then(|name| Path::new(name).file_name())
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| MESSAGE_FILES.contains(&name))
+    }
+    pub fn edits_allowed(&self) -> bool {
+        !self.read_only || self.edits_allowed
+    }
+    pub fn allow_edits(&mut self) {
+        self.edits_allowed = true;
+    }
+    pub fn changed_on_disk(&self) -> bool {
+        match (&self.file_name, self.modified) {
+            (Some(file_name), Some(modified)) => {
+                modified_time(file_name).is_some_and(|time| time != modified)
+            }
+            _ => false,
+        }
+    }
+    // takes in the file's text as it is on disk now, as one step undo takes
+    // back. Only the rows between those the two have the same at the start
+    // and end are replaced, so marks and the rows around them stay put.
+    // Returns how the rows after the replaced ones moved.
+    #[allow(clippy::arithmetic_side_effects)]
+    pub fn reload(&mut self) -> Result<Change, Error> {
+        let Some(file_name) = self.file_name.clone() else {
+            return Ok(Change::Edited(0));
+        };
+        let (text, encoding) = encoding::decode(&fs::read(&file_name)?)?;
+        // what is still being read is all here now
+        self.loading = None;
+        self.incomplete = false;
+        let new: Vec<&str> = text.lines().collect();
+        let (old_len, new_len) = (self.rows.len(), new.len());
+        let same = old_len.min(new_len);
+        let first = (0..same)
+            .find(|&y| self.rows[y].as_str() != new[y])
+            .unwrap_or(same);
+        let kept = (0..same - first)
+            .find(|&from_end| {
+                self.rows[old_len - 1 - from_end].as_str() != new[new_len - 1 - from_end]
+            })
+            .unwrap_or(same - first);
+        let (old_end, new_end) = (old_len - kept, new_len - kept);
+        let mut step = Vec::new();
+        if old_end > first && new_end > first {
+            let at = Position { x: 0, y: first };
+            let end = Position {
+                x: self.rows[old_end - 1].len(),
+                y: old_end - 1,
+            };
+            step.push(Operation::Delete {
+                at,
+                text: self.text(&at, &end),
+            });
+            step.push(Operation::Insert {
+                at,
+                text: new[first..new_end].join("\n"),
+            });
+        } else {
+            for row in self.rows[first..old_end].iter().rev() {
+                step.push(Operation::DeleteRow {
+                    at: first,
+                    text: row.as_str().to_string(),
+                });
+            }
+            for (y, row) in new.iter().enumerate().take(new_end).skip(first) {
+                step.push(Operation::InsertRow {
+                    at: y,
+                    text: (*row).to_string(),
+                });
+            }
+        }
+        if !step.is_empty() {
+            self.execute(step);
+        }
+        self.encoding = encoding;
+        self.line_ending = LineEnding::detect(&text);
+        self.no_final_newline = !text.is_empty() && !text.ends_with('\n');
+        self.modified = modified_time(&file_name);
+        self.dirty = false;
+        self.disk_conflict = false;
+        Ok(match new_end.cmp(&old_end) {
+            cmp::Ordering::Greater => Change::Inserted {
+                at: old_end,
+                count: new_end - old_end,
+            },
+            cmp::Ordering::Less => Change::Removed {
+                at: new_end,
+                count: old_end - new_end,
+            },
+            cmp::Ordering::Equal => Change::Edited(first),
+        })
+    }
+    pub fn has_disk_conflict(&self) -> bool {
+        self.disk_conflict
+    }
+    // notes that the file changed on disk under the edits, true if it
+    // hadn't been noted yet
+    pub fn note_disk_conflict(&mut self) -> bool {
+        !mem::replace(&mut self.disk_conflict, true)
+    }
     pub fn is_dirty(&mut self) -> bool {
         self.dirty
     }
-    pub fn insert(&mut self, at: &Position, c: char) {
+    // a typed character, which joins the typing before it in one undo step
+    // if it came within `window`, see History::record_typed
+    pub fn insert(&mut self, at: &Position, c: char, window: Duration) {
         if at.y > self.len() {
             return;
         }
-        self.dirty = true;
-        if c == '\n' {
-            self.insert_newline(at);
-            return;
-        }
+        let mut step = Vec::new();
         // if the position y is equal to the length of the document we add a new row
         if at.y == self.len() {
-            let mut row = Row::default();
-            row.insert(0, c);
-            self.rows.push(row);
-        } else if at.y < self.len() {
-            let row = self.rows.get_mut(at.y).unwrap();
-            row.insert(at.x, c);
+            step.push(Operation::InsertRow {
+                at: at.y,
+                text: String::new(),
+            });
+            if c == '\n' {
+                self.execute_typed(step, window);
+                return;
+            }
         }
+        step.push(Operation::Insert {
+            at: self.clamp(at),
+            text: c.to_string(),
+        });
+        self.execute_typed(step, window);
     }
-    pub fn find(&mut self, query: &str, cursor_position: &Position) -> Option<Position> {
-        for (y, row) in self.rows.iter().enumerate().skip(cursor_position.y) {
-            if let Some(x) = row.find(query) {
-                return Some(Position { x, y });
-            }
+    // breaks the row at `at`, starting the new row with the indentation of
+    // the one broken and a level more when the text before `at` ends in one
+    // of `indent_after`. A level is a tab in rows indented by tabs, else
+    // `tab_width` spaces. Returns the position just after the indentation.
+    pub fn insert_newline(
+        &mut self,
+        at: &Position,
+        indent_after: &str,
+        tab_width: usize,
+        window: Duration,
+    ) -> Position {
+        let Some(row) = self.rows.get(at.y) else {
+            self.insert(at, '\n', window);
+            return Position {
+                x: 0,
+                y: cmp::min(at.y.saturating_add(1), self.len()),
+            };
+        };
+        let at = self.clamp(at);
+        let before = row.substring(0, at.x);
+        let mut indent: String = before
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        if before.trim_end().ends_with(|c| indent_after.contains(c)) {
+            // a file whose indentation isn't known yet goes on like the row
+            let unit = self.indent.map_or_else(
+                || {
+                    if indent.starts_with('\t') {
+                        "\t".to_string()
+                    } else {
+                        " ".repeat(tab_width)
+                    }
+                },
+                Indent::unit,
+            );
+            indent.push_str(&unit);
+        }
+        let x = indent.chars().count();
+        self.execute_typed(
+            vec![Operation::Insert {
+                at,
+                text: format!("\n{indent}"),
+            }],
+            window,
+        );
+        Position {
+            x,
+            y: at.y.saturating_add(1),
         }
-        None
     }
-    fn insert_newline(&mut self, at: &Position) {
+    // inserts possibly multi-line text and returns the position just after it
+    pub fn insert_str(&mut self, at: &Position, text: &str) -> Position {
         if at.y > self.len() {
-            return;
+            return *at;
         }
+        let mut step = Vec::new();
         if at.y == self.len() {
-            self.rows.push(Row::default());
+            step.push(Operation::InsertRow {
+                at: at.y,
+                text: String::new(),
+            });
+        }
+        step.push(Operation::Insert {
+            at: self.clamp(at),
+            text: text.replace("\r\n", "\n"),
+        });
+        self.execute(step)
+    }
+    // the first match of `regex` at or after `at`
+    pub fn find_regex(&self, regex: &Regex, at: &Position) -> Option<Position> {
+        for (y, row) in self.rows.iter().enumerate().skip(at.y) {
+            let found = row
+                .regex_matches(regex, "")
+                .into_iter()
+                .find(|(range, _)| y > at.y || range.start >= at.x);
+            if let Some((range, _)) = found {
+                return Some(Position { x: range.start, y });
+            }
+        }
+        None
+    }
+    // the first match at or after `at`: its row, its graphemes, and what it
+    // is replaced with, capture groups filled in
+    pub fn next_replacement(
+        &self,
+        regex: &Regex,
+        replacement: &str,
+        at: &Position,
+    ) -> Option<(usize, Range<usize>, String)> {
+        self.rows
+            .iter()
+            .enumerate()
+            .skip(at.y)
+            .find_map(|(y, row)| {
+                row.regex_matches(regex, replacement)
+                    .into_iter()
+                    .find(|(range, _)| y > at.y || range.start >= at.x)
+                    .map(|(range, with)| (y, range, with))
+            })
+    }
+    // replaces every match as one undoable step, returning how many there were
+    pub fn replace_all(&mut self, regex: &Regex, replacement: &str) -> usize {
+        let mut step = Vec::new();
+        let mut count: usize = 0;
+        // bottom to top and right to left, so a replacement containing a
+        // newline doesn't move the matches still to be replaced
+        for (y, row) in self.rows.iter().enumerate().rev() {
+            for (range, with) in row.regex_matches(regex, replacement).into_iter().rev() {
+                step.extend(replacement_step(row, y, range, with));
+                count = count.saturating_add(1);
+            }
+        }
+        self.execute(step);
+        count
+    }
+    // replaces the graphemes `range` of row `y` with `with`, as one step
+    pub fn replace_range(&mut self, y: usize, range: Range<usize>, with: &str) {
+        if let Some(row) = self.rows.get(y) {
+            let step = replacement_step(row, y, range, with.to_string());
+            self.execute(step);
+        }
+    }
+    // the brackets around the text from `start` to `end`, the closing one's
+    // position being just after it
+    pub fn enclosing_brackets(
+        &self,
+        start: &Position,
+        end: &Position,
+    ) -> Option<(Position, Position)> {
+        let mut depth: usize = 0;
+        let mut opening = None;
+        'rows: for y in (0..=cmp::min(start.y, self.len().saturating_sub(1))).rev() {
+            let graphemes: Vec<&str> = self.rows[y].as_str().graphemes(true).collect();
+            let before = if y == start.y {
+                cmp::min(start.x, graphemes.len())
+            } else {
+                graphemes.len()
+            };
+            for x in (0..before).rev() {
+                match graphemes[x] {
+                    ")" | "]" | "}" => depth = depth.saturating_add(1),
+                    "(" | "[" | "{" if depth > 0 => depth = depth.saturating_sub(1),
+                    bracket @ ("(" | "[" | "{") => {
+                        opening = Some((Position { x, y }, bracket));
+                        break 'rows;
+                    }
+                    _ => (),
+                }
+            }
+        }
+        let (open, bracket) = opening?;
+        let close = match bracket {
+            "(" => ")",
+            "[" => "]",
+            _ => "}",
+        };
+        for y in end.y..self.len() {
+            let graphemes: Vec<&str> = self.rows[y].as_str().graphemes(true).collect();
+            let from = if y == end.y { end.x } else { 0 };
+            for (x, grapheme) in graphemes.iter().enumerate().skip(from) {
+                if *grapheme == bracket {
+                    depth = depth.saturating_add(1);
+                } else if *grapheme == close && depth > 0 {
+                    depth = depth.saturating_sub(1);
+                } else if *grapheme == close {
+                    return Some((
+                        open,
+                        Position {
+                            x: x.saturating_add(1),
+                            y,
+                        },
+                    ));
+                }
+            }
+        }
+        None
+    }
+    // where the bracket that pairs with the one at `at` is, counting the
+    // pairs nested in between and looking no further than `limit` rows away
+    pub fn matching_bracket(&self, at: &Position, limit: usize) -> Option<Position> {
+        let (open, close, forward) = match self.rows.get(at.y)?.grapheme(at.x)? {
+            "(" => ("(", ")", true),
+            "[" => ("[", "]", true),
+            "{" => ("{", "}", true),
+            ")" => ("(", ")", false),
+            "]" => ("[", "]", false),
+            "}" => ("{", "}", false),
+            _ => return None,
+        };
+        let (start, end) = if forward {
+            (at.y, cmp::min(self.len(), at.y.saturating_add(limit)))
+        } else {
+            (at.y.saturating_sub(limit), at.y.saturating_add(1))
+        };
+        let mut rows: Box<dyn Iterator<Item = usize>> = if forward {
+            Box::new(start..end)
+        } else {
+            Box::new((start..end).rev())
+        };
+        let (toward, away) = if forward {
+            (close, open)
+        } else {
+            (open, close)
+        };
+        let mut depth: usize = 0;
+        rows.find_map(|y| {
+            let mut graphemes: Vec<(usize, &str)> =
+                self.rows[y].as_str().graphemes(true).enumerate().collect();
+            if y == at.y {
+                graphemes.retain(|(x, _)| if forward { *x >= at.x } else { *x <= at.x });
+            }
+            if !forward {
+                graphemes.reverse();
+            }
+            graphemes.into_iter().find_map(|(x, grapheme)| {
+                if grapheme == away {
+                    depth = depth.saturating_add(1);
+                } else if grapheme == toward {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        return Some(Position { x, y });
+                    }
+                }
+                None
+            })
+        })
+    }
+    pub fn delete(&mut self, at: &Position, window: Duration) {
+        let Some(row) = self.rows.get(at.y) else {
+            return;
+        };
+        let text = if let Some(grapheme) = row.grapheme(at.x) {
+            grapheme.to_string()
+        } else if at.y.saturating_add(1) < self.len() {
+            // deleting at the end of a row joins it with the next one
+            "\n".to_string()
+        } else {
             return;
+        };
+        let at = self.clamp(at);
+        self.execute_typed(vec![Operation::Delete { at, text }], window);
+    }
+    // the text between two positions, rows joined by "\n"
+    pub fn text(&self, start: &Position, end: &Position) -> String {
+        let start = self.clamp_end(start);
+        let end = self.clamp_end(end);
+        let mut lines = Vec::new();
+        for y in start.y..=end.y {
+            let Some(row) = self.rows.get(y) else {
+                break;
+            };
+            let from = if y == start.y { start.x } else { 0 };
+            let to = if y == end.y { end.x } else { row.len() };
+            lines.push(row.substring(from, to));
         }
-        let current_row = &mut self.rows[at.y];
-        let new_row = current_row.split(at.x);
-        #[allow(clippy::arithmetic_side_effects)]
-        self.rows.insert(at.y + 1, new_row)
+        lines.join("\n")
     }
-    #[allow(clippy::arithmetic_side_effects)]
-    pub fn delete(&mut self, at: &Position) {
-        self.dirty = true;
-        let len = self.len();
-        if at.y >= len {
+    pub fn delete_range(&mut self, start: &Position, end: &Position) {
+        let text = self.text(start, end);
+        if text.is_empty() {
             return;
         }
-        if at.x == self.rows.get_mut(at.y).unwrap().len() && at.y + 1 < len {
-            let next_row = self.rows.remove(at.y + 1);
-            let row = self.rows.get_mut(at.y).unwrap();
-            row.append(&next_row);
-        } else {
-            let row = self.rows.get_mut(at.y).unwrap();
-            row.delete(at.x);
+        let at = self.clamp_end(start);
+        self.execute(vec![Operation::Delete { at, text }]);
+    }
+    // puts `text` in place of rows `first` to `last` as one undoable step,
+    // returning the position just after it
+    pub fn replace_rows(&mut self, first: usize, last: usize, text: &str) -> Position {
+        let at = Position { x: 0, y: first };
+        let end = Position {
+            x: self.rows.get(last).map_or(0, Row::len),
+            y: last,
+        };
+        let old = self.text(&at, &end);
+        let mut step = Vec::new();
+        if !old.is_empty() {
+            step.push(Operation::Delete { at, text: old });
+        }
+        step.push(Operation::Insert {
+            at,
+            text: text.to_string(),
+        });
+        self.execute(step)
+    }
+    // sorts rows `first` to `last` as one undoable step, false if they were
+    // in order already
+    pub fn sort_rows(&mut self, first: usize, last: usize, order: Order) -> bool {
+        let Some(rows) = self.rows.get(first..=last) else {
+            return false;
+        };
+        let old: Vec<&str> = rows.iter().map(Row::as_str).collect();
+        let mut sorted = old.clone();
+        match order {
+            Order::Ascending => sorted.sort_unstable(),
+            Order::Descending => sorted.sort_unstable_by(|a, b| b.cmp(a)),
+            Order::Numeric => {
+                static NUMBER: OnceLock<Regex> = OnceLock::new();
+                let number = NUMBER
+                    .get_or_init(|| Regex::new(r"-?\d+(\.\d+)?").expect("invalid number pattern"));
+                // each row's number found once, not at every comparison
+                let mut keyed: Vec<(f64, &str)> = old
+                    .iter()
+                    .map(|row| {
+                        let key = number
+                            .find(row)
+                            .and_then(|found| found.as_str().parse::<f64>().ok())
+                            .unwrap_or(f64::NEG_INFINITY);
+                        (key, *row)
+                    })
+                    .collect();
+                keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+                sorted = keyed.into_iter().map(|(_, row)| row).collect();
+            }
+        }
+        if sorted == old {
+            return false;
+        }
+        let text = sorted.join("\n");
+        self.replace_rows(first, last, &text);
+        true
+    }
+    // drops each of rows `first` to `last` that is the same as the one
+    // before it, as one undoable step, returning how many went
+    pub fn dedupe_rows(&mut self, first: usize, last: usize) -> usize {
+        let Some(rows) = self.rows.get(first..=last) else {
+            return 0;
+        };
+        let mut kept: Vec<&str> = rows.iter().map(Row::as_str).collect();
+        let count = kept.len();
+        kept.dedup();
+        let removed = count.saturating_sub(kept.len());
+        if removed > 0 {
+            let text = kept.join("\n");
+            self.replace_rows(first, last, &text);
         }
+        removed
+    }
+    // moves rows `first` to `last` past the row above or below them, as one
+    // undoable step, false at the top or bottom of the document
+    pub fn move_rows(&mut self, first: usize, last: usize, up: bool) -> bool {
+        let (from, to) = if up {
+            let Some(above) = first.checked_sub(1) else {
+                return false;
+            };
+            (above, last)
+        } else {
+            (last.saturating_add(1), first)
+        };
+        let Some(row) = self.rows.get(from).filter(|_| last < self.len()) else {
+            return false;
+        };
+        let text = row.as_str().to_string();
+        self.execute(vec![
+            Operation::DeleteRow {
+                at: from,
+                text: text.clone(),
+            },
+            Operation::InsertRow { at: to, text },
+        ]);
+        true
     }
     pub fn delete_row(&mut self, at: usize) {
-        self.dirty = true;
-        self.rows.remove(at);
+        let Some(row) = self.rows.get(at) else {
+            return;
+        };
+        let text = row.as_str().to_string();
+        self.execute(vec![Operation::DeleteRow { at, text }]);
+    }
+    pub fn insert_row(&mut self, row: &Row, at: usize) {
+        if at > self.len() {
+            return;
+        }
+        let text = row.as_str().to_string();
+        self.execute(vec![Operation::InsertRow { at, text }]);
     }
-    pub fn insert_row(&mut self, row: Row, at: usize) {
+    // reverts the last edit, returning where it happened
+    pub fn undo(&mut self) -> Option<Position> {
+        let step = self.history.undo()?;
+        Some(self.replay(&step))
+    }
+    pub fn redo(&mut self) -> Option<Position> {
+        let step = self.history.redo()?;
+        Some(self.replay(&step))
+    }
+    // to state `state` of the undo tree, see History::jump
+    pub fn undo_to(&mut self, state: usize) -> Option<Position> {
+        let step = self.history.jump(state)?;
+        Some(self.replay(&step))
+    }
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+    fn execute(&mut self, step: Vec<Operation>) -> Position {
+        let position = self.replay(&step);
+        self.history.record(step);
+        position
+    }
+    fn execute_typed(&mut self, step: Vec<Operation>, window: Duration) {
+        self.replay(&step);
+        self.history.record_typed(step, window);
+    }
+    fn replay(&mut self, step: &[Operation]) -> Position {
+        let mut position = Position::default();
+        for operation in step {
+            position = self.apply(operation);
+        }
         self.dirty = true;
-        self.rows.insert(at, row)
+        self.snapshot_due = true;
+        self.swap_due = true;
+        position
+    }
+    // performs one operation without recording it, returning the position
+    // just after the change
+    #[allow(clippy::arithmetic_side_effects)]
+    fn apply(&mut self, operation: &Operation) -> Position {
+        // the rows the operation replaces, from `first` on, and how many
+        // replace them
+        let (first, before, after) = match operation {
+            Operation::Insert { at, text } => (at.y, 1, 1 + text.matches('\n').count()),
+            Operation::Delete { at, text } => (at.y, 1 + text.matches('\n').count(), 1),
+            Operation::InsertRow { at, .. } => (*at, 0, 1),
+            Operation::DeleteRow { at, .. } => (*at, 1, 0),
+        };
+        let old = self
+            .counts
+            .map(|_| Counts::of(self.rows.iter().skip(first).take(before)));
+        let position = self.apply_uncounted(operation);
+        if let Some(old) = old {
+            let new = Counts::of(self.rows.iter().skip(first).take(after));
+            if let Some(counts) = &mut self.counts {
+                counts.replace(old, new);
+            }
+        }
+        position
+    }
+    // the operation itself, for `apply`
+    #[allow(clippy::arithmetic_side_effects)]
+    fn apply_uncounted(&mut self, operation: &Operation) -> Position {
+        match operation {
+            Operation::Insert { at, text } => {
+                let count = text.matches('\n').count();
+                self.notify(&[
+                    Change::Edited(at.y),
+                    Change::Inserted {
+                        at: at.y + 1,
+                        count,
+                    },
+                ]);
+                let end = self.splice_in(at, text);
+                if let Some(snippet) = &mut self.snippet {
+                    snippet.inserted(*at, end);
+                }
+                end
+            }
+            Operation::Delete { at, text } => {
+                let count = text.matches('\n').count();
+                self.notify(&[
+                    Change::Removed {
+                        at: at.y + 1,
+                        count,
+                    },
+                    Change::Edited(at.y),
+                ]);
+                if let Some(snippet) = &mut self.snippet {
+                    let last = text
+                        .rsplit('\n')
+                        .next()
+                        .unwrap_or_default()
+                        .graphemes(true)
+                        .count();
+                    let x = if count == 0 { at.x + last } else { last };
+                    snippet.deleted(*at, Position { x, y: at.y + count });
+                }
+                self.cut(at, text);
+                *at
+            }
+            Operation::InsertRow { at, text } => {
+                let change = Change::Inserted { at: *at, count: 1 };
+                self.notify(&[change]);
+                if let Some(snippet) = &mut self.snippet {
+                    snippet.shift_rows(|y| change.shift(y));
+                }
+                self.rows.insert(*at, Row::from(&text[..]));
+                Position { x: 0, y: *at }
+            }
+            Operation::DeleteRow { at, .. } => {
+                let change = Change::Removed { at: *at, count: 1 };
+                self.notify(&[change]);
+                if let Some(snippet) = &mut self.snippet {
+                    snippet.shift_rows(|y| change.shift(y));
+                }
+                self.rows.remove(*at);
+                // the row below was highlighted following the removed one
+                if let Some(row) = self.rows.get_mut(*at) {
+                    row.set_highlighted(None);
+                }
+                Position { x: 0, y: *at }
+            }
+        }
+    }
+    // the text is split into rows once and spliced in, so large pastes don't
+    // shift the rows below once per line
+    #[allow(clippy::arithmetic_side_effects)]
+    fn splice_in(&mut self, at: &Position, text: &str) -> Position {
+        let mut lines = text.split('\n');
+        let first = lines.next().unwrap_or_default();
+        let mut new_rows: Vec<Row> = lines.map(Row::from).collect();
+        let row = &mut self.rows[at.y];
+        let Some(last) = new_rows.last_mut() else {
+            let len = row.len();
+            row.insert_str(at.x, first);
+            return Position {
+                x: at.x + row.len() - len,
+                y: at.y,
+            };
+        };
+        let tail = row.split(at.x);
+        row.append(&Row::from(first));
+        let x = last.len();
+        last.append(&tail);
+        let y = at.y + new_rows.len();
+        let below = at.y + 1;
+        self.rows.splice(below..below, new_rows);
+        Position { x, y }
+    }
+    // removes `text`, which is known to start at `at`
+    #[allow(clippy::arithmetic_side_effects)]
+    fn cut(&mut self, at: &Position, text: &str) {
+        let lines: Vec<&str> = text.split('\n').collect();
+        let last_y = at.y + lines.len() - 1;
+        let last_line = lines.last().copied().unwrap_or_default();
+        let end_x = if lines.len() == 1 {
+            at.x + last_line.graphemes(true).count()
+        } else {
+            last_line.graphemes(true).count()
+        };
+        let tail = self.rows[last_y].split(end_x);
+        self.rows.drain(at.y + 1..=last_y);
+        let row = &mut self.rows[at.y];
+        row.split(at.x);
+        row.append(&tail);
+    }
+    fn clamp(&self, at: &Position) -> Position {
+        let x = self
+            .rows
+            .get(at.y)
+            .map_or(0, |row| cmp::min(at.x, row.len()));
+        Position { x, y: at.y }
+    }
+    // like clamp, but positions past the last row become the end of it
+    pub fn clamp_end(&self, at: &Position) -> Position {
+        match self.rows.last() {
+            Some(last) if at.y >= self.len() => Position {
+                x: last.len(),
+                y: self.len().saturating_sub(1),
+            },
+            _ => self.clamp(at),
+        }
+    }
+    // highlights the first `syntaxes.len()` rows, each in its syntax, where
+    // they haven't been yet or are out of date: when the row above ends in a
+    // different state than it did, say after typing an unterminated string
+    pub fn highlight(
+        &mut self,
+        syntaxes: &[&SyntaxReference],
+        ps: &SyntaxSet,
+        theme: &Theme,
+        max_len: usize,
+    ) {
+        let highlighter = Highlighter::new(theme);
+        let mut stale = false;
+        for (y, syntax) in syntaxes.iter().enumerate().take(self.rows.len()) {
+            let current = self.rows[y]
+                .highlighted()
+                .is_some_and(|highlighted| highlighted.scope == syntax.scope);
+            if current && !stale {
+                continue;
+            }
+            // a row in another syntax than the one above starts afresh
+            let before = y
+                .checked_sub(1)
+                .filter(|&above| syntaxes[above].scope == syntax.scope)
+                .and_then(|above| self.rows[above].highlighted())
+                .map(|highlighted| &highlighted.state);
+            let row = &self.rows[y];
+            let new = Highlighted::new(row, syntax, before, ps, &highlighter, max_len);
+            stale = row.highlighted().is_none_or(|old| old.state != new.state);
+            self.rows[y].set_highlighted(Some(new));
+        }
+    }
+    // after the theme changes
+    pub fn clear_highlighting(&mut self) {
+        for row in &mut self.rows {
+            row.set_highlighted(None);
+        }
+    }
+    // starts collecting the changes made from now on, for `changes`, until
+    // the subscriber gives up its subscription
+    pub fn subscribe(&mut self) -> Subscription {
+        if let Some(free) = self.subscribers.iter().position(Option::is_none) {
+            self.subscribers[free] = Some(Vec::new());
+            return Subscription(free);
+        }
+        self.subscribers.push(Some(Vec::new()));
+        Subscription(self.subscribers.len().saturating_sub(1))
+    }
+    pub fn unsubscribe(&mut self, subscription: Subscription) {
+        if let Some(queue) = self.subscribers.get_mut(subscription.0) {
+            *queue = None;
+        }
+    }
+    // the changes since the last call, oldest first
+    pub fn changes(&mut self, subscription: Subscription) -> Vec<Change> {
+        self.subscribers
+            .get_mut(subscription.0)
+            .and_then(Option::as_mut)
+            .map(mem::take)
+            .unwrap_or_default()
+    }
+    fn notify(&mut self, changes: &[Change]) {
+        for mark in self.marks.values_mut() {
+            for change in changes {
+                mark.y = change.shift(mark.y);
+            }
+        }
+        for diagnostic in &mut self.diagnostics {
+            for change in changes {
+                diagnostic.start.y = change.shift(diagnostic.start.y);
+                diagnostic.end.y = change.shift(diagnostic.end.y);
+            }
+        }
+        for queue in self.subscribers.iter_mut().flatten() {
+            queue.extend(changes.iter().filter(|change| {
+                !matches!(
+                    change,
+                    Change::Inserted { count: 0, .. } | Change::Removed { count: 0, .. }
+                )
+            }));
+        }
+    }
+    pub fn set_mark(&mut self, name: char, at: Position) {
+        self.marks.insert(name, at);
+    }
+    // where the mark is, kept to the text there is now
+    pub fn mark(&self, name: char) -> Option<Position> {
+        self.marks.get(&name).map(|at| self.clamp_end(at))
+    }
+    // the first of the marks on row `y`, for the gutter
+    pub fn mark_on(&self, y: usize) -> Option<char> {
+        self.marks
+            .iter()
+            .find(|(_, at)| at.y == y)
+            .map(|(name, _)| *name)
+    }
+    pub fn has_marks(&self) -> bool {
+        !self.marks.is_empty()
+    }
+    // what check `checked` found in the file, its columns counted in
+    // characters, as rustc counts them, until they are made graphemes here
+    pub fn set_diagnostics(&mut self, checked: usize, mut diagnostics: Vec<Diagnostic>) {
+        let graphemes = |rows: &[Row], at: &mut Position| {
+            if let Some(row) = rows.get(at.y) {
+                let text = row.as_str();
+                let end = text
+                    .char_indices()
+                    .nth(at.x)
+                    .map_or(text.len(), |(index, _)| index);
+                at.x = text[..end].graphemes(true).count();
+            }
+        };
+        for diagnostic in &mut diagnostics {
+            graphemes(&self.rows, &mut diagnostic.start);
+            graphemes(&self.rows, &mut diagnostic.end);
+        }
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.start.y, diagnostic.start.x));
+        self.diagnostics = diagnostics;
+        self.checked = checked;
+    }
+    pub fn checked(&self) -> usize {
+        self.checked
+    }
+    // in the order they are in the file
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+    // the worst of those starting on row `y`, for the gutter
+    pub fn diagnostic_on(&self, y: usize) -> Option<Severity> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.start.y == y)
+            .map(|diagnostic| diagnostic.severity)
+            .min()
+    }
+    // the worst of those the cursor at `at` is on
+    pub fn diagnostic_at(&self, at: Position) -> Option<&Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.covers(at))
+            .min_by_key(|diagnostic| diagnostic.severity)
+    }
+    pub fn counts(&mut self) -> Counts {
+        *self
+            .counts
+            .get_or_insert_with(|| Counts::of(self.rows.iter()))
     }
     pub fn row(&self, index: usize) -> Option<&Row> {
         self.rows.get(index)
@@ -102,15 +1307,194 @@ impl Document {
     pub fn len(&self) -> usize {
         self.rows.len()
     }
-    pub fn save(&mut self) -> Result<(), Error> {
+    pub fn needs_snapshot(&self) -> bool {
+        self.dirty && self.snapshot_due
+    }
+    // copies the text into the recovery directory, leaving the file alone
+    pub fn snapshot(&mut self) -> Result<(), Error> {
+        recovery::save(self.file_name.as_deref(), &self.contents())?;
+        self.snapshot_due = false;
+        Ok(())
+    }
+    pub fn needs_swap(&self) -> bool {
+        self.dirty
+            && self.swap_due
+            && self.file_name.is_some()
+            && matches!(self.swap, Swap::None | Swap::Written)
+    }
+    pub fn write_swap(&mut self) -> Result<(), Error> {
+        let Some(file_name) = &self.file_name else {
+            return Ok(());
+        };
+        swap::write(file_name, &self.contents())?;
+        self.swap = Swap::Written;
+        self.swap_due = false;
+        Ok(())
+    }
+    // removes the swap file written for edits that are saved or given up
+    pub fn remove_swap(&mut self) -> Result<(), Error> {
+        if self.swap == Swap::Written {
+            self.discard_swap()?;
+        }
+        Ok(())
+    }
+    fn contents(&self) -> String {
+        let mut contents = String::new();
+        for row in &self.rows {
+            contents.push_str(row.as_str());
+            contents.push('\n');
+        }
+        contents
+    }
+    // writes the file after what `on_save` asks for, returning how many
+    // rows that cleaned up
+    pub fn save(&mut self, policy: &Policy, on_save: OnSave) -> Result<usize, Error> {
+        let mut cleaned = 0;
+        if self.file_name.is_some() {
+            // a project that wants no final newline has the last word
+            let final_newline = on_save.final_newline && policy.final_newline != Some(false);
+            cleaned = self.clean(on_save.trim_trailing_whitespace, final_newline);
+            if final_newline {
+                self.no_final_newline = false;
+            }
+        }
         if let Some(file_name) = &self.file_name {
-            let mut file = fs::File::create(file_name)?;
-            for row in &self.rows {
-                file.write_all(row.as_bytes())?;
-                file.write(b"\n")?;
+            let ending = policy.line_ending.unwrap_or(self.line_ending).as_str();
+            let final_newline = policy.final_newline.unwrap_or(!self.no_final_newline);
+            let mut text = String::new();
+            for (index, row) in self.rows.iter().enumerate() {
+                text.push_str(row.as_str());
+                if final_newline || index.saturating_add(1) < self.rows.len() {
+                    text.push_str(ending);
+                }
             }
+            // encoded first, so text the encoding can't hold leaves the file
+            // as it was
+            let bytes = encoding::encode(&text, self.encoding)?;
+            fs::File::create(file_name)?.write_all(&bytes)?;
             self.dirty = false;
+            self.disk_conflict = false;
+            self.modified = modified_time(file_name);
         }
-        Ok(())
+        Ok(cleaned)
+    }
+    // takes spaces and tabs off the ends of rows and blank rows off the end
+    // of the document, as one undoable step, returning how many rows changed
+    fn clean(&mut self, trim: bool, blank_rows: bool) -> usize {
+        let trailing = |row: &Row| {
+            let text = row.as_str();
+            text.len()
+                .saturating_sub(text.trim_end_matches([' ', '\t']).len())
+        };
+        let blank = if blank_rows {
+            self.rows
+                .iter()
+                .rev()
+                .take_while(|row| trailing(row) == row.as_str().len())
+                .count()
+        } else {
+            0
+        };
+        let kept = self.rows.len().saturating_sub(blank);
+        let mut step = Vec::new();
+        if trim {
+            for (y, row) in self.rows[..kept].iter().enumerate() {
+                let spaces = trailing(row);
+                if spaces == 0 {
+                    continue;
+                }
+                let text = &row.as_str()[row.as_str().len().saturating_sub(spaces)..];
+                let x = row.len().saturating_sub(text.graphemes(true).count());
+                step.push(Operation::Delete {
+                    at: Position { x, y },
+                    text: text.to_string(),
+                });
+            }
+        }
+        let cleaned = step.len().saturating_add(blank);
+        for at in (kept..self.rows.len()).rev() {
+            let text = self.rows[at].as_str().to_string();
+            step.push(Operation::DeleteRow { at, text });
+        }
+        if !step.is_empty() {
+            self.execute(step);
+        }
+        cleaned
+    }
+}
+
+fn replacement_step(row: &Row, y: usize, range: Range<usize>, with: String) -> Vec<Operation> {
+    let at = Position { x: range.start, y };
+    let mut step = Vec::new();
+    let text = row.substring(range.start, range.end);
+    if !text.is_empty() {
+        step.push(Operation::Delete { at, text });
     }
+    if !with.is_empty() {
+        step.push(Operation::Insert { at, text: with });
+    }
+    step
+}
+
+// reads the first rows of `filename` and leaves the rest to a thread
+fn open_lazily(filename: &str, size: u64) -> Result<(Vec<Row>, Loading), Error> {
+    let mut lines = BufReader::new(fs::File::open(filename)?).lines();
+    let mut rows = Vec::new();
+    let mut read: u64 = 0;
+    for line in lines.by_ref().take(FIRST_ROWS) {
+        let line = line?;
+        read = read.saturating_add(line_bytes(&line));
+        rows.push(Row::from(line.as_str()));
+    }
+    let (sender, batches) = mpsc::channel();
+    thread::spawn(move || loop {
+        let mut batch = Vec::with_capacity(BATCH_ROWS);
+        let mut bytes: u64 = 0;
+        for line in lines.by_ref().take(BATCH_ROWS) {
+            match line {
+                Ok(line) => {
+                    bytes = bytes.saturating_add(line_bytes(&line));
+                    batch.push(line);
+                }
+                Err(error) => {
+                    let _ = sender.send(Err(error));
+                    return;
+                }
+            }
+        }
+        // an empty batch is the end of the file, a failed send a document
+        // that was closed
+        if batch.is_empty() || sender.send(Ok((batch, bytes))).is_err() {
+            return;
+        }
+    });
+    Ok((
+        rows,
+        Loading {
+            batches,
+            size,
+            read,
+        },
+    ))
+}
+
+// a line's share of the file, counting its newline
+fn line_bytes(line: &str) -> u64 {
+    u64::try_from(line.len())
+        .unwrap_or(u64::MAX)
+        .saturating_add(1)
+}
+
+fn ends_with_newline(filename: &str) -> Result<bool, Error> {
+    let mut file = fs::File::open(filename)?;
+    file.seek(SeekFrom::End(-1))?;
+    let mut last = [0];
+    file.read_exact(&mut last)?;
+    Ok(last == [b'\n'])
+}
+
+fn modified_time(filename: &str) -> Option<SystemTime> {
+    fs::metadata(filename)
+        .and_then(|metadata| metadata.modified())
+        .ok()
 }
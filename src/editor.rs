@@ -1,38 +1,249 @@
+use crate::bidi;
+use crate::buffer::{Buffer, Closed};
+use crate::command_line::{self, Action};
+use crate::config::{self, Command, Config, LineNumbers, WrapAlign};
+use crate::diagnostics::{self, Diagnostic, Severity};
+use crate::document::{Order, Subscription};
+use crate::file_tree::FileTree;
+use crate::help;
+use crate::highlighting;
+use crate::indent::Indent;
+use crate::index::Index;
+use crate::keys;
+use crate::language;
+use crate::messages::{self, message};
+use crate::outline::Outline;
+use crate::pane::{self, Area, Direction, Pane, Split};
+use crate::paths;
+use crate::policy::{LineEnding, Policy};
+use crate::project_search::{self, Edit};
+use crate::quickfix::{self, Location};
+use crate::recovery;
+use crate::reflow;
+use crate::render;
+use crate::row;
+use crate::search::{self, SearchState};
+use crate::session::{self, Entry, Session};
+use crate::snippets;
+use crate::spell;
+use crate::swap;
+use crate::symbols;
+use crate::syntax;
+use crate::tags::{self, Tags};
+use crate::terminal::{Input, Modified};
+use crate::tutor::{self, Tutor};
+use crate::ui::{self, UiTheme};
+use crate::unicode;
+use crate::variables;
+use crate::watch::{self, Watch};
 use crate::Document;
 use crate::Row;
 use crate::Terminal;
+use regex::Regex;
+use std::cmp;
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::env;
+use std::fs;
+use std::iter;
+use std::mem;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
 use std::time::Duration;
 use std::time::Instant;
+use syntect::highlighting::{Style, Theme, ThemeSet};
+use syntect::parsing::{SyntaxSet, SyntaxSetBuilder};
 use termion::color;
-use termion::event::Key;
-use syntect::easy::HighlightLines;
-use syntect::parsing::SyntaxSet;
-use syntect::highlighting::{ThemeSet, Style};
-use syntect::util::as_24_bit_terminal_escaped;
+use termion::event::{Key, MouseButton, MouseEvent};
+use termion::style;
+use unicode_segmentation::UnicodeSegmentation;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
-const STATUS_BG_COLOR: color::Rgb = color::Rgb(239, 239, 239);
-const STATUS_FG_COLOR: color::Rgb = color::Rgb(63, 63, 63);
+const LONG_LINE_COLOR: color::Rgb = color::Rgb(204, 102, 0);
+// how long to wait for a key before checking on the file again
+const INPUT_POLL: Duration = Duration::from_millis(250);
+const ANIMATION_FRAME: Duration = Duration::from_millis(16);
+// how long each turn of the loop spends taking in rows of a file that is
+// still loading
+const LOAD_BUDGET: Duration = Duration::from_millis(10);
 
-// this is pretty cool i think something
+// the syntaxes, the themes and what went wrong loading them
+type Assets = (SyntaxSet, ThemeSet, Option<String>);
+// rows scrolled per turn of the mouse wheel
+const WHEEL_ROWS: usize = 3;
+// how many closed buffers can be reopened
+const CLOSED_BUFFERS: usize = 20;
+// a frame taking longer than this on average means a slow terminal, and
+// one taking less than the second a terminal that is fast again
+const SLOW_FRAME: Duration = Duration::from_millis(50);
+const FAST_FRAME: Duration = Duration::from_millis(10);
+// how often a slow terminal redraws while the editor is idle
+const SLOW_IDLE_REDRAW: Duration = Duration::from_secs(1);
+// how often edited documents are copied to the recovery directory
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30);
+// how often the swap files of edited documents are brought up to date
+const SWAP_INTERVAL: Duration = Duration::from_secs(1);
+// how often the open files are looked at for changes made to them outside,
+// where they can't be watched
+const DISK_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+const LINE_NUMBER_COLOR: color::Rgb = color::Rgb(101, 115, 126);
+// how much of the file name the status bar keeps before dropping other details
+const MIN_FILE_NAME_WIDTH: usize = 12;
+// how many rows away the pair of a bracket is looked for, so one left open
+// in a long file doesn't scan all of it on every keypress
+const BRACKET_SCAN_ROWS: usize = 1000;
+// how wide the file tree is, at most a third of the screen
+const TREE_WIDTH: usize = 28;
+// and the outline on the right
+const OUTLINE_WIDTH: usize = 28;
+// how many places JumpBack can go back through
+const JUMPS: usize = 100;
+
+// without `modal` in the config only Insert and MoveRows are used
+#[derive(Clone, Copy, PartialEq)]
 enum EditorMode {
+    // keys are commands, as in vim
     Normal,
-    CtrlXPressed,
+    // an operator key was pressed and waits for the second one, as in `dd`
+    Pending(char),
+    Insert,
+    // like Normal, but moving the cursor selects
+    Visual,
+    // after Ctrl-X the up and down arrows move the cursor's row, or the
+    // rows of a selection made with Shift and the arrows
+    MoveRows,
 }
+#[allow(clippy::struct_excessive_bools)]
 pub struct Editor {
     should_quit: bool,
     terminal: Terminal,
+    // what the window title was last set to
+    title: String,
+    // the row the message bar last said, in screen reader mode
+    announced: Option<usize>,
+    // the row of the closers auto-close put in, and how far each is from
+    // the end of the row, which typing in front of it doesn't change
+    auto_closed: (usize, Vec<usize>),
     cursor_position: Position,
     offset: Position,
     document: Document,
     status_message: StatusMessage,
     mode: EditorMode,
+    // the settings of the current buffer, see Config::layered
+    config: Config,
+    // the ones as loaded and changed by `:set`, which `config` is layered
+    // over
+    global_config: Config,
+    // where a smooth scroll is heading
+    scroll_target: Option<usize>,
+    // the other end of the selection, the cursor being the first
+    selection_anchor: Option<Position>,
+    clipboard: String,
+    // the other open documents, the next one first
+    buffers: VecDeque<Buffer>,
+    // the most recently closed last
+    closed: Vec<Closed>,
+    split: Option<Split>,
+    // for shrinking: the selections before and after each expansion, the
+    // latest last
+    expansions: Vec<((Position, Position), (Position, Position))>,
+    // matches of the search being typed, highlighted on screen
+    search_highlight: Option<Regex>,
+    // the last search, which FindNext and FindPrevious go on with
+    search: Option<SearchState>,
+    // the queries searched for before, for the search prompt to recall
+    search_history: search::History,
+    last_snapshot: Instant,
+    // None until the open files are first watched, an error where they
+    // can't be and are looked at every DISK_CHECK_INTERVAL instead
+    watch: Option<Result<Watch, String>>,
+    last_disk_check: Instant,
+    last_swap: Instant,
+    // what the theme prompt accepts
+    theme_names: Vec<String>,
+    // how long drawing a frame takes, averaged
+    frame_time: Duration,
+    last_draw: Instant,
+    // drawing less for a slow terminal: no highlighting and a plainer
+    // status bar
+    slow: bool,
+    // the theme's colors for text without highlighting, and for comments
+    plain: Style,
+    comment: Style,
+    ui: UiTheme,
+    // the syntaxes and themes, placeholders until `assets` comes in
+    ps: Arc<SyntaxSet>,
+    ts: Arc<ThemeSet>,
+    assets: Option<Receiver<Assets>>,
+    // for following the document's changes
+    subscription: Subscription,
+    // the status bar's scope, for a row and language, until a change above
+    scope: Option<(usize, String, Vec<String>)>,
+    // how far along the tutorial is, with `--tutor`
+    tutor: Option<Tutor>,
+    // what Locations found, and the one last gone to
+    locations: Vec<Location>,
+    location: usize,
+    // the tags file once it is needed, read again when it changes
+    tags: Option<Tags>,
+    // the project's files and declarations, walked once something asks
+    // for them
+    index: Option<Index>,
+    // the places jumps to tags left, the last one last
+    jumps: Vec<(String, Position)>,
+    // the check running on a thread, what the last one found in each file,
+    // and how many there have been, each document knowing which one its
+    // diagnostics are from
+    checking: Option<Receiver<diagnostics::Found>>,
+    checked: Vec<(String, Diagnostic)>,
+    checks: usize,
+    // lines the config takes for secrets are drawn masked
+    redact: bool,
+    // what spell checking goes by, loaded once it is turned on
+    dictionary: Option<spell::Dictionary>,
+    // the word SpellSuggest last put in, for the next press to go on with
+    spelling: Option<Spelling>,
+    // drawn to the left of the panes while it is shown
+    file_tree: Option<FileTree>,
+    // what the file tree moved to the trash, each from where it was, the
+    // last last
+    deleted: Vec<(PathBuf, PathBuf)>,
+    outline: Option<Outline>,
+    // quit with unsaved changes thrown away, which tools running the editor
+    // see as a nonzero exit status
+    aborted: bool,
+    // --batch: the keys given with --keys and none from the terminal,
+    // nothing drawn, and quitting once they are done. Where the editor would
+    // ask something or say something went wrong it quits instead, with
+    // `failure` printed and a nonzero exit status.
+    batch: bool,
+    failure: Option<String>,
+}
+
+// a misspelled word at `start` of row `y` and the suggestions for it, the
+// one at `index` being in the row now, or the word itself after the last
+struct Spelling {
+    y: usize,
+    start: usize,
+    word: String,
+    suggestions: Vec<String>,
+    index: usize,
+}
+
+impl Spelling {
+    fn current(&self) -> &str {
+        self.suggestions.get(self.index).unwrap_or(&self.word)
+    }
 }
 
 struct StatusMessage {
     text: String,
     time: Instant,
+    // drawn in bold reverse video, so it doesn't rest on the words alone
+    error: bool,
 }
 
 impl StatusMessage {
@@ -40,299 +251,3670 @@ impl StatusMessage {
         Self {
             text: message,
             time: Instant::now(),
+            error: false,
+        }
+    }
+    fn error(message: String) -> Self {
+        Self {
+            error: true,
+            ..Self::from(message)
         }
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
 }
 
 impl Editor {
-    pub fn run(&mut self) {
-        let ps = SyntaxSet::load_defaults_newlines();
-        let ts = ThemeSet::load_defaults();
+    // whether the editor quit without throwing away changes
+    pub fn run(&mut self) -> bool {
+        let mut changed = true;
+        if !self.batch {
+            Terminal::save_title();
+        }
         loop {
-            // this is so the screen is refreshed every time the loop runs
-            if let Err(error) = self.refresh_screen(&ps, &ts) {
-                die(&error);
+            if self.receive_assets()
+                || self.receive_diagnostics()
+                || self.index.as_mut().is_some_and(Index::receive)
+            {
+                changed = true;
+            }
+            self.attach_diagnostics();
+            self.follow_changes();
+            if self.config.spell_check && self.dictionary.is_none() {
+                self.load_dictionary();
+                changed = true;
+            }
+            if self.document.found_swap() {
+                if let Err(error) = self.offer_swap() {
+                    die(&error);
+                }
+                changed = true;
+            }
+            let (ps, ts) = (Arc::clone(&self.ps), Arc::clone(&self.ts));
+            // this is so the screen is refreshed every time the loop runs,
+            // except on a slow terminal, where an idle editor only redraws
+            // now and then, and for a screen reader, which would read the
+            // same screen again
+            let due = self.last_draw.elapsed() >= SLOW_IDLE_REDRAW && !self.config.screen_reader;
+            let idle_redraw = !self.slow && !self.config.screen_reader;
+            if changed || idle_redraw || due || self.should_quit {
+                if let Err(error) = self.refresh_screen(&ps, &ts) {
+                    die(&error);
+                }
             }
             if self.should_quit {
                 break;
             }
-            if let Err(error) = self.process_keypress() {
-                die(&error);
+            if self.batch && self.status_message.error {
+                let failure = self.status_message.text.clone();
+                self.fail(failure);
+            }
+            changed = match self.process_keypress() {
+                Ok(changed) => changed,
+                Err(error) if self.batch => {
+                    self.fail(error.to_string());
+                    true
+                }
+                Err(error) => {
+                    die(&error);
+                    true
+                }
+            };
+            if self.load() {
+                changed = true;
             }
+            self.check_disk();
+            self.snapshot();
+            self.write_swaps();
+        }
+        if !self.batch {
+            self.save_last_session();
+        }
+        // an exit that doesn't get this far leaves the swap files for
+        // offer_swap to find next time
+        for document in iter::once(&mut self.document)
+            .chain(self.buffers.iter_mut().map(|buffer| &mut buffer.document))
+        {
+            // nothing more can be done about one that can't be removed
+            document.remove_swap().ok();
+        }
+        if let Some(failure) = &self.failure {
+            eprintln!("{failure}");
+        } else if !self.batch {
+            Terminal::restore_title();
+            Terminal::flush().ok();
+        }
+        !self.aborted
+    }
+    // ends a --batch run that can't go on, keeping the first reason
+    fn fail(&mut self, failure: String) {
+        self.failure.get_or_insert(failure);
+        self.aborted = true;
+        self.should_quit = true;
+    }
+    // what a prompt does under --batch, which never waits for an answer
+    fn refuse_prompt(&mut self, question: &str) -> std::io::Error {
+        let failure = message!("batch_prompt", question = question.trim());
+        self.fail(failure.clone());
+        std::io::Error::other(failure)
+    }
+    // swaps in the syntaxes and themes once the thread loading them is done,
+    // true if it just did
+    fn receive_assets(&mut self) -> bool {
+        let Some(Ok((ps, ts, error))) = self.assets.as_ref().map(Receiver::try_recv) else {
+            return false;
+        };
+        self.assets = None;
+        if let Some(error) = error {
+            self.status_message = StatusMessage::error(error);
+        } else if !ts.themes.contains_key(&self.config.theme) {
+            self.status_message =
+                StatusMessage::error(message!("unknown_theme", theme = self.config.theme));
         }
+        self.theme_names = ts.themes.keys().cloned().collect();
+        self.ps = Arc::new(ps);
+        self.ts = Arc::new(ts);
+        // what was worked out with plain text only is looked at again
+        for document in iter::once(&mut self.document)
+            .chain(self.buffers.iter_mut().map(|buffer| &mut buffer.document))
+        {
+            document.syntax = None;
+            document.clear_highlighting();
+        }
+        true
     }
     pub fn default() -> Self {
-        let args: Vec<String> = env::args().collect();
-        let mut initial_status =
-            String::from("HELP: Ctrl-F = find | Ctrl-S = save | Ctrl-C = quit");
-        let document = if args.len() > 1 {
-            let file_name = &args[1];
-            let doc = Document::open(file_name);
-            if let Ok(doc) = doc {
-                doc
-            } else {
-                initial_status = format!("ERR: Could not open file: {file_name}");
-                Document::default()
-            }
+        let args: Vec<String> = env::args().skip(1).collect();
+        let (mut config, config_error) = Config::load();
+        let locale_error = messages::init(&config);
+        let mut initial_status = config_error
+            .or(locale_error)
+            .map_or_else(|| StatusMessage::from(help(&config)), StatusMessage::error);
+        let (mut buffers, tutor, keys) = open_args(&args, &mut config, &mut initial_status);
+        let assets = load_assets();
+        let (ps, ts) = placeholder_assets();
+        let ui = load_ui_theme(&config.ui_theme, &config).unwrap_or_else(|error| {
+            initial_status = StatusMessage::error(message!("error", error = error));
+            load_ui_theme(ui::DEFAULT, &config).unwrap_or_default()
+        });
+        let first = buffers
+            .pop_front()
+            .unwrap_or_else(|| Buffer::from(Document::default()));
+        let mut document = first.document;
+        let subscription = document.subscribe();
+        if document.is_commit_message() {
+            initial_status = StatusMessage::from(message!("commit_message"));
+        }
+        let batch = args.iter().any(|arg| arg == "--batch");
+        let mut terminal = if batch {
+            Terminal::headless()
         } else {
-            Document::default()
+            Terminal::default().expect("failed to initialize terminal")
         };
-        Self {
+        terminal.inject(&keys);
+        let mut editor = Self {
             should_quit: false,
-            terminal: Terminal::default().expect("failed to initialize terminal"),
-            cursor_position: Position::default(),
-            offset: Position::default(),
+            terminal,
+            title: String::new(),
+            announced: None,
+            auto_closed: (0, Vec::new()),
+            cursor_position: first.cursor_position,
+            offset: first.offset,
             document,
-            status_message: StatusMessage::from(initial_status),
-            mode: EditorMode::Normal,
-        }
+            status_message: initial_status,
+            mode: if config.modal {
+                EditorMode::Normal
+            } else {
+                EditorMode::Insert
+            },
+            global_config: config.clone(),
+            config,
+            scroll_target: None,
+            selection_anchor: None,
+            clipboard: String::new(),
+            buffers,
+            closed: Vec::new(),
+            split: None,
+            expansions: Vec::new(),
+            search_highlight: None,
+            search: None,
+            search_history: search::History::load(),
+            last_snapshot: Instant::now(),
+            watch: None,
+            last_disk_check: Instant::now(),
+            last_swap: Instant::now(),
+            theme_names: Vec::new(),
+            frame_time: Duration::ZERO,
+            last_draw: Instant::now(),
+            slow: false,
+            plain: Style::default(),
+            comment: Style::default(),
+            aborted: false,
+            batch,
+            failure: None,
+            redact: false,
+            dictionary: None,
+            spelling: None,
+            file_tree: None,
+            deleted: Vec::new(),
+            outline: None,
+            ui,
+            ps: Arc::new(ps),
+            ts: Arc::new(ts),
+            assets: Some(assets),
+            subscription,
+            scope: None,
+            tutor,
+            locations: Vec::new(),
+            location: 0,
+            tags: None,
+            index: None,
+            jumps: Vec::new(),
+            checking: None,
+            checked: Vec::new(),
+            checks: 0,
+        };
+        editor.layer_config();
+        editor.clamp_cursor();
+        editor
     }
     fn refresh_screen(&mut self, ps: &SyntaxSet, ts: &ThemeSet) -> Result<(), std::io::Error> {
+        if self.batch {
+            return Ok(());
+        }
+        if self.terminal.refresh_size() {
+            Terminal::clear_screen();
+            self.scroll();
+        }
+        if !self.should_quit {
+            self.update_title();
+            syntax::remember(&mut self.document, ps);
+            self.update_outline(ps);
+            self.plain = highlighting::plain(self.theme(ts));
+            self.comment = highlighting::comment(self.theme(ts));
+            if !self.slow && self.assets.is_none() {
+                self.highlight(ps, ts);
+            }
+        }
+        // only the drawing is timed, highlighting a lot of text for the
+        // first time is slow on any terminal
+        let started = Instant::now();
         Terminal::cursor_hide();
         Terminal::cursor_position(&Position::default());
         if self.should_quit {
             Terminal::clear_screen();
-            println!("Goodbye.\r");
+            println!("{}\r", message!("goodbye"));
         } else {
-            self.draw_rows(ps, ts);
-            self.draw_status_bar();
-            self.draw_message_bar();
-            let x = self.cursor_position.x.saturating_sub(self.offset.x);
-            let y = self.cursor_position.y.saturating_sub(self.offset.y);
-            Terminal::cursor_position(&Position { x, y });
+            self.draw_rows(ts);
+            let size = self.terminal.size();
+            let (status_bar, message_bar) = (size.status_bar, size.message_bar);
+            let mut bar = usize::from(size.height);
+            if status_bar {
+                Terminal::cursor_position(&Position { x: 0, y: bar });
+                self.draw_status_bar(ps, ts);
+                bar = bar.saturating_add(1);
+            }
+            if message_bar {
+                Terminal::cursor_position(&Position { x: 0, y: bar });
+                self.draw_message_bar();
+            }
+            let area = self.text_area();
+            // on the selected entry while the file tree or outline has the
+            // keys
+            let tree = self.file_tree.as_ref().filter(|tree| tree.focused);
+            let outline = self.outline.as_ref().filter(|outline| outline.focused);
+            let (area, (x, y)) = match (tree, outline) {
+                (Some(tree), _) => (
+                    Area { x: 0, ..area },
+                    (0, tree.selected.saturating_sub(tree.offset)),
+                ),
+                (_, Some(outline)) => {
+                    let panes = self.panes_area();
+                    let x = panes.x.saturating_add(panes.width).saturating_add(1);
+                    (
+                        Area { x, ..area },
+                        (0, outline.selected.saturating_sub(outline.offset)),
+                    )
+                }
+                _ => (area, self.cursor_on_screen(area.width)),
+            };
+            // kept in the pane, however little of it there is
+            Terminal::cursor_position(&Position {
+                x: area
+                    .x
+                    .saturating_add(cmp::min(x, area.width.saturating_sub(1))),
+                y: area
+                    .y
+                    .saturating_add(cmp::min(y, area.height.saturating_sub(1))),
+            });
+        }
+        // the cursor may be off screen until the animation catches up
+        if self.scroll_target.is_none() {
+            Terminal::cursor_show();
         }
-        Terminal::cursor_show();
-        Terminal::flush()
+        let flushed = Terminal::flush();
+        self.measure_frame(started.elapsed());
+        flushed
     }
-    fn draw_status_bar(&mut self) {
-        let mut status;
-        let width = self.terminal.size().width as usize;
-        let modified_indicator = if self.document.is_dirty() {
-            " (modified)"
+    // the file name and whether it has unsaved changes, as the status bar
+    // has them
+    fn update_title(&mut self) {
+        let modified = if self.document.is_dirty() {
+            message!("status_modified")
         } else {
-            ""
+            String::new()
         };
-        let mut file_name = "[No_Name]".to_string();
-        if let Some(name) = &self.document.file_name {
-            file_name = name.clone();
-            file_name.truncate(20);
+        let name = self
+            .document
+            .file_name
+            .as_deref()
+            .and_then(|name| Path::new(name).file_name())
+            .map_or_else(|| message!("no_name").into(), |name| name.to_string_lossy());
+        let title = format!("{name}{modified}");
+        if title != self.title {
+            Terminal::set_title(&title);
+            self.title = title;
         }
-        status = format!(
-            "{} - {} lines{}",
-            file_name,
-            self.document.len(),
-            modified_indicator
-        );
+    }
+    // writes block while the terminal is behind, so a slow connection shows
+    // as frames that take long
+    fn measure_frame(&mut self, took: Duration) {
+        self.last_draw = Instant::now();
+        self.frame_time = (self.frame_time * 3 + took) / 4;
+        if !self.slow && self.frame_time > SLOW_FRAME {
+            self.slow = true;
+            self.status_message = StatusMessage::from(message!("slow_terminal"));
+        } else if self.slow && self.frame_time < FAST_FRAME {
+            self.slow = false;
+        }
+    }
+    fn draw_status_bar(&mut self, ps: &SyntaxSet, ts: &ThemeSet) {
+        let width = self.terminal.size().width as usize;
+        let mode_indicator = match self.mode {
+            EditorMode::MoveRows => message!("mode_move_rows"),
+            _ if !self.config.modal => String::new(),
+            EditorMode::Insert => message!("mode_insert"),
+            EditorMode::Visual => message!("mode_visual"),
+            EditorMode::Normal | EditorMode::Pending(_) => message!("mode_normal"),
+        };
+        let file_name = self
+            .document
+            .file_name
+            .as_deref()
+            .map_or_else(|| message!("no_name"), paths::home_relative);
+        let details = self.status_details();
+        // which pane the status bar is about, in bold, while there are two
+        let pane_indicator = match &self.split {
+            None => String::new(),
+            Some(split) => match (split.direction, split.second_focused) {
+                (Direction::Horizontal, false) => message!("status_pane_top"),
+                (Direction::Horizontal, true) => message!("status_pane_bottom"),
+                (Direction::Vertical, false) => message!("status_pane_left"),
+                (Direction::Vertical, true) => message!("status_pane_right"),
+            },
+        };
+        let mode_indicator = format!("{pane_indicator}{mode_indicator}");
         let line_number = self.cursor_position.y.saturating_add(1);
         let document_length = self.document.len();
-        let line_indicator = format!("{line_number}/{document_length}");
-        #[allow(clippy::arithmetic_side_effects)]
-        let len = status.len() + line_indicator.len();
-        status.push_str(&" ".repeat(width.saturating_sub(len)));
-        status = format!("{status}{line_indicator}");
-        status.truncate(width);
-        Terminal::set_bg_color(STATUS_BG_COLOR);
-        Terminal::set_fg_color(STATUS_FG_COLOR);
-        println!("{status}\r");
+        let position = format!("{line_number}/{document_length}");
+        let indent = self
+            .document
+            .indent()
+            .map_or_else(String::new, |indent| format!("{} ", indent.name()));
+        let mut line_indicator = format!(
+            "{indent}{} {} | {position}",
+            self.document.encoding().name(),
+            self.document.line_ending().name()
+        );
+        let language = &syntax::for_document(&self.document, ps).name;
+        let scope = if self.slow {
+            Vec::new()
+        } else {
+            self.current_scope(language)
+        };
+        if !scope.is_empty() {
+            line_indicator = format!("{} | {line_indicator}", scope.join(" > "));
+        }
+        // the mode and the line number always show. When the terminal is
+        // narrow the details give way first, then the scope, then the middle
+        // of the file name, after its directories are abbreviated.
+        let name_width = cmp::min(width_of(&file_name), MIN_FILE_NAME_WIDTH);
+        let fixed = width_of(&mode_indicator)
+            .saturating_add(name_width)
+            .saturating_add(1);
+        if fixed.saturating_add(width_of(&line_indicator)) > width {
+            line_indicator = position;
+        }
+        let room = width
+            .saturating_sub(width_of(&mode_indicator))
+            .saturating_sub(width_of(&line_indicator))
+            .saturating_sub(1);
+        let ellipsis = &self.config.glyphs().ellipsis;
+        let details = ellipsize_end(&details, room.saturating_sub(name_width), ellipsis);
+        let file_name = paths::abbreviate(
+            &file_name,
+            room.saturating_sub(width_of(&details)),
+            ellipsis,
+        );
+        let left = format!("{mode_indicator}{file_name}{details}");
+        let gap = width
+            .saturating_sub(width_of(&left))
+            .saturating_sub(width_of(&line_indicator));
+        let status = format!("{left}{}{line_indicator}", " ".repeat(gap));
+        let status: String = status.chars().take(width).collect();
+        let (bg, fg) = self.ui.status_colors(self.theme(ts));
+        Terminal::set_bg_color(bg);
+        Terminal::set_fg_color(fg);
+        let bold: String = status
+            .chars()
+            .take(pane_indicator.chars().count())
+            .collect();
+        let rest: String = status.chars().skip(bold.chars().count()).collect();
+        // a reset ends the bold, NoBold being a double underline on some
+        // terminals
+        print!("{}{bold}{}", style::Bold, style::Reset);
+        Terminal::set_bg_color(bg);
+        Terminal::set_fg_color(fg);
+        print!("{rest}");
         Terminal::reset_fg_color();
         Terminal::reset_bg_color();
     }
+    // the counts and flags after the file name, and the selection's counts
+    fn status_details(&mut self) -> String {
+        let lock_indicator = if self.document.is_read_only() {
+            message!("status_read_only")
+        } else {
+            String::new()
+        };
+        let modified_indicator = if self.document.has_disk_conflict() {
+            message!("status_changed_on_disk")
+        } else if self.document.is_dirty() {
+            message!("status_modified")
+        } else {
+            String::new()
+        };
+        let slow_indicator = if self.slow {
+            message!("status_slow")
+        } else {
+            String::new()
+        };
+        let indexing_indicator = self
+            .index
+            .as_ref()
+            .and_then(Index::walking)
+            .map_or_else(String::new, |count| {
+                message!("status_indexing", count = count)
+            });
+        let loading_indicator = self
+            .document
+            .loading_progress()
+            .map_or_else(String::new, |percent| {
+                message!("status_loading", percent = percent)
+            });
+        let mut lines = message!("status_lines", count = self.document.len());
+        if self.config.word_count {
+            let counts = self.document.counts();
+            let counts = message!(
+                "status_counts",
+                words = counts.words,
+                characters = counts.characters
+            );
+            lines = format!("{lines}{counts}");
+        }
+        let mut details = format!(
+            "{lock_indicator}{lines}{modified_indicator}{loading_indicator}{indexing_indicator}{slow_indicator}"
+        );
+        // counting words and finding the scope are left out on a slow
+        // terminal, to spend the time on the text
+        if let Some((start, end)) = self.selection().filter(|_| !self.slow) {
+            let text = self.document.text(&start, &end);
+            let lines = end.y.saturating_sub(start.y).saturating_add(1);
+            let characters = text.graphemes(true).count();
+            let words = text.split_whitespace().count();
+            let selected = message!(
+                "status_selected",
+                lines = lines,
+                characters = characters,
+                words = words
+            );
+            details = format!("{details} | {selected}");
+        }
+        details
+    }
+    // the declarations around the cursor, looked for again only once the
+    // cursor is on another row or a row above it changed
+    fn current_scope(&mut self, language: &str) -> Vec<String> {
+        let y = self.cursor_position.y;
+        if let Some((cached_y, cached_language, scope)) = &self.scope {
+            if *cached_y == y && cached_language == language {
+                return scope.clone();
+            }
+        }
+        let scope = symbols::scope(&self.document, language, y);
+        self.scope = Some((y, language.to_string(), scope.clone()));
+        scope
+    }
     fn draw_message_bar(&self) {
         Terminal::clear_current_line();
         let message = &self.status_message;
-        if Instant::now() - message.time < Duration::new(5, 0) {
-            let mut text = message.text.clone();
-            text.truncate(self.terminal.size().width as usize);
-            print!("{text}");
+        let width = usize::from(self.terminal.size().width);
+        let mut text: String = if message.time.elapsed() < Duration::new(5, 0) {
+            message.text.chars().take(width).collect()
+        } else {
+            String::new()
+        };
+        if let Some(bg) = self.ui.message_bg {
+            Terminal::set_bg_color(bg.0);
+            text = format!("{text:width$}");
+        }
+        if let Some(fg) = self.ui.message_fg {
+            Terminal::set_fg_color(fg.0);
         }
+        if message.error && !text.is_empty() {
+            print!("{}{}", style::Bold, style::Invert);
+        }
+        print!("{text}");
+        Terminal::reset_fg_color();
+        Terminal::reset_bg_color();
+        print!("{}", style::Reset);
     }
-    fn process_keypress(&mut self) -> Result<(), std::io::Error> {
-        let pressed_key = Terminal::read_key()?;
-        match pressed_key {
-            Key::Esc => match self.mode {
-                EditorMode::CtrlXPressed => self.mode = EditorMode::Normal,
-                _ => (),
-            },
-            Key::Ctrl('c') => {
-                if self.document.is_dirty() {
-                    self.dirty_quit()?;
-                } else {
-                    self.should_quit = true;
-                }
+    // whether there was anything to do, and so to redraw
+    fn process_keypress(&mut self) -> Result<bool, std::io::Error> {
+        if self.batch && !self.terminal.has_pending() {
+            self.run_command(Command::Quit)?;
+            return Ok(true);
+        }
+        let loading = self.document.loading_progress().is_some() || self.assets.is_some();
+        let timeout = if self.scroll_target.is_some() || loading {
+            ANIMATION_FRAME
+        } else {
+            INPUT_POLL
+        };
+        let Some(input) = self.terminal.poll_input(timeout)? else {
+            let animating = self.scroll_target.is_some();
+            self.animate_scroll();
+            return Ok(animating);
+        };
+        // finish any running animation so the key acts on the final view
+        if let Some(target) = self.scroll_target.take() {
+            self.offset.y = target;
+        }
+        let previous_offset = self.offset.y;
+        let said = self.status_message.time;
+        let jump = match input {
+            Input::Key(pressed_key) => {
+                self.process_key(pressed_key)?;
+                matches!(pressed_key, Key::PageUp | Key::PageDown)
+                    || self.config.command(pressed_key) == Some(Command::Find)
             }
-            Key::Ctrl('x') => {
-                self.mode = EditorMode::CtrlXPressed;
+            Input::Modified(modified) => {
+                self.process_modified(modified);
+                false
             }
-            Key::Ctrl('d') => {
-                // remove line at cursor
-                self.document.delete_row(self.cursor_position.y)
+            Input::Mouse(event) => {
+                self.process_mouse(event);
+                false
             }
-            Key::Ctrl('f') => self.search(),
-            Key::Ctrl('s') => self.save(),
-            Key::Char(c) => {
-                self.document.insert(&self.cursor_position, c);
-                self.move_cursor(Key::Right);
+        };
+        self.scroll();
+        if self.status_message.time == said {
+            self.show_diagnostic();
+        }
+        if self.config.screen_reader {
+            self.announce(said);
+        }
+        // an animation is a lot of frames for a slow terminal
+        let smooth = self.config.smooth_scroll && !self.slow && !self.config.screen_reader;
+        if smooth && jump && self.offset.y.abs_diff(previous_offset) > 1 {
+            self.scroll_target = Some(self.offset.y);
+            self.offset.y = previous_offset;
+        }
+        Ok(true)
+    }
+    // puts the line the cursor moved to in the message bar, for a screen
+    // reader to say, unless the key had something else to tell since `said`.
+    // Characters that are only told apart by color are named in words.
+    fn announce(&mut self, said: Instant) {
+        let y = self.cursor_position.y;
+        if self.announced == Some(y) {
+            return;
+        }
+        self.announced = Some(y);
+        if self.status_message.time != said {
+            return;
+        }
+        let text = self.document.row(y).map_or("", Row::as_str).to_string();
+        let chars: Vec<char> = text.chars().collect();
+        let suspicious = self.config.mark_suspicious
+            && (0..chars.len()).any(|index| {
+                let previous = index.checked_sub(1).map(|index| chars[index]);
+                unicode::is_suspicious(chars[index], previous, chars.get(index + 1).copied())
+            });
+        let id = if suspicious {
+            "announce_suspicious"
+        } else {
+            "announce_line"
+        };
+        self.status_message =
+            StatusMessage::from(message!(id, number = y.saturating_add(1), text = text));
+    }
+    fn process_key(&mut self, pressed_key: Key) -> Result<(), std::io::Error> {
+        let command = self.config.command(pressed_key);
+        let panel = !matches!(
+            command,
+            Some(Command::FileTree | Command::Outline | Command::Quit)
+        );
+        if panel && self.file_tree.as_ref().is_some_and(|tree| tree.focused) {
+            return self.process_tree_key(pressed_key);
+        }
+        if panel && self.outline.as_ref().is_some_and(|outline| outline.focused) {
+            self.process_outline_key(pressed_key);
+            return Ok(());
+        }
+        if pressed_key == Key::Char('\n') && command.is_none() && self.document.is_listing() {
+            self.open_listed();
+            return Ok(());
+        }
+        if pressed_key == Key::Char('\n') && command.is_none() && self.document.is_undo_tree() {
+            return self.open_listed_state();
+        }
+        if self.is_edit(pressed_key, command) && !self.confirm_edit()? {
+            return Ok(());
+        }
+        if let Some(command) = command {
+            self.run_command(command)?;
+            // only shifted movement and commands that just look at the text
+            // keep a selection going, and comments, to be toggled back on the
+            // same rows. Going to the other pane brings back its own.
+            if !matches!(
+                command,
+                Command::Copy
+                    | Command::NextPane
+                    | Command::ExpandSelection
+                    | Command::ShrinkSelection
+                    | Command::InspectGrapheme
+                    | Command::SelectionBytes
+                    | Command::ToggleWarnings
+                    | Command::ToggleInvisibles
+                    | Command::ToggleComment
+                    | Command::ExpandSnippet
+            ) {
+                self.selection_anchor = None;
             }
-            Key::Backspace => {
-                if self.cursor_position.x > 0 || self.cursor_position.y > 0 {
-                    self.move_cursor(Key::Left);
-                    self.document.delete(&self.cursor_position);
-                }
+            return Ok(());
+        }
+        if self.mode == EditorMode::Normal && pressed_key == Key::Char(':') {
+            return self.command_line();
+        }
+        if matches!(
+            self.mode,
+            EditorMode::Normal | EditorMode::Pending(_) | EditorMode::Visual
+        ) {
+            self.process_normal(pressed_key);
+            return Ok(());
+        }
+        match pressed_key {
+            Key::Esc => {
+                self.mode = self.base_mode();
+                self.search_highlight = None;
+                self.document.snippet = None;
+            }
+            Key::Char('\t') | Key::BackTab if self.document.snippet.is_some() => {
+                // the field is selected, for typing to replace
+                self.next_field(pressed_key == Key::Char('\t'));
+                return Ok(());
+            }
+            Key::Backspace | Key::Delete if self.selection().is_some() => self.delete_selection(),
+            Key::Char(c) => self.type_char(c)?,
+            Key::Backspace if self.config.auto_close && self.delete_pair() => (),
+            Key::Backspace if self.cursor_position.x > 0 || self.cursor_position.y > 0 => {
+                self.move_cursor(Key::Left);
+                self.document
+                    .delete(&self.cursor_position, self.undo_pause());
             }
             Key::Delete => {
-                self.document.delete(&self.cursor_position);
+                self.document
+                    .delete(&self.cursor_position, self.undo_pause());
             }
-            Key::Up | Key::Down => match self.mode {
-                EditorMode::CtrlXPressed => self.move_row(pressed_key),
-                EditorMode::Normal => self.move_cursor(pressed_key),
-            },
+            Key::Up | Key::Down if self.mode == EditorMode::MoveRows => {
+                // the selection goes along with the rows
+                self.move_row(pressed_key);
+                return Ok(());
+            }
+            Key::Up | Key::Down => self.move_cursor(pressed_key),
             Key::Left | Key::Right | Key::PageDown | Key::PageUp | Key::End | Key::Home => {
-                self.move_cursor(pressed_key)
+                self.move_arrow(pressed_key);
             }
             _ => (),
         }
-        self.scroll();
+        self.selection_anchor = None;
         Ok(())
     }
-
-    fn search(&mut self) {
-        if let Some(query) = self.prompt("Search: ").unwrap_or(None) {
-            if let Some(position) = self.document.find(&query[..], &self.cursor_position) {
-                self.cursor_position = position;
-            } else {
-                self.status_message = StatusMessage::from(format!("Not found :{}.", query));
+    // what typing `c` does, or pasting the text it starts
+    fn type_char(&mut self, c: char) -> Result<(), std::io::Error> {
+        // typing replaces the selection
+        self.delete_selection();
+        let pasted = self.terminal.read_pending_chars()?;
+        if pasted.is_empty() && self.config.auto_close && self.auto_close(c) {
+            // typed already
+        } else if pasted.is_empty() && c == '\n' && self.config.auto_indent {
+            let (at, window) = (self.cursor_position, self.undo_pause());
+            self.cursor_position = self.new_row(&at, window);
+        } else if let (true, '\t', Some(Indent::Spaces(width))) =
+            (pasted.is_empty(), c, self.document.indent())
+        {
+            // spaces to the next level, typed one at a time so
+            // undo takes them back with what was typed around them
+            let count = width.saturating_sub(self.cursor_position.x % cmp::max(width, 1));
+            for _ in 0..count {
+                self.document
+                    .insert(&self.cursor_position, ' ', self.undo_pause());
+                self.move_cursor(Key::Right);
             }
+        } else if pasted.is_empty() {
+            self.document
+                .insert(&self.cursor_position, c, self.undo_pause());
+            self.move_cursor(Key::Right);
+        } else {
+            let text = format!("{c}{pasted}");
+            self.cursor_position = self.document.insert_str(&self.cursor_position, &text);
         }
+        Ok(())
     }
-    fn move_row(&mut self, key: Key) {
-        let Position { x: _, y } = self.cursor_position;
-        if let Some(row) = self.document.row(y) {
-            let new_row = row.clone();
-            self.document.delete_row(y);
-            match key {
-                Key::Up => {
-                    if y > 0 {
-                        self.document.insert_row(new_row, y - 1);
-                    }
-                }
-                Key::Down => {
-                    if y + 1 <= self.document.len() {
-                        self.document.insert_row(new_row, y + 1);
-                    }
+    // how long typing may pause and still be undone together
+    fn undo_pause(&self) -> Duration {
+        Duration::from_millis(self.config.undo_pause_ms)
+    }
+    fn run_command(&mut self, command: Command) -> Result<(), std::io::Error> {
+        match command {
+            Command::Quit => {
+                if self.any_dirty() {
+                    self.dirty_quit()?;
+                } else {
+                    self.should_quit = true;
                 }
+            }
+            Command::Copy => self.copy(),
+            Command::Help => self.help(""),
+            Command::MatchBracket => self.match_bracket(),
+            Command::ToggleComment => self.toggle_comment(),
+            Command::SpellSuggest => self.spell_suggest(),
+            Command::UiTheme => self.choose_ui_theme(),
+            Command::Locations => self.find_locations(),
+            Command::NextLocation => self.step_location(true),
+            Command::PreviousLocation => self.step_location(false),
+            Command::ProjectSearch => self.project_search(),
+            Command::FileTree => self.toggle_file_tree(),
+            Command::Outline => self.toggle_outline(),
+            Command::ExpandSnippet => self.expand_snippet(),
+            Command::GoToDefinition | Command::SearchTags | Command::JumpBack => {
+                self.tag_command(command);
+            }
+            Command::CargoCheck => self.cargo_check(),
+            Command::NextDiagnostic | Command::PreviousDiagnostic => {
+                self.step_diagnostic(command == Command::NextDiagnostic);
+            }
+            Command::Redact => self.toggle_redact(),
+            Command::LineEnding => self.switch_line_ending(),
+            Command::ShowPath => {
+                let message = self
+                    .document
+                    .file_name
+                    .as_deref()
+                    .map_or_else(|| message!("no_file_yet"), paths::full);
+                self.status_message = StatusMessage::from(message);
+            }
+            Command::Cut if self.selection().is_some() => {
+                self.copy();
+                self.delete_selection();
+            }
+            Command::Cut => {
+                self.mode = EditorMode::MoveRows;
+            }
+            Command::Paste => self.paste(),
+            Command::DeleteRow => {
+                // remove line at cursor
+                self.document.delete_row(self.cursor_position.y);
+            }
+            Command::Find => self.search(),
+            Command::FindNext => self.search_again(false),
+            Command::FindPrevious => self.search_again(true),
+            Command::Replace => self.replace(),
+            Command::InspectGrapheme => self.inspect_grapheme(),
+            Command::ToggleInvisibles => self.toggle_invisibles(),
+            Command::ToggleWarnings => self.toggle_warnings(),
+            Command::Save => self.save(),
+            Command::Undo => self.undo(),
+            Command::Redo => self.redo(),
+            Command::InsertFile => self.insert_file(),
+            Command::Open => self.open_file(),
+            Command::NextBuffer => self.cycle_buffer(true),
+            Command::CloseBuffer => self.close_buffer()?,
+            Command::ReopenBuffer => self.reopen_buffer(),
+            Command::SelectionBytes => self.selection_bytes(),
+            Command::Split => self.cycle_split(),
+            Command::LineNumbers => self.cycle_line_numbers(),
+            Command::Wrap => self.toggle_wrap(),
+            Command::InsertVariable => self.insert_variable(),
+            Command::Theme => self.choose_theme(),
+            Command::Execute => self.command_line()?,
+            Command::SetMark | Command::GoToMark => self.mark(command)?,
+            Command::Reflow => self.reflow(),
+            Command::ExpandSelection => self.expand_selection(),
+            Command::ShrinkSelection => self.shrink_selection(),
+            Command::SaveSession => self.save_session(),
+            Command::LoadSession => self.load_session(),
+            Command::NextPane => {
+                let second = self
+                    .split
+                    .as_ref()
+                    .is_some_and(|split| !split.second_focused);
+                self.focus_pane(second);
+            }
+        }
+        Ok(())
+    }
+    fn toggle_redact(&mut self) {
+        self.redact = !self.redact;
+        let message = if self.redact {
+            message!("redact_on")
+        } else {
+            message!("redact_off")
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+    fn toggle_warnings(&mut self) {
+        let setting = if self.config.mark_suspicious {
+            "nomark_suspicious"
+        } else {
+            "mark_suspicious"
+        };
+        self.set_global(setting).ok();
+        let message = if self.config.mark_suspicious {
+            message!("warnings_on")
+        } else {
+            message!("warnings_off")
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+    fn toggle_wrap(&mut self) {
+        self.set_global(if self.config.wrap { "nowrap" } else { "wrap" })
+            .ok();
+        self.offset.x = 0;
+        let message = if self.config.wrap {
+            message!("wrap_on")
+        } else {
+            message!("wrap_off")
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+    fn toggle_invisibles(&mut self) {
+        let setting = if self.config.show_invisibles {
+            "noshow_invisibles"
+        } else {
+            "show_invisibles"
+        };
+        self.set_global(setting).ok();
+        let message = if self.config.show_invisibles {
+            message!("invisibles_on")
+        } else {
+            message!("invisibles_off")
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+    // LF to CRLF and back, for the next save
+    fn switch_line_ending(&mut self) {
+        let line_ending = match self.document.line_ending() {
+            LineEnding::Lf => LineEnding::Crlf,
+            LineEnding::Crlf => LineEnding::Lf,
+        };
+        self.document.set_line_ending(line_ending);
+        self.status_message =
+            StatusMessage::from(message!("line_endings", ending = line_ending.name()));
+    }
+    fn process_modified(&mut self, modified: Modified) {
+        // Ctrl-Alt goes through the buffers, Alt alone between the panes
+        if modified.alt && modified.ctrl && !modified.shift {
+            match modified.key {
+                Key::Left | Key::Up => self.cycle_buffer(false),
+                Key::Right | Key::Down => self.cycle_buffer(true),
+                _ => (),
+            }
+            return;
+        }
+        if modified.alt && !modified.shift {
+            match modified.key {
+                Key::Left | Key::Up => self.focus_pane(false),
+                Key::Right | Key::Down => self.focus_pane(true),
                 _ => (),
             }
-            self.move_cursor(key)
+            return;
+        }
+        if modified.shift {
+            if self.selection_anchor.is_none() {
+                self.selection_anchor = Some(self.cursor_position);
+            }
+        } else {
+            self.selection_anchor = None;
+        }
+        match modified.key {
+            Key::Left | Key::Right if modified.ctrl => self.move_word(modified.key),
+            key => self.move_arrow(key),
         }
     }
-    fn dirty_quit(&mut self) -> Result<(), std::io::Error> {
-        let ps = SyntaxSet::load_defaults_newlines();
-        let ts = ThemeSet::load_defaults();
-        loop {
-            self.status_message = StatusMessage::from(
-                "You will loose unsaved changes, enter to quit? esc to continue.".to_string(),
-            );
-            self.refresh_screen(&ps, &ts)?;
-            match Terminal::read_key()? {
-                Key::Char(c) => {
-                    if c == '\n' {
-                        self.should_quit = true;
-                        break;
+    // vim's keys for the modes where typing doesn't insert
+    fn process_normal(&mut self, key: Key) {
+        if let EditorMode::Pending(operator) = self.mode {
+            // any key other than the operator again cancels it
+            self.mode = EditorMode::Normal;
+            match (operator, key) {
+                ('m', Key::Char(name)) => self.set_mark(name),
+                ('\'', Key::Char(name)) => self.go_to_mark(name),
+                ('d' | 'y', _) if key == Key::Char(operator) => {
+                    self.yank_row();
+                    if operator == 'd' {
+                        self.document.delete_row(self.cursor_position.y);
                     }
                 }
-                Key::Esc => {
-                    self.status_message = StatusMessage::from(String::from(
-                        "HELP: Ctrl-F = find | Ctrl-S = save | Ctrl-C = quit",
-                    ));
-                    break;
-                }
                 _ => (),
             }
+            return;
         }
-        Ok(())
-    }
-    fn save(&mut self) {
-        if self.document.file_name.is_none() {
-            let new_name = self.prompt("Save As: ").unwrap_or(None);
-            if new_name.is_none() {
-                self.status_message = StatusMessage::from("Save aborted.".to_string());
-                return;
+        let visual = self.mode == EditorMode::Visual;
+        match key {
+            Key::Char('h') => self.move_arrow(Key::Left),
+            Key::Char('j') => self.move_cursor(Key::Down),
+            Key::Char('k') => self.move_cursor(Key::Up),
+            Key::Char('l') => self.move_arrow(Key::Right),
+            Key::Left
+            | Key::Right
+            | Key::Up
+            | Key::Down
+            | Key::PageDown
+            | Key::PageUp
+            | Key::End
+            | Key::Home => self.move_arrow(key),
+            Key::Esc | Key::Char('v') if visual => self.mode = EditorMode::Normal,
+            Key::Esc => self.search_highlight = None,
+            Key::Char('y') if visual => {
+                self.copy();
+                self.mode = EditorMode::Normal;
             }
-            self.document.file_name = new_name;
+            Key::Char('d' | 'x') if visual => {
+                self.copy();
+                self.delete_selection();
+                self.mode = EditorMode::Normal;
+            }
+            Key::Char(operator @ ('d' | 'y' | 'm' | '\'')) => {
+                self.mode = EditorMode::Pending(operator);
+            }
+            Key::Char('x') => self.document.delete(&self.cursor_position, Duration::ZERO),
+            Key::Char('n') => self.search_again(false),
+            Key::Char('N') => self.search_again(true),
+            Key::Char('%') => self.match_bracket(),
+            Key::Char('p') => self.put(),
+            Key::Char('o') => {
+                let y = self.cursor_position.y;
+                let x = self.document.row(y).map_or(0, Row::len);
+                if self.config.auto_indent {
+                    self.cursor_position = self.new_row(&Position { x, y }, Duration::ZERO);
+                } else {
+                    self.document
+                        .insert(&Position { x, y }, '\n', Duration::ZERO);
+                    self.cursor_position = Position {
+                        x: 0,
+                        y: cmp::min(y.saturating_add(1), self.document.len()),
+                    };
+                }
+                self.mode = EditorMode::Insert;
+            }
+            Key::Char('i') => self.mode = EditorMode::Insert,
+            Key::Char('a') => {
+                let len = self
+                    .document
+                    .row(self.cursor_position.y)
+                    .map_or(0, Row::len);
+                self.cursor_position.x = cmp::min(self.cursor_position.x.saturating_add(1), len);
+                self.mode = EditorMode::Insert;
+            }
+            Key::Char('v') => {
+                self.selection_anchor = Some(self.cursor_position);
+                self.mode = EditorMode::Visual;
+            }
+            _ => (),
+        }
+        if self.mode != EditorMode::Visual {
+            self.selection_anchor = None;
         }
-        if self.document.save().is_ok() {
-            self.status_message = StatusMessage::from("File saved successfully.".to_string());
+    }
+    // the bracket at `at`, or else the one just before it, and its pair
+    fn bracket_pair(&self, at: &Position) -> Option<(Position, Position)> {
+        let before = at.x.checked_sub(1).map(|x| Position { x, y: at.y });
+        [Some(*at), before]
+            .into_iter()
+            .flatten()
+            .find_map(|bracket| {
+                let pair = self
+                    .document
+                    .matching_bracket(&bracket, BRACKET_SCAN_ROWS)?;
+                Some((bracket, pair))
+            })
+    }
+    fn match_bracket(&mut self) {
+        if let Some((_, pair)) = self.bracket_pair(&self.cursor_position) {
+            self.cursor_position = pair;
+            self.scroll();
         } else {
-            self.status_message = StatusMessage::from("Error writing file!".to_string());
+            self.status_message = StatusMessage::from(message!("no_matching_bracket"));
         }
     }
-    fn scroll(&mut self) {
-        let Position { x, y } = self.cursor_position;
-        let height = self.terminal.size().height as usize;
-        let width = self.terminal.size().width as usize;
-        let offset = &mut self.offset;
-        if y < offset.y {
-            offset.y = y;
-        } else if y >= offset.y.saturating_add(height) {
-            offset.y = y.saturating_sub(height).saturating_add(1);
+    // what Esc goes back to
+    fn base_mode(&self) -> EditorMode {
+        if self.config.modal {
+            EditorMode::Normal
+        } else {
+            EditorMode::Insert
         }
-        if x < offset.x {
-            offset.x = x;
-        } else if x >= offset.x.saturating_add(width) {
-            offset.x = x.saturating_sub(width).saturating_add(1);
+    }
+    // copies the cursor's row, newline included, so `p` puts it as a row
+    fn yank_row(&mut self) {
+        if let Some(row) = self.document.row(self.cursor_position.y) {
+            self.clipboard = format!("{}\n", row.as_str());
         }
     }
-    fn move_cursor(&mut self, key: Key) {
-        let Position { mut x, mut y } = self.cursor_position;
-        let height = self.document.len();
-        let terminal_height = self.terminal.size().height as usize;
-        let width = if let Some(row) = self.document.row(y) {
-            row.len()
-        } else {
-            0
+    // like paste, but whole rows from `yy` or `dd` go below the cursor's row
+    fn put(&mut self) {
+        let Some(text) = self.clipboard.strip_suffix('\n') else {
+            self.paste();
+            return;
         };
-        match key {
-            Key::Up => y = y.saturating_sub(1),
-            Key::Down => {
-                if y < height {
-                    y = y.saturating_add(1);
+        let y = self.cursor_position.y;
+        let text = text.to_string();
+        if let Some(row) = self.document.row(y) {
+            let at = Position { x: row.len(), y };
+            self.document.insert_str(&at, &format!("\n{text}"));
+            self.cursor_position = Position {
+                x: 0,
+                y: y.saturating_add(1),
+            };
+        } else {
+            self.cursor_position = Position { x: 0, y };
+            self.document.insert_str(&self.cursor_position, &text);
+        }
+    }
+    // a click moves the cursor, dragging selects and the wheel scrolls
+    fn process_mouse(&mut self, event: MouseEvent) {
+        match event {
+            MouseEvent::Press(MouseButton::WheelUp, ..) => self.scroll_wheel(false),
+            MouseEvent::Press(MouseButton::WheelDown, ..) => self.scroll_wheel(true),
+            MouseEvent::Press(MouseButton::Left, x, y) => {
+                // termion counts from 1
+                let (column, line) = (
+                    usize::from(x).saturating_sub(1),
+                    usize::from(y).saturating_sub(1),
+                );
+                let panes = self.panes_area();
+                let right = panes.x.saturating_add(panes.width);
+                self.unfocus_panels();
+                if let Some(tree) = self.file_tree.as_mut().filter(|_| column < panes.x) {
+                    tree.focused = true;
+                    tree.selected = tree.offset.saturating_add(line);
+                    tree.select(true, 0);
+                    return;
                 }
-            }
-            Key::Left => {
-                if x > 0 {
-                    x -= 1;
-                } else if y > 0 {
-                    y -= 1;
-                    if let Some(row) = self.document.row(y) {
-                        x = row.len();
-                    } else {
-                        x = 0;
+                if let Some(outline) = self.outline.as_mut().filter(|_| column > right) {
+                    outline.focused = true;
+                    outline.selected = outline.offset.saturating_add(line);
+                    outline.select(true, 0);
+                    return;
+                }
+                if let Some((first, second)) = self.pane_areas() {
+                    if first.contains(column, line) {
+                        self.focus_pane(false);
+                    } else if second.contains(column, line) {
+                        self.focus_pane(true);
                     }
                 }
+                self.selection_anchor = None;
+                self.cursor_position = self.position_at(column, line);
             }
-            Key::Right => {
-                if x < width {
-                    x += 1;
-                } else if y < height {
-                    y += 1;
-                    x = 0;
+            MouseEvent::Hold(x, y) => {
+                let (column, line) = (
+                    usize::from(x).saturating_sub(1),
+                    usize::from(y).saturating_sub(1),
+                );
+                if self.selection_anchor.is_none() {
+                    self.selection_anchor = Some(self.cursor_position);
                 }
+                self.cursor_position = self.position_at(column, line);
             }
-            Key::PageUp => {
-                y = if y > terminal_height {
-                    y.saturating_sub(terminal_height)
-                } else {
-                    0
+            _ => (),
+        }
+    }
+    // the document position drawn at a screen cell of the focused pane, the
+    // nearest one for cells outside of its text
+    fn position_at(&self, column: usize, line: usize) -> Position {
+        let area = self.text_area();
+        let column = column.saturating_sub(area.x);
+        let line = cmp::min(line.saturating_sub(area.y), area.height.saturating_sub(1));
+        let (y, start, end) = if self.config.wrap {
+            let mut y = self.offset.y;
+            let mut line = line;
+            loop {
+                let segments = self.segments(y, area.width);
+                if line < segments.len() || y >= self.document.len() {
+                    let index = cmp::min(line, segments.len().saturating_sub(1));
+                    let start = segments.get(index).copied().unwrap_or(0);
+                    // the last column of a full line belongs to it, not the next
+                    let end = segments
+                        .get(index.saturating_add(1))
+                        .map(|next| next.saturating_sub(1));
+                    break (y, start, end);
                 }
+                line = line.saturating_sub(segments.len());
+                y = y.saturating_add(1);
             }
-            Key::PageDown => {
-                y = if y.saturating_add(terminal_height) < height {
+        } else {
+            (self.offset.y.saturating_add(line), self.offset.x, None)
+        };
+        let Some(row) = self.document.row(y) else {
+            return Position {
+                x: 0,
+                y: cmp::min(y, self.document.len()),
+            };
+        };
+        let end = end.unwrap_or_else(|| row.len());
+        Position {
+            x: row.index_at(start, end, column, self.config.tab_width),
+            y,
+        }
+    }
+    // moves the view, taking the cursor along where it would go off screen
+    fn scroll_wheel(&mut self, down: bool) {
+        let height = self.text_area().height;
+        let margin = cmp::min(self.config.scrolloff, height.saturating_sub(1) / 2);
+        self.offset.y = if down {
+            cmp::min(
+                self.offset.y.saturating_add(WHEEL_ROWS),
+                self.document.len().saturating_sub(margin),
+            )
+        } else {
+            self.offset.y.saturating_sub(WHEEL_ROWS)
+        };
+        let top = if self.offset.y > 0 {
+            self.offset.y.saturating_add(margin)
+        } else {
+            0
+        };
+        let bottom = self
+            .offset
+            .y
+            .saturating_add(height)
+            .saturating_sub(margin)
+            .saturating_sub(1);
+        self.cursor_position.y = self.cursor_position.y.clamp(top, cmp::max(top, bottom));
+        self.clamp_cursor();
+    }
+    // by word within a row, and onto the neighbouring row at either end
+    fn move_word(&mut self, key: Key) {
+        let Position { x, y } = self.cursor_position;
+        let word_chars = self.word_chars();
+        let row_len = self.document.row(y).map_or(0, Row::len);
+        self.cursor_position = match key {
+            Key::Right if x >= row_len && y < self.document.len() => Position {
+                x: 0,
+                y: y.saturating_add(1),
+            },
+            Key::Right => Position {
+                x: self
+                    .document
+                    .row(y)
+                    .map_or(x, |row| row.next_word(x, &word_chars)),
+                y,
+            },
+            Key::Left if x == 0 => {
+                let Some(above) = y.checked_sub(1) else {
+                    return;
+                };
+                Position {
+                    x: self.document.row(above).map_or(0, Row::len),
+                    y: above,
+                }
+            }
+            Key::Left => Position {
+                x: self
+                    .document
+                    .row(y)
+                    .map_or(0, |row| row.previous_word(x, &word_chars)),
+                y,
+            },
+            _ => return,
+        };
+    }
+    // types an opener along with its closer, or over a closer that was
+    // typed that way. False for anything else, which is typed as it is.
+    fn auto_close(&mut self, c: char) -> bool {
+        let Position { x, y } = self.cursor_position;
+        let row = self.document.row(y);
+        let next = row
+            .and_then(|row| row.grapheme(x))
+            .and_then(|next| next.chars().next());
+        let previous = x
+            .checked_sub(1)
+            .and_then(|x| row?.grapheme(x))
+            .and_then(|previous| previous.chars().next());
+        let len = row.map_or(0, Row::len);
+        if next == Some(c) && self.take_auto_closed(len.saturating_sub(x)) {
+            self.move_cursor(Key::Right);
+            return true;
+        }
+        let Some(closer) = closer(c) else {
+            return false;
+        };
+        // pairing up in front of a word, or a quote right after one, would
+        // more likely be in the way
+        let is_word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+        if is_word(next) || (c == closer && is_word(previous)) {
+            return false;
+        }
+        let window = self.undo_pause();
+        self.document.insert(&self.cursor_position, c, window);
+        self.document.insert(
+            &Position {
+                x: x.saturating_add(1),
+                y,
+            },
+            closer,
+            window,
+        );
+        self.move_cursor(Key::Right);
+        if self.auto_closed.0 != y {
+            self.auto_closed = (y, Vec::new());
+        }
+        self.auto_closed
+            .1
+            .push(len.saturating_sub(x).saturating_add(1));
+        true
+    }
+    // Backspace between an opener and the closer auto-close put in deletes
+    // both
+    fn delete_pair(&mut self) -> bool {
+        let Position { x, y } = self.cursor_position;
+        let Some(row) = self.document.row(y) else {
+            return false;
+        };
+        let opener = x
+            .checked_sub(1)
+            .and_then(|x| row.grapheme(x))
+            .and_then(|opener| opener.chars().next());
+        let next = row.grapheme(x).and_then(|next| next.chars().next());
+        if opener.and_then(closer).is_none() || opener.and_then(closer) != next {
+            return false;
+        }
+        if !self.take_auto_closed(row.len().saturating_sub(x)) {
+            return false;
+        }
+        let start = Position {
+            x: x.saturating_sub(1),
+            y,
+        };
+        self.document.delete_range(
+            &start,
+            &Position {
+                x: x.saturating_add(1),
+                y,
+            },
+        );
+        self.cursor_position = start;
+        true
+    }
+    // whether auto-close put in the closer `offset` from the end of the
+    // cursor's row, forgetting it if so
+    fn take_auto_closed(&mut self, offset: usize) -> bool {
+        let (y, closers) = &mut self.auto_closed;
+        if *y != self.cursor_position.y {
+            closers.clear();
+            return false;
+        }
+        match closers.iter().rposition(|closer| *closer == offset) {
+            Some(index) => {
+                closers.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+    // breaks the row at `at` with auto-indent, returning where the cursor
+    // goes
+    fn new_row(&mut self, at: &Position, window: Duration) -> Position {
+        let indent_after = self
+            .config
+            .indent_after(self.document.file_name.as_deref())
+            .to_string();
+        self.document
+            .insert_newline(at, &indent_after, self.config.tab_width, window)
+    }
+    // the extra word characters configured for the document's file type
+    fn word_chars(&self) -> String {
+        self.document
+            .file_name
+            .as_deref()
+            .and_then(|name| Path::new(name).extension())
+            .and_then(|extension| self.config.word_chars.get(&*extension.to_string_lossy()))
+            .cloned()
+            .unwrap_or_default()
+    }
+    // the selected span, start first, if it covers anything
+    fn selection(&self) -> Option<(Position, Position)> {
+        self.view().selection()
+    }
+    // the columns of row `y` that are selected in `pane`
+    fn selected_columns(pane: &Pane, y: usize, len: usize) -> Option<Range<usize>> {
+        let (start, end) = pane.selection()?;
+        if y < start.y || y > end.y {
+            return None;
+        }
+        let from = if y == start.y { start.x } else { 0 };
+        let to = if y == end.y { end.x } else { len };
+        Some(from..to)
+    }
+    // the selected span if there is one, an empty one at the cursor if not
+    fn span(&self) -> (Position, Position) {
+        self.selection()
+            .unwrap_or((self.cursor_position, self.cursor_position))
+    }
+    fn select(&mut self, (start, end): (Position, Position)) {
+        self.selection_anchor = (start != end).then_some(start);
+        self.cursor_position = end;
+    }
+    // selects the smallest of the word, quoted string, bracket pair (inside,
+    // then with the brackets), lines, block of lines and document that holds
+    // more than the selection does
+    fn expand_selection(&mut self) {
+        let span = self.span();
+        // a selection changed by other means starts over
+        while self
+            .expansions
+            .last()
+            .is_some_and(|(_, after)| *after != span)
+        {
+            self.expansions.pop();
+        }
+        let contains = |(start, end): (Position, Position)| {
+            (start.y, start.x) <= (span.0.y, span.0.x)
+                && (end.y, end.x) >= (span.1.y, span.1.x)
+                && (start, end) != span
+        };
+        let size = |(start, end): &(Position, Position)| self.document.text(start, end).len();
+        let Some(expanded) = self
+            .selection_candidates(span)
+            .into_iter()
+            .filter(|candidate| contains(*candidate))
+            .min_by_key(size)
+        else {
+            return;
+        };
+        self.expansions.push((span, expanded));
+        self.select(expanded);
+    }
+    fn shrink_selection(&mut self) {
+        let span = self.span();
+        match self.expansions.pop() {
+            Some((before, after)) if after == span => self.select(before),
+            _ => {
+                self.expansions.clear();
+                self.selection_anchor = None;
+            }
+        }
+    }
+    fn selection_candidates(
+        &self,
+        (start, end): (Position, Position),
+    ) -> Vec<(Position, Position)> {
+        let mut candidates = Vec::new();
+        let at = |x: usize, y: usize| Position { x, y };
+        if let Some(row) = self.document.row(start.y).filter(|_| start.y == end.y) {
+            let y = start.y;
+            if let Some(word) = row.word_at(start.x, &self.word_chars()) {
+                candidates.push((at(word.start, y), at(word.end, y)));
+            }
+            for quoted in row.quoted() {
+                candidates.push((
+                    at(quoted.start.saturating_add(1), y),
+                    at(quoted.end.saturating_sub(1), y),
+                ));
+                candidates.push((at(quoted.start, y), at(quoted.end, y)));
+            }
+        }
+        if let Some((open, close)) = self.document.enclosing_brackets(&start, &end) {
+            candidates.push((
+                at(open.x.saturating_add(1), open.y),
+                at(close.x.saturating_sub(1), close.y),
+            ));
+            candidates.push((open, close));
+        }
+        let row_len = |y: usize| self.document.row(y).map_or(0, Row::len);
+        let last = self.document.len().saturating_sub(1);
+        let end_y = cmp::min(end.y, last);
+        candidates.push((at(0, start.y), at(row_len(end_y), end_y)));
+        // the block is the surrounding rows up to the nearest blank ones
+        let blank = |y: usize| {
+            self.document
+                .row(y)
+                .is_none_or(|row| row.as_str().trim().is_empty())
+        };
+        let mut top = start.y;
+        while top > 0 && !blank(top.saturating_sub(1)) {
+            top = top.saturating_sub(1);
+        }
+        let mut bottom = end_y;
+        while bottom < last && !blank(bottom.saturating_add(1)) {
+            bottom = bottom.saturating_add(1);
+        }
+        candidates.push((at(0, top), at(row_len(bottom), bottom)));
+        candidates.push((at(0, 0), at(row_len(last), last)));
+        candidates
+    }
+    fn delete_selection(&mut self) {
+        if let Some((start, end)) = self.selection() {
+            self.document.delete_range(&start, &end);
+            self.cursor_position = start;
+        }
+        self.selection_anchor = None;
+    }
+    fn copy(&mut self) {
+        if let Some((start, end)) = self.selection() {
+            self.clipboard = self.document.text(&start, &end);
+        } else {
+            self.status_message = StatusMessage::from(message!("nothing_selected"));
+        }
+    }
+    fn selection_bytes(&mut self) {
+        let message = if let Some((start, end)) = self.selection() {
+            let bytes = self.document.text(&start, &end).len();
+            message!("selection_bytes", bytes = bytes)
+        } else {
+            message!("nothing_selected")
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+    fn paste(&mut self) {
+        if self.clipboard.is_empty() {
+            return;
+        }
+        self.delete_selection();
+        self.cursor_position = self
+            .document
+            .insert_str(&self.cursor_position, &self.clipboard);
+    }
+    fn is_edit(&self, key: Key, command: Option<Command>) -> bool {
+        match command {
+            Some(
+                Command::DeleteRow
+                | Command::InsertFile
+                | Command::Undo
+                | Command::Redo
+                | Command::Paste
+                | Command::Replace
+                | Command::InsertVariable
+                | Command::Reflow
+                | Command::ToggleComment
+                | Command::SpellSuggest
+                | Command::ExpandSnippet,
+            ) => true,
+            Some(Command::Cut) => self.selection().is_some(),
+            Some(_) => false,
+            None => match (self.mode, key) {
+                (EditorMode::Normal, Key::Char('x' | 'p' | 'o'))
+                | (EditorMode::Pending('d'), Key::Char('d'))
+                | (EditorMode::Visual, Key::Char('d' | 'x')) => true,
+                (EditorMode::Normal | EditorMode::Pending(_) | EditorMode::Visual, _) => false,
+                (_, Key::Char(_) | Key::Backspace | Key::Delete)
+                | (EditorMode::MoveRows, Key::Up | Key::Down) => true,
+                _ => false,
+            },
+        }
+    }
+    // asks once before the first edit of a file we can't write to
+    fn confirm_edit(&mut self) -> Result<bool, std::io::Error> {
+        if self.document.edits_allowed() {
+            return Ok(true);
+        }
+        let answer = self.prompt(&message!("edit_read_only"))?;
+        if answer.is_some_and(|answer| answer.eq_ignore_ascii_case("y")) {
+            self.document.allow_edits();
+            return Ok(true);
+        }
+        self.status_message = StatusMessage::from(message!("read_only"));
+        Ok(false)
+    }
+    fn animate_scroll(&mut self) {
+        let Some(target) = self.scroll_target else {
+            return;
+        };
+        // ease out: cover a third of the remaining distance each frame
+        let step = target.abs_diff(self.offset.y).div_ceil(3);
+        if target > self.offset.y {
+            self.offset.y = self.offset.y.saturating_add(step);
+        } else {
+            self.offset.y = self.offset.y.saturating_sub(step);
+        }
+        if self.offset.y == target {
+            self.scroll_target = None;
+        }
+    }
+
+    // a query starting with `/` is a regular expression. The cursor follows
+    // the query as it is typed and goes back if the search is cancelled.
+    fn search(&mut self) {
+        let start = self.cursor_position;
+        let history = self.search_history.queries().to_vec();
+        let on_change = |editor: &mut Self, query: &str| {
+            editor.cursor_position = start;
+            let state = editor
+                .search_state(query)
+                .ok()
+                .filter(|_| !query.is_empty());
+            editor.search_highlight = state.as_ref().map(|state| state.regex().clone());
+            if let Some(found) = state.and_then(|state| state.find(&editor.document, &start)) {
+                editor.cursor_position = found.start;
+            }
+            editor.scroll();
+        };
+        let query = self
+            .prompt_full(
+                &message!("search_prompt"),
+                on_change,
+                |_| Vec::new(),
+                &history,
+            )
+            .unwrap_or(None);
+        let Some(query) = query else {
+            self.search_highlight = None;
+            self.cursor_position = start;
+            return;
+        };
+        if !self.config.persist_search_highlight {
+            self.search_highlight = None;
+        }
+        self.search_history.push(&query);
+        // a history that can't be written is only lost at exit
+        self.search_history.save().ok();
+        let state = match self.search_state(&query) {
+            Ok(state) => state,
+            Err(error) => {
+                self.status_message = StatusMessage::error(pattern_error(&error));
+                return;
+            }
+        };
+        // the same query again goes on to the next match, like FindNext
+        let from = if self
+            .search
+            .as_ref()
+            .is_some_and(|search| search.query == query)
+        {
+            Position {
+                x: start.x.saturating_add(1),
+                ..start
+            }
+        } else {
+            start
+        };
+        self.go_to_match(&state, &from);
+        self.search = Some(state);
+    }
+    // the last search's next match, or its previous one going `backward`
+    fn search_again(&mut self, backward: bool) {
+        let Some(search) = &self.search else {
+            let key = self.config.key_name(Command::Find);
+            self.status_message = StatusMessage::from(message!("no_search", key = key));
+            return;
+        };
+        let Position { x, y } = self.cursor_position;
+        let (state, from) = if backward {
+            (search.reversed(), Position { x, y })
+        } else {
+            (
+                search.clone(),
+                Position {
+                    x: x.saturating_add(1),
+                    y,
+                },
+            )
+        };
+        self.go_to_match(&state, &from);
+        if self.config.persist_search_highlight {
+            self.search_highlight = Some(state.regex().clone());
+        }
+    }
+    fn go_to_match(&mut self, state: &SearchState, from: &Position) {
+        let Some(found) = state.find(&self.document, from) else {
+            self.status_message = StatusMessage::from(message!("not_found", query = state.query));
+            return;
+        };
+        self.selection_anchor = None;
+        self.cursor_position = found.start;
+        self.scroll();
+        if found.wrapped {
+            let message = match state.direction {
+                search::Direction::Forward => message!("wrapped_to_top"),
+                search::Direction::Backward => message!("wrapped_to_bottom"),
+            };
+            self.status_message = StatusMessage::from(message);
+        }
+    }
+    fn search_state(&self, query: &str) -> Result<SearchState, regex::Error> {
+        let mut state = SearchState::new(query, self.config.search_case)?;
+        state.wrap = self.config.search_wrap;
+        Ok(state)
+    }
+    // asks at each match from the cursor on whether to replace it: y or
+    // Space replaces it, n skips it, a or ! replaces it and the rest, b does
+    // that and replaces every match in the other open buffers too, and q,
+    // Enter or Esc stops
+    fn replace(&mut self) {
+        let Some(pattern) = self.prompt(&message!("replace_prompt")).unwrap_or(None) else {
+            return;
+        };
+        let Some(regex) = self.regex(&pattern) else {
+            return;
+        };
+        let Some(replacement) = self
+            .prompt(&message!("replace_with_prompt"))
+            .unwrap_or(None)
+        else {
+            return;
+        };
+        let mut at = self.cursor_position;
+        let mut count: usize = 0;
+        let mut all = false;
+        let mut other_buffers = false;
+        let mut found = false;
+        self.search_highlight = Some(regex.clone());
+        while let Some((y, range, with)) = self.document.next_replacement(&regex, &replacement, &at)
+        {
+            found = true;
+            let start = Position { x: range.start, y };
+            // past an empty match, so it isn't found again
+            let skip = usize::from(range.is_empty());
+            if !all {
+                self.cursor_position = start;
+                self.selection_anchor = Some(Position { x: range.end, y });
+                self.scroll();
+                match self.read_answer(&message!("replace_query", replacement = with)) {
+                    Ok(Key::Char('y' | ' ')) => (),
+                    Ok(Key::Char('n')) => {
+                        at = Position {
+                            x: range.end.saturating_add(skip),
+                            y,
+                        };
+                        continue;
+                    }
+                    Ok(Key::Char('a' | '!')) => all = true,
+                    Ok(Key::Char('b')) => {
+                        all = true;
+                        other_buffers = true;
+                    }
+                    Ok(Key::Char('q' | '\n') | Key::Esc | Key::Ctrl('c')) | Err(_) => break,
+                    Ok(_) => continue,
+                }
+            }
+            self.document.replace_range(y, range, &with);
+            count = count.saturating_add(1);
+            // after the replacement, which may have had newlines put in
+            let lines: Vec<&str> = with.split('\n').collect();
+            let last = lines.last().map_or(0, |line| line.graphemes(true).count());
+            at = match lines.len() {
+                1 => Position {
+                    x: start.x.saturating_add(last).saturating_add(skip),
+                    y,
+                },
+                len => Position {
+                    x: last.saturating_add(skip),
+                    y: y.saturating_add(len - 1),
+                },
+            };
+            self.cursor_position = Position {
+                x: at.x.saturating_sub(skip),
+                y: at.y,
+            };
+        }
+        self.selection_anchor = None;
+        self.search_highlight = None;
+        self.clamp_cursor();
+        self.scroll();
+        let message = if other_buffers {
+            self.replace_in_buffers(&regex, &replacement, count)
+        } else if found {
+            message!("replaced_all", count = count)
+        } else {
+            message!("not_found", query = pattern)
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+    // replaces every match in the buffers other than the one on screen,
+    // each as a step its own undo takes back, and tells how many went in
+    // each, `count` being the one on screen's. Read-only ones are left as
+    // they are.
+    fn replace_in_buffers(&mut self, regex: &Regex, replacement: &str, count: usize) -> String {
+        let name = |document: &Document| {
+            document
+                .file_name
+                .clone()
+                .unwrap_or_else(|| message!("no_name"))
+        };
+        let mut total = count;
+        let mut counts = vec![message!(
+            "buffer_count",
+            file = name(&self.document),
+            count = count
+        )];
+        for buffer in &mut self.buffers {
+            let document = &mut buffer.document;
+            if !document.edits_allowed() {
+                counts.push(message!("buffer_read_only", file = name(document)));
+                continue;
+            }
+            let replaced = document.replace_all(regex, replacement);
+            total = total.saturating_add(replaced);
+            if replaced > 0 {
+                counts.push(message!(
+                    "buffer_count",
+                    file = name(document),
+                    count = replaced
+                ));
+            }
+        }
+        message!(
+            "replaced_in_buffers",
+            count = total,
+            buffers = self.buffers.len().saturating_add(1),
+            counts = counts.join(", ")
+        )
+    }
+    // the key pressed in answer to `question`
+    fn read_answer(&mut self, question: &str) -> Result<Key, std::io::Error> {
+        if self.batch {
+            return Err(self.refuse_prompt(question));
+        }
+        self.status_message = StatusMessage::from(question.to_string());
+        let (ps, ts) = (Arc::clone(&self.ps), Arc::clone(&self.ts));
+        self.refresh_screen(&ps, &ts)?;
+        self.terminal.read_key()
+    }
+    fn regex(&mut self, pattern: &str) -> Option<Regex> {
+        match Regex::new(pattern) {
+            Ok(regex) => Some(regex),
+            Err(error) => {
+                self.status_message = StatusMessage::error(pattern_error(&error));
+                None
+            }
+        }
+    }
+    // inserts a file, or the output of a `!command`, at the cursor
+    fn insert_file(&mut self) {
+        let Some(source) = self.prompt(&message!("insert_file_prompt")).unwrap_or(None) else {
+            return;
+        };
+        let text = if let Some(command) = source.strip_prefix('!') {
+            match process::Command::new("sh").arg("-c").arg(command).output() {
+                Ok(output) if output.status.success() => {
+                    String::from_utf8_lossy(&output.stdout).into_owned()
+                }
+                Ok(output) => {
+                    let error = String::from_utf8_lossy(&output.stderr);
+                    let error = error.lines().next().unwrap_or_default();
+                    self.status_message = StatusMessage::from(message!(
+                        "command_failed",
+                        status = output.status,
+                        error = error
+                    ));
+                    return;
+                }
+                Err(error) => {
+                    self.status_message =
+                        StatusMessage::from(message!("cannot_run_command", error = error));
+                    return;
+                }
+            }
+        } else {
+            match fs::read_to_string(&source) {
+                Ok(text) => text,
+                Err(error) => {
+                    self.status_message =
+                        StatusMessage::from(message!("cannot_read", file = source, error = error));
+                    return;
+                }
+            }
+        };
+        self.cursor_position = self.document.insert_str(&self.cursor_position, &text);
+        self.status_message =
+            StatusMessage::from(message!("inserted_lines", count = text.lines().count()));
+    }
+    fn open_file(&mut self) {
+        let Some(file_name) = self.prompt(&message!("open_prompt")).unwrap_or(None) else {
+            return;
+        };
+        let document = match Document::open(&file_name) {
+            Ok(document) => document,
+            Err(error) => {
+                self.status_message =
+                    StatusMessage::from(message!("cannot_open", file = file_name, error = error));
+                return;
+            }
+        };
+        let previous = self.switch_to(Buffer::from(document));
+        self.shelve(previous);
+        self.status_message = StatusMessage::from(message!("opened", file = file_name));
+    }
+    // keeps a buffer that was switched away from among the others
+    fn shelve(&mut self, mut buffer: Buffer) {
+        // an untouched scratch document isn't worth keeping around, unlike
+        // a listing to come back to
+        if buffer.document.file_name.is_some()
+            || buffer.document.is_dirty()
+            || buffer.document.is_listing()
+        {
+            self.buffers.push_back(buffer);
+        }
+    }
+    fn close_buffer(&mut self) -> Result<(), std::io::Error> {
+        if self.document.is_dirty() {
+            let answer = self.prompt(&message!("close_dirty"))?;
+            if !answer.is_some_and(|answer| answer.eq_ignore_ascii_case("y")) {
+                return Ok(());
+            }
+        }
+        let next = self
+            .buffers
+            .pop_front()
+            .unwrap_or_else(|| Buffer::from(Document::default()));
+        let mut closed = self.switch_to(next);
+        // its edits were given up
+        closed.document.remove_swap().ok();
+        let Some(file_name) = closed.document.file_name else {
+            return Ok(());
+        };
+        self.status_message = StatusMessage::from(message!("closed", file = file_name));
+        if self.closed.len() >= CLOSED_BUFFERS {
+            self.closed.remove(0);
+        }
+        self.closed.push(Closed {
+            file_name,
+            cursor_position: closed.cursor_position,
+        });
+        Ok(())
+    }
+    // reads the file afresh, with the cursor back where it was
+    fn reopen_buffer(&mut self) {
+        let Some(closed) = self.closed.pop() else {
+            self.status_message = StatusMessage::from(message!("no_closed_buffers"));
+            return;
+        };
+        let document = match Document::open(&closed.file_name) {
+            Ok(document) => document,
+            Err(error) => {
+                self.status_message = StatusMessage::from(message!(
+                    "cannot_open",
+                    file = closed.file_name,
+                    error = error
+                ));
+                return;
+            }
+        };
+        let mut buffer = Buffer::from(document);
+        buffer.cursor_position = closed.cursor_position;
+        let previous = self.switch_to(buffer);
+        self.shelve(previous);
+        self.clamp_cursor();
+        self.status_message = StatusMessage::from(message!("reopened", file = closed.file_name));
+    }
+    fn find_locations(&mut self) {
+        let end = Position {
+            x: 0,
+            y: self.document.len(),
+        };
+        let mut locations = quickfix::parse(&self.document.text(&Position::default(), &end));
+        if locations.is_empty() {
+            locations = quickfix::parse(&self.clipboard);
+        }
+        if locations.is_empty() {
+            self.status_message = StatusMessage::from(message!("no_locations"));
+            return;
+        }
+        self.locations = locations;
+        self.go_to_location(0);
+    }
+    // shows the file tree with the keys, or hides it once it has them
+    fn toggle_file_tree(&mut self) {
+        if self.file_tree.as_ref().is_some_and(|tree| tree.focused) {
+            self.file_tree = None;
+        } else {
+            self.unfocus_panels();
+            let tree = self.file_tree.get_or_insert_with(FileTree::default);
+            self.status_message = match env::current_dir()
+                .map_err(|error| error.to_string())
+                .and_then(|dir| Policy::filter(&dir))
+            {
+                Ok(filter) => {
+                    tree.filter = filter;
+                    StatusMessage::from(message!("file_tree_help"))
+                }
+                Err(error) => StatusMessage::error(message!("error", error = error)),
+            };
+            tree.refresh();
+            tree.focused = true;
+        }
+        self.scroll();
+    }
+    // shows the outline with the keys, or hides it once it has them
+    fn toggle_outline(&mut self) {
+        if self.outline.as_ref().is_some_and(|outline| outline.focused) {
+            self.outline = None;
+        } else {
+            self.unfocus_panels();
+            let outline = self.outline.get_or_insert_with(Outline::new);
+            outline.focused = true;
+            outline.stale = true;
+            self.status_message = StatusMessage::from(message!("outline_help"));
+        }
+        self.scroll();
+    }
+    // the keys go back to the text
+    fn unfocus_panels(&mut self) {
+        if let Some(tree) = &mut self.file_tree {
+            tree.focused = false;
+        }
+        if let Some(outline) = &mut self.outline {
+            outline.focused = false;
+        }
+    }
+    // makes the outline again after an edit or for another buffer, and
+    // while it doesn't have the keys has it follow the cursor
+    fn update_outline(&mut self, ps: &SyntaxSet) {
+        let height = usize::from(self.terminal.size().height);
+        let Some(outline) = &mut self.outline else {
+            return;
+        };
+        if outline.stale {
+            let language = &syntax::for_document(&self.document, ps).name;
+            outline.refresh(&self.document, language);
+            if outline.is_empty() && outline.focused {
+                self.status_message = StatusMessage::from(message!("no_symbols"));
+            }
+        }
+        if !outline.focused {
+            outline.follow(self.cursor_position.y);
+        }
+        outline.scroll(height);
+    }
+    fn process_outline_key(&mut self, key: Key) {
+        let height = usize::from(self.terminal.size().height);
+        let Some(outline) = &mut self.outline else {
+            return;
+        };
+        match key {
+            Key::Up => outline.select(false, 1),
+            Key::Down => outline.select(true, 1),
+            Key::PageUp => outline.select(false, height),
+            Key::PageDown => outline.select(true, height),
+            Key::Home => outline.select(false, usize::MAX),
+            Key::End => outline.select(true, usize::MAX),
+            Key::Esc | Key::Char('\t') => outline.focused = false,
+            Key::Char('\n') => {
+                let Some(y) = outline.selected().map(|symbol| symbol.y) else {
+                    return;
+                };
+                outline.focused = false;
+                self.selection_anchor = None;
+                self.cursor_position = Position { x: 0, y };
+                self.clamp_cursor();
+                self.scroll();
+            }
+            _ => (),
+        }
+        if let Some(outline) = &mut self.outline {
+            outline.scroll(height);
+        }
+    }
+    fn process_tree_key(&mut self, key: Key) -> Result<(), std::io::Error> {
+        let height = usize::from(self.terminal.size().height);
+        match key {
+            Key::Char('n') => self.tree_create()?,
+            Key::Char('r') => self.tree_rename()?,
+            Key::Char('d') => self.tree_delete()?,
+            Key::Char('u') => self.tree_restore(),
+            Key::Char('\n') => self.tree_open(),
+            _ => (),
+        }
+        let Some(tree) = &mut self.file_tree else {
+            return Ok(());
+        };
+        match key {
+            Key::Up => tree.select(false, 1),
+            Key::Down => tree.select(true, 1),
+            Key::PageUp => tree.select(false, height),
+            Key::PageDown => tree.select(true, height),
+            Key::Home => tree.select(false, usize::MAX),
+            Key::End => tree.select(true, usize::MAX),
+            Key::Right
+                if tree
+                    .selected()
+                    .is_some_and(|entry| entry.directory && !tree.is_expanded(entry)) =>
+            {
+                tree.toggle();
+            }
+            Key::Left => tree.collapse(),
+            Key::Esc | Key::Char('\t') => tree.focused = false,
+            _ => (),
+        }
+        tree.scroll(height);
+        Ok(())
+    }
+    // opens the selected directory or closes it, or opens the selected file
+    // and goes to it
+    fn tree_open(&mut self) {
+        let Some(tree) = &mut self.file_tree else {
+            return;
+        };
+        let Some(entry) = tree.selected() else {
+            return;
+        };
+        if entry.directory {
+            tree.toggle();
+            return;
+        }
+        let file_name = entry.path.to_string_lossy().into_owned();
+        if self.show_file(&file_name) {
+            if let Some(tree) = &mut self.file_tree {
+                tree.focused = false;
+            }
+            self.status_message = StatusMessage::from(message!("opened", file = file_name));
+        }
+    }
+    fn tree_create(&mut self) -> Result<(), std::io::Error> {
+        let Some(name) = self.prompt(&message!("file_tree_new_prompt"))? else {
+            return Ok(());
+        };
+        let message = match self.file_tree.as_mut().map(|tree| tree.create(&name)) {
+            Some(Ok(path)) => {
+                self.touch_index(&path);
+                message!("file_tree_created", file = path.display())
+            }
+            Some(Err(error)) => message!("error", error = error),
+            None => return Ok(()),
+        };
+        self.status_message = StatusMessage::from(message);
+        Ok(())
+    }
+    // renames the selected entry, and the open documents that were in it
+    fn tree_rename(&mut self) -> Result<(), std::io::Error> {
+        let Some(name) = self
+            .file_tree
+            .as_ref()
+            .and_then(FileTree::selected)
+            .and_then(|entry| entry.path.file_name())
+        else {
+            return Ok(());
+        };
+        let name = name.to_string_lossy().into_owned();
+        let prompt = message!("file_tree_rename_prompt");
+        let Some(name) = self.prompt_full(&prompt, |_, _| (), |_| Vec::new(), &[name])? else {
+            return Ok(());
+        };
+        let (old, new) = match self.file_tree.as_mut().map(|tree| tree.rename(&name)) {
+            Some(Ok((old, new))) => {
+                self.touch_index(&old);
+                self.touch_index(&new);
+                (old, new)
+            }
+            Some(Err(error)) => {
+                self.status_message = StatusMessage::error(message!("error", error = error));
+                return Ok(());
+            }
+            None => return Ok(()),
+        };
+        for document in iter::once(&mut self.document)
+            .chain(self.buffers.iter_mut().map(|buffer| &mut buffer.document))
+        {
+            let moved = document.file_name.as_deref().and_then(|file_name| {
+                let rest = Path::new(file_name).strip_prefix(&old).ok()?;
+                Some(
+                    new.join(rest)
+                        .to_string_lossy()
+                        .trim_end_matches('/')
+                        .to_string(),
+                )
+            });
+            if moved.is_some() {
+                document.file_name = moved;
+            }
+        }
+        self.status_message = StatusMessage::from(message!(
+            "file_tree_renamed",
+            old = old.display(),
+            new = new.display()
+        ));
+        Ok(())
+    }
+    // moves the selected file or directory to the trash once that is
+    // confirmed
+    fn tree_delete(&mut self) -> Result<(), std::io::Error> {
+        let Some(path) = self
+            .file_tree
+            .as_ref()
+            .and_then(FileTree::selected)
+            .map(|entry| entry.path.clone())
+        else {
+            return Ok(());
+        };
+        let answer = self.prompt(&message!("file_tree_delete", file = path.display()))?;
+        if !answer.is_some_and(|answer| answer.eq_ignore_ascii_case("y")) {
+            return Ok(());
+        }
+        let Some(trash) = self.config.trash() else {
+            self.status_message = StatusMessage::error(message!("file_tree_no_trash"));
+            return Ok(());
+        };
+        let message = match self.file_tree.as_mut().map(|tree| tree.delete(&trash)) {
+            Some(Ok((path, trashed))) => {
+                self.touch_index(&path);
+                let message = message!("file_tree_deleted", file = path.display());
+                self.deleted.push((path, trashed));
+                message
+            }
+            Some(Err(error)) => message!("error", error = error),
+            None => return Ok(()),
+        };
+        self.status_message = StatusMessage::from(message);
+        Ok(())
+    }
+    // moves what the tree deleted last back out of the trash
+    fn tree_restore(&mut self) {
+        let Some((path, trashed)) = self.deleted.pop() else {
+            self.status_message = StatusMessage::from(message!("file_tree_nothing_deleted"));
+            return;
+        };
+        let Some(tree) = &mut self.file_tree else {
+            return;
+        };
+        self.status_message = match tree.restore(&path, &trashed) {
+            Ok(()) => {
+                if let Some(index) = &mut self.index {
+                    index.touch(&path);
+                }
+                StatusMessage::from(message!("file_tree_restored", file = path.display()))
+            }
+            Err(error) => {
+                self.deleted.push((path, trashed));
+                StatusMessage::error(message!("error", error = error))
+            }
+        };
+    }
+    // lists the lines of the files under the working directory that match
+    // a query, in a buffer whose rows Enter opens
+    fn project_search(&mut self) {
+        let history = self.search_history.queries().to_vec();
+        let prompt = message!("project_search_prompt");
+        let Some(query) = self
+            .prompt_full(&prompt, |_, _| (), |_| Vec::new(), &history)
+            .unwrap_or(None)
+        else {
+            return;
+        };
+        self.search_history.push(&query);
+        self.search_history.save().ok();
+        let state = match self.search_state(&query) {
+            Ok(state) => state,
+            Err(error) => {
+                self.status_message = StatusMessage::error(pattern_error(&error));
+                return;
+            }
+        };
+        // the index's files once it has them, saving a walk
+        let indexed = self.start_index().and_then(Index::files);
+        let results = env::current_dir()
+            .map_err(|error| error.to_string())
+            .and_then(|dir| {
+                if let Some(files) = indexed {
+                    return Ok(project_search::search_files(&dir, files, state.regex()));
+                }
+                let filter = Policy::filter(&dir)?;
+                project_search::search(&dir, state.regex(), &filter)
+                    .map_err(|error| error.to_string())
+            });
+        let results = match results {
+            Ok(results) => results,
+            Err(error) => {
+                self.status_message = StatusMessage::error(message!("error", error = error));
+                return;
+            }
+        };
+        let count = results.hits.len();
+        if count == 0 {
+            self.status_message =
+                StatusMessage::from(message!("project_no_matches", query = query));
+            return;
+        }
+        let id = if results.capped {
+            "project_results_capped"
+        } else {
+            "project_results"
+        };
+        let header = message!(id, count = count, files = results.files, query = query);
+        let lines = results.listing();
+        self.show_listing(
+            header,
+            lines,
+            results.hits.into_iter().map(|hit| hit.location).collect(),
+        );
+    }
+    // a listing buffer of `lines` under `header`, one a location
+    fn show_listing(&mut self, header: String, lines: Vec<String>, locations: Vec<Location>) {
+        let text: Vec<String> = iter::once(header).chain(lines).collect();
+        // for NextLocation to go on to the first
+        self.location = locations.len().saturating_sub(1);
+        self.locations = locations;
+        let previous = self.switch_to(Buffer::from(Document::listing(&text.join("\n"))));
+        self.shelve(previous);
+        self.cursor_position = Position { x: 0, y: 1 };
+        self.scroll();
+        let next = self.config.key_name(Command::NextLocation);
+        let save = self.config.key_name(Command::Save);
+        self.status_message =
+            StatusMessage::from(message!("project_results_opened", next = next, save = save));
+    }
+    // the tags file, read again when it changed, None after saying why
+    // there is none
+    fn tags(&mut self) -> Option<&Tags> {
+        if !Tags::exists() {
+            // what the index found stands in for a tags file
+            self.tags = None;
+            let walking = self
+                .start_index()
+                .filter(|index| index.tags().is_none())
+                .and_then(Index::walking);
+            if let Some(count) = walking {
+                self.status_message = StatusMessage::from(message!("indexing_tags", count = count));
+            }
+            return self.index.as_ref().and_then(Index::tags);
+        }
+        if self.tags.as_ref().is_none_or(Tags::is_stale) {
+            match Tags::load() {
+                Ok(tags) => self.tags = Some(tags),
+                Err(error) => {
+                    self.tags = None;
+                    self.status_message = StatusMessage::error(message!("error", error = error));
+                }
+            }
+        }
+        self.tags.as_ref()
+    }
+    // the index, started or walked again if it is old, None after saying
+    // why there is none
+    fn start_index(&mut self) -> Option<&Index> {
+        if let Some(index) = &mut self.index {
+            index.refresh();
+        } else {
+            let started = env::current_dir()
+                .map_err(|error| error.to_string())
+                .and_then(|dir| Ok(Index::start(dir.clone(), Policy::filter(&dir)?)));
+            match started {
+                Ok(index) => self.index = Some(index),
+                Err(error) => {
+                    self.status_message = StatusMessage::error(message!("error", error = error));
+                }
+            }
+        }
+        self.index.as_ref()
+    }
+    // takes a file or directory written or changed into the index
+    fn touch_index(&mut self, path: &Path) {
+        if let Some(index) = &mut self.index {
+            index.touch(path);
+        }
+    }
+    fn tag_command(&mut self, command: Command) {
+        match command {
+            Command::GoToDefinition => self.go_to_definition(),
+            Command::SearchTags => self.search_tags(),
+            _ => self.jump_back(),
+        }
+    }
+    fn go_to_definition(&mut self) {
+        let at = self.cursor_position;
+        let word_chars = self.word_chars();
+        let Some(name) = self.document.row(at.y).and_then(|row| {
+            let word = row.word_at(at.x, &word_chars)?;
+            Some(row.substring(word.start, word.end))
+        }) else {
+            self.status_message = StatusMessage::from(message!("no_word"));
+            return;
+        };
+        let Some(tags) = self.tags() else {
+            return;
+        };
+        let found = tags::locations(&tags.named(&name));
+        if found.is_empty() {
+            self.status_message = StatusMessage::from(message!("no_tag", name = name));
+            return;
+        }
+        self.go_to_tags(&name, found);
+    }
+    fn search_tags(&mut self) {
+        let Some(query) = self.prompt(&message!("search_tags_prompt")).unwrap_or(None) else {
+            return;
+        };
+        let Some(tags) = self.tags() else {
+            return;
+        };
+        let found = tags::locations(&tags.matching(&query));
+        if found.is_empty() {
+            self.status_message = StatusMessage::from(message!("no_tags_matching", query = query));
+            return;
+        }
+        self.go_to_tags(&query, found);
+    }
+    // to the one place, or a listing of them, keeping where the cursor was
+    // for JumpBack
+    fn go_to_tags(&mut self, query: &str, found: Vec<(Location, String)>) {
+        if let Some(file_name) = self.document.file_name.clone() {
+            self.jumps.push((file_name, self.cursor_position));
+            let excess = self.jumps.len().saturating_sub(JUMPS);
+            self.jumps.drain(..excess);
+        }
+        if let [(location, text)] = found.as_slice() {
+            let message = message!(
+                "tag",
+                file = location.file_name,
+                line = location.line,
+                text = text.trim()
+            );
+            self.locations = found.into_iter().map(|(location, _)| location).collect();
+            self.go_to_location(0);
+            self.status_message = StatusMessage::from(message);
+            return;
+        }
+        let header = message!("tags_listed", count = found.len(), query = query);
+        let lines = found
+            .iter()
+            .map(
+                |(
+                    Location {
+                        file_name,
+                        line,
+                        column,
+                    },
+                    text,
+                )| format!("{file_name}:{line}:{column}: {}", text.trim()),
+            )
+            .collect();
+        self.show_listing(
+            header,
+            lines,
+            found.into_iter().map(|(location, _)| location).collect(),
+        );
+    }
+    fn jump_back(&mut self) {
+        let Some((file_name, at)) = self.jumps.pop() else {
+            self.status_message = StatusMessage::from(message!("no_jumps"));
+            return;
+        };
+        if !self.show_file(&file_name) {
+            return;
+        }
+        self.selection_anchor = None;
+        self.cursor_position = at;
+        self.clamp_cursor();
+        self.scroll();
+        self.status_message = StatusMessage::from(message!(
+            "jumped_back",
+            file = file_name,
+            line = at.y.saturating_add(1)
+        ));
+    }
+    // runs `cargo check`, what it finds showing in the files once it is done
+    fn cargo_check(&mut self) {
+        if self.checking.is_some() {
+            self.status_message = StatusMessage::from(message!("check_running"));
+            return;
+        }
+        self.checking = Some(diagnostics::check());
+        self.status_message = StatusMessage::from(message!("checking"));
+    }
+    // takes what the check found once it is done, true if it just was
+    fn receive_diagnostics(&mut self) -> bool {
+        let Some(Ok(found)) = self.checking.as_ref().map(Receiver::try_recv) else {
+            return false;
+        };
+        self.checking = None;
+        let found = match found {
+            Ok(found) => found,
+            Err(error) => {
+                self.status_message = StatusMessage::error(message!("check_failed", error = error));
+                return true;
+            }
+        };
+        let count = |severity: Severity| {
+            found
+                .iter()
+                .filter(|(_, diagnostic)| diagnostic.severity == severity)
+                .count()
+        };
+        let (errors, warnings) = (count(Severity::Error), count(Severity::Warning));
+        let mut files: Vec<&str> = found
+            .iter()
+            .map(|(file_name, _)| file_name.as_str())
+            .collect();
+        files.sort_unstable();
+        files.dedup();
+        let message = if found.is_empty() {
+            message!("check_clean")
+        } else {
+            let key = self.config.key_name(Command::NextDiagnostic);
+            message!(
+                "checked",
+                errors = errors,
+                warnings = warnings,
+                files = files.len(),
+                key = key
+            )
+        };
+        self.status_message = StatusMessage::from(message);
+        self.checked = found;
+        self.checks = self.checks.saturating_add(1);
+        true
+    }
+    // gives each open document what the last check found in its file, once
+    // it is read
+    fn attach_diagnostics(&mut self) {
+        let (checks, checked) = (self.checks, &self.checked);
+        for document in iter::once(&mut self.document)
+            .chain(self.buffers.iter_mut().map(|buffer| &mut buffer.document))
+        {
+            if document.checked() == checks || document.loading_progress().is_some() {
+                continue;
+            }
+            let found = document.file_name.as_deref().map_or_else(Vec::new, |name| {
+                checked
+                    .iter()
+                    .filter(|(file_name, _)| same_file(file_name, name))
+                    .map(|(_, diagnostic)| diagnostic.clone())
+                    .collect()
+            });
+            document.set_diagnostics(checks, found);
+        }
+    }
+    // to the diagnostic after the cursor, or before it, around the ends,
+    // or to the first of another file when there are none in this one
+    fn step_diagnostic(&mut self, forward: bool) {
+        let mut first = false;
+        if self.document.diagnostics().is_empty() {
+            let Some(file_name) = self.checked.first().map(|(file_name, _)| file_name.clone())
+            else {
+                let key = self.config.key_name(Command::CargoCheck);
+                self.status_message = StatusMessage::from(message!("no_diagnostics", key = key));
+                return;
+            };
+            if !self.show_file(&file_name) {
+                return;
+            }
+            self.attach_diagnostics();
+            first = true;
+        }
+        let diagnostics = self.document.diagnostics();
+        let count = diagnostics.len();
+        let at = (self.cursor_position.y, self.cursor_position.x);
+        let starts = |diagnostic: &Diagnostic| (diagnostic.start.y, diagnostic.start.x);
+        let index = if first {
+            0
+        } else if forward {
+            diagnostics
+                .iter()
+                .position(|diagnostic| starts(diagnostic) > at)
+                .unwrap_or(0)
+        } else {
+            diagnostics
+                .iter()
+                .rposition(|diagnostic| starts(diagnostic) < at)
+                .unwrap_or(count.saturating_sub(1))
+        };
+        let Some(diagnostic) = diagnostics.get(index).cloned() else {
+            let key = self.config.key_name(Command::CargoCheck);
+            self.status_message = StatusMessage::from(message!("no_diagnostics", key = key));
+            return;
+        };
+        self.selection_anchor = None;
+        self.cursor_position = diagnostic.start;
+        self.clamp_cursor();
+        self.scroll();
+        self.status_message = StatusMessage::from(message!(
+            "diagnostic",
+            number = index.saturating_add(1),
+            count = count,
+            severity = message!(diagnostic.severity.id()),
+            message = diagnostic.message
+        ));
+    }
+    // what a check found where the cursor is, in the message bar
+    fn show_diagnostic(&mut self) {
+        if let Some(diagnostic) = self.document.diagnostic_at(self.cursor_position) {
+            let severity = message!(diagnostic.severity.id());
+            self.status_message = StatusMessage::from(message!(
+                "diagnostic_here",
+                severity = severity,
+                message = diagnostic.message
+            ));
+        }
+    }
+    // goes to the place named on the cursor's row of a listing
+    fn open_listed(&mut self) {
+        let row = self
+            .document
+            .row(self.cursor_position.y)
+            .map_or("", Row::as_str);
+        let Some(location) = quickfix::parse(row).into_iter().next() else {
+            return;
+        };
+        let index = if let Some(index) = self.locations.iter().position(|known| *known == location)
+        {
+            index
+        } else {
+            self.locations = vec![location];
+            0
+        };
+        self.go_to_location(index);
+    }
+    // around the list, from the last one to the first and back
+    fn step_location(&mut self, forward: bool) {
+        let count = self.locations.len();
+        if count == 0 {
+            let key = self.config.key_name(Command::Locations);
+            self.status_message = StatusMessage::from(message!("no_locations_yet", key = key));
+            return;
+        }
+        let index = if forward {
+            self.location.saturating_add(1) % count
+        } else {
+            self.location
+                .checked_sub(1)
+                .unwrap_or(count.saturating_sub(1))
+        };
+        self.go_to_location(index);
+    }
+    // shows the location's file, from the open buffers when it is among them
+    fn go_to_location(&mut self, index: usize) {
+        let Some(location) = self.locations.get(index) else {
+            return;
+        };
+        let (file_name, line, column) =
+            (location.file_name.clone(), location.line, location.column);
+        self.location = index;
+        if !self.show_file(&file_name) {
+            return;
+        }
+        self.selection_anchor = None;
+        self.cursor_position = Position {
+            x: column.saturating_sub(1),
+            y: line.saturating_sub(1),
+        };
+        self.clamp_cursor();
+        self.scroll();
+        self.status_message = StatusMessage::from(message!(
+            "location",
+            number = index.saturating_add(1),
+            count = self.locations.len(),
+            file = file_name,
+            line = line,
+            column = column
+        ));
+    }
+    // switches to the buffer of `file_name`, opening it if it isn't open
+    // yet, false after saying why it can't be
+    fn show_file(&mut self, file_name: &str) -> bool {
+        let open = |document: &Document| {
+            document
+                .file_name
+                .as_deref()
+                .is_some_and(|name| same_file(name, file_name))
+        };
+        if open(&self.document) {
+            return true;
+        }
+        let buffer = match self
+            .buffers
+            .iter()
+            .position(|buffer| open(&buffer.document))
+        {
+            Some(position) => self.buffers.remove(position),
+            None => match Document::open(file_name) {
+                Ok(document) => Some(Buffer::from(document)),
+                Err(error) => {
+                    self.status_message = StatusMessage::from(message!(
+                        "cannot_open",
+                        file = file_name,
+                        error = error
+                    ));
+                    return false;
+                }
+            },
+        };
+        if let Some(buffer) = buffer {
+            let previous = self.switch_to(buffer);
+            self.shelve(previous);
+        }
+        true
+    }
+    // to the next buffer, or the one before, around the list
+    fn cycle_buffer(&mut self, forward: bool) {
+        let next = if forward {
+            self.buffers.pop_front()
+        } else {
+            self.buffers.pop_back()
+        };
+        let Some(next) = next else {
+            self.status_message = StatusMessage::from(message!("no_other_buffers"));
+            return;
+        };
+        let previous = self.switch_to(next);
+        if forward {
+            self.buffers.push_back(previous);
+        } else {
+            self.buffers.push_front(previous);
+        }
+        let name = self
+            .document
+            .file_name
+            .clone()
+            .unwrap_or_else(|| message!("no_name"));
+        self.status_message = StatusMessage::from(message!("switched_to", file = name));
+    }
+    // shows `buffer`, handing back the one it replaces
+    fn switch_to(&mut self, buffer: Buffer) -> Buffer {
+        self.selection_anchor = None;
+        if let Some(outline) = &mut self.outline {
+            outline.stale = true;
+        }
+        self.scroll_target = None;
+        self.document.unsubscribe(self.subscription);
+        let previous = Buffer {
+            document: mem::replace(&mut self.document, buffer.document),
+            cursor_position: mem::replace(&mut self.cursor_position, buffer.cursor_position),
+            offset: mem::replace(&mut self.offset, buffer.offset),
+        };
+        self.subscribe();
+        self.layer_config();
+        // the buffer may have shrunk while it wasn't on screen, by a
+        // replacement in all buffers or a reload
+        self.clamp_cursor();
+        self.offset.y = cmp::min(self.offset.y, self.cursor_position.y);
+        // both panes show the current buffer
+        if let Some(split) = &mut self.split {
+            split.other = Pane {
+                cursor_position: self.cursor_position,
+                offset: self.offset,
+                selection_anchor: None,
+            };
+        }
+        previous
+    }
+    // the settings for the current buffer, from the global ones
+    fn layer_config(&mut self) {
+        self.config = self.global_config.layered(
+            self.document.file_name.as_deref(),
+            &self.document.local_settings,
+        );
+    }
+    // a setting for every buffer, which like vim's `:set` the current one
+    // takes over its own for it
+    fn set_global(&mut self, setting: &str) -> Result<(), String> {
+        self.global_config.set(setting)?;
+        let name = config::setting_name(setting);
+        self.document
+            .local_settings
+            .retain(|local| config::setting_name(local) != name);
+        self.layer_config();
+        Ok(())
+    }
+    // a setting for the current buffer only, over the global ones and its
+    // filetype's
+    fn set_local(&mut self, setting: &str) -> Result<(), String> {
+        self.config.set(setting)?;
+        let name = config::setting_name(setting);
+        let local = &mut self.document.local_settings;
+        local.retain(|local| config::setting_name(local) != name);
+        local.push(setting.to_string());
+        Ok(())
+    }
+    // follows the changes of the document on screen from now on, nothing
+    // known about another one holds for it
+    fn subscribe(&mut self) {
+        self.subscription = self.document.subscribe();
+        self.scope = None;
+    }
+    // keeps what is worked out from the document in step with its changes
+    fn follow_changes(&mut self) {
+        let changes = self.document.changes(self.subscription);
+        if let Some(message) = self
+            .tutor
+            .as_mut()
+            .filter(|_| !changes.is_empty())
+            .and_then(|tutor| tutor.check(&self.document))
+        {
+            self.status_message = StatusMessage::from(message);
+        }
+        if let Some(outline) = self.outline.as_mut().filter(|_| !changes.is_empty()) {
+            outline.stale = true;
+        }
+        for change in changes {
+            if self
+                .scope
+                .as_ref()
+                .is_some_and(|(y, ..)| change.first_row() <= *y)
+            {
+                self.scope = None;
+            }
+            // the other pane stays on the text it shows
+            if let Some(split) = &mut self.split {
+                split.other.cursor_position.y = change.shift(split.other.cursor_position.y);
+                split.other.offset.y = change.shift(split.other.offset.y);
+                if let Some(anchor) = &mut split.other.selection_anchor {
+                    anchor.y = change.shift(anchor.y);
+                }
+            }
+        }
+    }
+    // the open files with where they are scrolled to, and the theme
+    fn session(&self) -> Session {
+        let current = (&self.document, self.cursor_position, self.offset);
+        let others = self
+            .buffers
+            .iter()
+            .map(|buffer| (&buffer.document, buffer.cursor_position, buffer.offset));
+        let buffers = iter::once(current)
+            .chain(others)
+            .filter_map(|(document, Position { x, y }, offset)| {
+                let file_name = document.file_name.clone()?;
+                Some(Entry {
+                    file_name,
+                    x,
+                    y,
+                    offset_x: offset.x,
+                    offset_y: offset.y,
+                })
+            })
+            .collect();
+        Session {
+            buffers,
+            theme: Some(self.config.theme.clone()),
+        }
+    }
+    // what --session opens again, left as it was when nothing was opened
+    // from a file this time
+    fn save_last_session(&self) {
+        let session = self.session();
+        if !session.buffers.is_empty() {
+            // the screen is gone by now, so there is nowhere to say it failed
+            session::save_last(&session).ok();
+        }
+    }
+    fn save_session(&mut self) {
+        let Some(name) = self
+            .prompt(&message!("save_session_prompt"))
+            .unwrap_or(None)
+        else {
+            return;
+        };
+        self.status_message = match session::save(&name, &self.session()) {
+            Ok(()) => StatusMessage::from(message!("saved_session", name = name)),
+            Err(error) => StatusMessage::error(message!("error", error = error)),
+        };
+    }
+    // opens the session's files in place of the open ones, except for those
+    // with unsaved edits, which stay open behind them
+    fn load_session(&mut self) {
+        let names = session::names();
+        if names.is_empty() {
+            self.status_message = StatusMessage::from(message!("no_sessions"));
+            return;
+        }
+        let prompt = message!("load_session_prompt", names = names.join(", "));
+        let Some(name) = self.prompt(&prompt).unwrap_or(None) else {
+            return;
+        };
+        let session = match session::load(&name) {
+            Ok(session) => session,
+            Err(error) => {
+                self.status_message = StatusMessage::error(message!("error", error = error));
+                return;
+            }
+        };
+        if let Some(theme) = session.theme.filter(|theme| *theme != self.config.theme) {
+            self.set_theme(&theme);
+        }
+        let mut opened = open_session(session.buffers);
+        let Some(first) = opened.pop_front() else {
+            self.status_message = StatusMessage::from(message!("session_unopened", name = name));
+            return;
+        };
+        let previous = self.switch_to(first);
+        self.clamp_cursor();
+        let replaced: Vec<Buffer> = iter::once(previous).chain(self.buffers.drain(..)).collect();
+        for mut buffer in replaced {
+            if buffer.document.is_dirty() {
+                opened.push_back(buffer);
+            } else if let Some(file_name) = buffer.document.file_name {
+                self.closed.push(Closed {
+                    file_name,
+                    cursor_position: buffer.cursor_position,
+                });
+            }
+        }
+        let excess = self.closed.len().saturating_sub(CLOSED_BUFFERS);
+        self.closed.drain(..excess);
+        self.buffers = opened;
+        self.status_message = StatusMessage::from(message!("loaded_session", name = name));
+    }
+    fn any_dirty(&mut self) -> bool {
+        self.document.is_dirty()
+            || self
+                .buffers
+                .iter_mut()
+                .any(|buffer| buffer.document.is_dirty())
+    }
+    // inserts a built-in variable by name, or a template using ${name}
+    fn insert_variable(&mut self) {
+        let prompt = message!(
+            "insert_variable_prompt",
+            names = variables::NAMES.join(", ")
+        );
+        let Some(answer) = self.prompt(&prompt).unwrap_or(None) else {
+            return;
+        };
+        let file_name = self.document.file_name.as_deref();
+        let text = if answer.contains("${") {
+            variables::expand(&answer, file_name, &self.config)
+        } else if let Some(value) = variables::value(&answer, file_name, &self.config) {
+            value
+        } else {
+            self.status_message = StatusMessage::from(message!("no_value", name = answer));
+            return;
+        };
+        self.delete_selection();
+        self.cursor_position = self.document.insert_str(&self.cursor_position, &text);
+    }
+    fn undo(&mut self) {
+        if let Some(position) = self.document.undo() {
+            self.cursor_position = position;
+        } else {
+            self.status_message = StatusMessage::from(message!("nothing_to_undo"));
+        }
+    }
+    fn redo(&mut self) {
+        if let Some(position) = self.document.redo() {
+            self.cursor_position = position;
+        } else {
+            self.status_message = StatusMessage::from(message!("nothing_to_redo"));
+        }
+    }
+    // to a state of the undo tree, on whichever branch it is
+    fn undo_to(&mut self, state: usize) {
+        if let Some(position) = self.document.undo_to(state) {
+            self.cursor_position = position;
+            self.clamp_cursor();
+            self.scroll();
+            self.status_message = StatusMessage::from(message!("undo_state", state = state));
+        } else {
+            self.status_message = StatusMessage::from(message!("no_undo_state", state = state));
+        }
+    }
+    // opens the undo tree of the document in a buffer of its own, a line a
+    // state with the one it was made from and when, the current one marked
+    fn show_undo_tree(&mut self) {
+        let history = self.document.history();
+        let states = history.states();
+        if states.len() < 2 {
+            self.status_message = StatusMessage::from(message!("nothing_to_undo"));
+            return;
+        }
+        let current = history.current();
+        let of = self.document.file_name.clone();
+        let file = of.clone().unwrap_or_else(|| message!("no_name"));
+        let mut lines = vec![message!("undo_tree", file = file)];
+        for state in &states {
+            let marker = if state.number == current { '>' } else { ' ' };
+            let text = if state.number == 0 {
+                message!("undo_root")
+            } else {
+                message!(
+                    "undo_node",
+                    state = state.number,
+                    parent = state.parent,
+                    age = recovery::age(state.made.elapsed().as_secs())
+                )
+            };
+            lines.push(format!("{marker} {}{text}", "  ".repeat(state.level)));
+        }
+        let (y, level) = states
+            .iter()
+            .enumerate()
+            .find(|(_, state)| state.number == current)
+            .map_or((0, 0), |(index, state)| {
+                (index.saturating_add(1), state.level)
+            });
+        let previous = self.switch_to(Buffer::from(Document::undo_tree(&lines.join("\n"), of)));
+        self.shelve(previous);
+        // on the current state's number
+        self.cursor_position = Position {
+            x: level.saturating_mul(2).saturating_add(2),
+            y,
+        };
+        self.scroll();
+        let key = self.config.key_name(Command::CloseBuffer);
+        self.status_message = StatusMessage::from(message!("undo_tree_opened", key = key));
+    }
+    // goes back to the buffer the undo tree on screen is of, in the state on
+    // the cursor's row
+    fn open_listed_state(&mut self) -> Result<(), std::io::Error> {
+        let of = self.document.states_of().map(str::to_string);
+        let row = self
+            .document
+            .row(self.cursor_position.y)
+            .map_or("", Row::as_str);
+        let digits: String = row
+            .trim_start_matches(['>', ' '])
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .collect();
+        let Ok(state) = digits.parse() else {
+            return Ok(());
+        };
+        let index =
+            self.buffers
+                .iter()
+                .position(|buffer| match (&buffer.document.file_name, &of) {
+                    (Some(name), Some(of)) => same_file(name, of),
+                    (None, None) => !buffer.document.is_read_only(),
+                    _ => false,
+                });
+        let Some(buffer) = index.and_then(|index| self.buffers.remove(index)) else {
+            self.status_message = StatusMessage::from(message!("undo_tree_gone"));
+            return Ok(());
+        };
+        let tree = self.switch_to(buffer);
+        self.shelve(tree);
+        if self.confirm_edit()? {
+            self.undo_to(state);
+        }
+        Ok(())
+    }
+    fn inspect_grapheme(&mut self) {
+        let Position { x, y } = self.cursor_position;
+        let message = match self.document.row(y).and_then(|row| row.grapheme(x)) {
+            Some(grapheme) => unicode::describe(grapheme),
+            None => message!("no_character"),
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+    // moves the cursor's row, or the selected ones, a row up or down, the
+    // cursor and the selection with them
+    fn move_row(&mut self, key: Key) {
+        let (first, last) = match self.selection() {
+            Some((start, end)) if end.x == 0 && end.y > start.y => (start.y, end.y - 1),
+            Some((start, end)) => (start.y, end.y),
+            None => (self.cursor_position.y, self.cursor_position.y),
+        };
+        let up = key == Key::Up;
+        if !self.document.move_rows(first, last, up) {
+            return;
+        }
+        let shift = |position: &mut Position| {
+            position.y = if up {
+                position.y.saturating_sub(1)
+            } else {
+                position.y.saturating_add(1)
+            };
+        };
+        shift(&mut self.cursor_position);
+        if let Some(anchor) = &mut self.selection_anchor {
+            shift(anchor);
+        }
+        self.scroll();
+    }
+    // sets or goes to the mark named by the next key
+    fn mark(&mut self, command: Command) -> Result<(), std::io::Error> {
+        let set = command == Command::SetMark;
+        let question = if set {
+            message!("set_mark_prompt")
+        } else {
+            message!("go_to_mark_prompt")
+        };
+        let key = self.read_answer(&question)?;
+        self.status_message = StatusMessage::from(String::new());
+        match key {
+            Key::Char(name) if set => self.set_mark(name),
+            Key::Char(name) => self.go_to_mark(name),
+            _ => (),
+        }
+        Ok(())
+    }
+    fn set_mark(&mut self, name: char) {
+        let message = if name.is_ascii_alphabetic() {
+            self.document.set_mark(name, self.cursor_position);
+            message!("mark_set", name = name)
+        } else {
+            message!("mark_not_letter")
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+    fn go_to_mark(&mut self, name: char) {
+        if let Some(at) = self.document.mark(name) {
+            self.cursor_position = at;
+            self.scroll();
+        } else {
+            self.status_message = StatusMessage::from(message!("no_mark", name = name));
+        }
+    }
+    fn dirty_quit(&mut self) -> Result<(), std::io::Error> {
+        if self.batch {
+            return Err(self.refuse_prompt(&message!("quit_dirty")));
+        }
+        let (ps, ts) = (Arc::clone(&self.ps), Arc::clone(&self.ts));
+        loop {
+            self.status_message = StatusMessage::from(message!("quit_dirty"));
+            self.refresh_screen(&ps, &ts)?;
+            match self.terminal.read_key()? {
+                Key::Char('\n') => {
+                    self.should_quit = true;
+                    self.aborted = true;
+                    break;
+                }
+                Key::Esc => {
+                    self.status_message = StatusMessage::from(help(&self.config));
+                    break;
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+    fn save(&mut self) {
+        if self.document.is_listing() && self.write_back() {
+            return;
+        }
+        if self.document.file_name.is_none() {
+            let new_name = self.prompt(&message!("save_as_prompt")).unwrap_or(None);
+            if new_name.is_none() {
+                self.status_message = StatusMessage::from(message!("save_aborted"));
+                return;
+            }
+            self.document.file_name = new_name;
+            // the new name may say more about the language than the text did
+            self.document.syntax = None;
+            self.layer_config();
+        }
+        if let Some(directory) = self.missing_directory() {
+            let question = message!("create_directory", directory = directory.display());
+            let answer = self.prompt(&question).unwrap_or(None);
+            if !answer.is_some_and(|answer| answer.eq_ignore_ascii_case("y")) {
+                self.status_message = StatusMessage::from(message!("save_aborted"));
+                return;
+            }
+            if let Err(error) = fs::create_dir_all(&directory) {
+                self.status_message = StatusMessage::from(message!(
+                    "cannot_create_directory",
+                    directory = directory.display(),
+                    error = error
+                ));
+                return;
+            }
+        }
+        if self.document.is_incomplete() {
+            self.status_message = StatusMessage::from(message!("save_incomplete"));
+            return;
+        }
+        let file_name = self.document.file_name.clone().unwrap_or_default();
+        let policy = match Policy::for_file(&file_name) {
+            Ok(policy) => policy,
+            Err(error) => {
+                self.status_message = StatusMessage::error(message!("error", error = error));
+                return;
+            }
+        };
+        let violations = policy.apply(&mut self.document, self.config.tab_width);
+        let saved = self.document.save(&policy, self.config.on_save());
+        self.clamp_cursor();
+        match saved {
+            Ok(cleaned) => {
+                self.touch_index(Path::new(&file_name));
+                let message = if let Err(error) = self.document.remove_swap() {
+                    message!("saved_swap_left", error = error)
+                } else if !violations.is_empty() {
+                    message!(
+                        "saved_with_violations",
+                        violations = violations.join(&message!("list_and"))
+                    )
+                } else if cleaned > 0 {
+                    message!("saved_cleaned", count = cleaned)
+                } else {
+                    message!("saved")
+                };
+                self.status_message = StatusMessage::from(message);
+            }
+            Err(error) => {
+                self.status_message = StatusMessage::error(message!("cannot_write", error = error));
+            }
+        }
+    }
+    // writes the lines edited in a listing back over the lines of the files
+    // they came from, after asking, false when none were edited. A file open
+    // in a buffer is edited there, and saved unless it had unsaved edits.
+    fn write_back(&mut self) -> bool {
+        let rows: Vec<String> = (0..self.document.len())
+            .filter_map(|y| self.document.row(y))
+            .map(|row| row.as_str().to_string())
+            .collect();
+        let edits = project_search::edits(self.document.listed().unwrap_or_default(), &rows);
+        if edits.is_empty() {
+            return false;
+        }
+        let mut files: BTreeMap<String, Vec<Edit>> = BTreeMap::new();
+        for edit in edits {
+            files
+                .entry(edit.location.file_name.clone())
+                .or_default()
+                .push(edit);
+        }
+        let count: usize = files.values().map(Vec::len).sum();
+        let question = message!("write_back_confirm", count = count, files = files.len());
+        let answer = self.prompt(&question).unwrap_or(None);
+        if !answer.is_some_and(|answer| answer.eq_ignore_ascii_case("y")) {
+            self.status_message = StatusMessage::from(message!("save_aborted"));
+            return true;
+        }
+        let (mut written, mut unsaved): (usize, Vec<String>) = (0, Vec::new());
+        for (file_name, edits) in &files {
+            match self.write_back_file(file_name, edits) {
+                Ok((applied, saved)) => {
+                    written = written.saturating_add(applied);
+                    if !saved {
+                        unsaved.push(file_name.clone());
+                    }
+                }
+                Err(error) => {
+                    self.status_message = StatusMessage::error(message!(
+                        "cannot_write",
+                        error = format!("{file_name}: {error}")
+                    ));
+                    return true;
+                }
+            }
+        }
+        self.document.relist();
+        let mut message = message!("written_back", count = written, files = files.len());
+        if written < count {
+            let stale = message!("written_back_stale", count = count.saturating_sub(written));
+            message = format!("{message} {stale}");
+        }
+        if !unsaved.is_empty() {
+            let left = message!("written_back_unsaved", files = unsaved.join(", "));
+            message = format!("{message} {left}");
+        }
+        self.status_message = StatusMessage::from(message);
+        true
+    }
+    // the edits to `file_name` put in its buffer, or in the file if it isn't
+    // open, with how many of them were and whether it was saved
+    fn write_back_file(
+        &mut self,
+        file_name: &str,
+        edits: &[Edit],
+    ) -> Result<(usize, bool), String> {
+        let open = self.buffers.iter().position(|buffer| {
+            buffer
+                .document
+                .file_name
+                .as_deref()
+                .is_some_and(|name| same_file(name, file_name))
+        });
+        let mut unopened = None;
+        let document = match open {
+            Some(index) => &mut self.buffers[index].document,
+            None => unopened.insert(Document::open(file_name).map_err(|error| error.to_string())?),
+        };
+        let had_edits = document.is_dirty();
+        let mut applied: usize = 0;
+        for edit in edits {
+            let y = edit.location.line.saturating_sub(1);
+            let line = document.row(y).map(|row| row.as_str().to_string());
+            if let Some(text) = line.and_then(|line| project_search::apply(edit, &line)) {
+                document.replace_rows(y, y, &text);
+                applied = applied.saturating_add(1);
+            }
+        }
+        if had_edits || applied == 0 {
+            return Ok((applied, !had_edits));
+        }
+        let policy = Policy::for_file(file_name)?;
+        let config = self
+            .global_config
+            .layered(Some(file_name), &document.local_settings);
+        policy.apply(document, config.tab_width);
+        document
+            .save(&policy, config.on_save())
+            .map_err(|error| error.to_string())?;
+        self.touch_index(Path::new(file_name));
+        Ok((applied, true))
+    }
+    // takes in more of a file that is still loading, true if the document
+    // grew or loading failed
+    fn load(&mut self) -> bool {
+        match self.document.load(LOAD_BUDGET) {
+            Ok(added) => added,
+            Err(error) => {
+                self.status_message = StatusMessage::error(message!(
+                    "cannot_read_rest",
+                    count = self.document.len(),
+                    error = error
+                ));
+                true
+            }
+        }
+    }
+    fn missing_directory(&self) -> Option<PathBuf> {
+        let file_name = self.document.file_name.as_ref()?;
+        let directory = Path::new(file_name).parent()?;
+        if directory.as_os_str().is_empty() || directory.exists() {
+            return None;
+        }
+        Some(directory.to_path_buf())
+    }
+    // takes in the changes made outside to the files open in buffers, as
+    // the watch reports them. One with edits of its own is left alone, the
+    // user decides by saving or not, and said to have changed.
+    fn check_disk(&mut self) {
+        if !self.config.watch {
+            self.watch = None;
+            return;
+        }
+        let Some(changed) = self.changed_files() else {
+            return;
+        };
+        let touched = |document: &Document| {
+            document
+                .file_name
+                .as_deref()
+                .and_then(watch::key)
+                .is_some_and(|key| changed.contains(&key))
+                && document.changed_on_disk()
+        };
+        for buffer in &mut self.buffers {
+            let document = &mut buffer.document;
+            if !touched(document) {
+                continue;
+            }
+            let file = document.file_name.clone().unwrap_or_default();
+            if document.is_dirty() {
+                if document.note_disk_conflict() {
+                    self.status_message =
+                        StatusMessage::from(message!("buffer_changed_on_disk", file = file));
+                }
+                continue;
+            }
+            // the file may be half written, in which case the rest of the
+            // writing brings another event
+            if let Ok(change) = document.reload() {
+                buffer.cursor_position.y = change.shift(buffer.cursor_position.y);
+                buffer.offset.y = change.shift(buffer.offset.y);
+                self.status_message = StatusMessage::from(message!("buffer_reloaded", file = file));
+            }
+        }
+        if !touched(&self.document) {
+            return;
+        }
+        if self.document.is_dirty() {
+            if self.document.note_disk_conflict() {
+                self.status_message = StatusMessage::from(message!("changed_on_disk"));
+            }
+            return;
+        }
+        if let Ok(change) = self.document.reload() {
+            self.cursor_position.y = change.shift(self.cursor_position.y);
+            self.offset.y = change.shift(self.offset.y);
+            if let Some(split) = &mut self.split {
+                split.other.cursor_position.y = change.shift(split.other.cursor_position.y);
+                split.other.offset.y = change.shift(split.other.offset.y);
+            }
+            self.selection_anchor = None;
+            self.clamp_cursor();
+            self.scroll();
+            self.status_message = StatusMessage::from(message!("reloaded"));
+        }
+    }
+    // the files, as watch::key names them, that events came for since the
+    // last call, or all the open ones each DISK_CHECK_INTERVAL where they
+    // can't be watched. None when there are none to look at.
+    fn changed_files(&mut self) -> Option<HashSet<PathBuf>> {
+        let names: Vec<String> = iter::once(&self.document)
+            .chain(self.buffers.iter().map(|buffer| &buffer.document))
+            .filter_map(|document| document.file_name.clone())
+            .collect();
+        if self.watch.is_none() {
+            let watch = Watch::start();
+            if let Err(error) = &watch {
+                self.status_message = StatusMessage::error(message!("cannot_watch", error = error));
+            }
+            self.watch = Some(watch);
+        }
+        if let Some(Ok(watch)) = &mut self.watch {
+            match watch.follow(names.iter().map(String::as_str)) {
+                Ok(()) => {
+                    let changed = watch.changed();
+                    return (!changed.is_empty()).then_some(changed);
+                }
+                Err(error) => {
+                    self.status_message =
+                        StatusMessage::error(message!("cannot_watch", error = error));
+                    self.watch = Some(Err(error));
+                }
+            }
+        }
+        if self.last_disk_check.elapsed() < DISK_CHECK_INTERVAL {
+            return None;
+        }
+        self.last_disk_check = Instant::now();
+        Some(names.iter().filter_map(|name| watch::key(name)).collect())
+    }
+    // never writes to the files themselves, see `--recover`
+    fn snapshot(&mut self) {
+        if !self.config.recovery || self.last_snapshot.elapsed() < SNAPSHOT_INTERVAL {
+            return;
+        }
+        self.last_snapshot = Instant::now();
+        let documents = iter::once(&mut self.document)
+            .chain(self.buffers.iter_mut().map(|buffer| &mut buffer.document));
+        for document in documents {
+            if !document.needs_snapshot() {
+                continue;
+            }
+            if let Err(error) = document.snapshot() {
+                self.status_message =
+                    StatusMessage::error(message!("cannot_snapshot", error = error));
+                return;
+            }
+        }
+    }
+    fn write_swaps(&mut self) {
+        if !self.config.swap_files || self.last_swap.elapsed() < SWAP_INTERVAL {
+            return;
+        }
+        self.last_swap = Instant::now();
+        let documents = iter::once(&mut self.document)
+            .chain(self.buffers.iter_mut().map(|buffer| &mut buffer.document));
+        for document in documents {
+            if !document.needs_swap() {
+                continue;
+            }
+            if let Err(error) = document.write_swap() {
+                self.status_message =
+                    StatusMessage::error(message!("cannot_write_swap", error = error));
+                return;
+            }
+        }
+    }
+    // asks what to do with the swap file left beside the document's file:
+    // open its text as unsaved edits, delete it, or leave it be
+    fn offer_swap(&mut self) -> Result<(), std::io::Error> {
+        let file_name = self.document.file_name.clone().unwrap_or_default();
+        let question = message!("found_swap", file = swap::path(&file_name).display());
+        let answer = self.prompt(&question)?;
+        match answer.as_deref().map(str::to_ascii_lowercase).as_deref() {
+            Some("y") => match self.document.recover_swap() {
+                Ok(document) => {
+                    self.document.unsubscribe(self.subscription);
+                    self.document = document;
+                    self.subscribe();
+                    self.clamp_cursor();
+                    self.status_message = StatusMessage::from(message!("recovered"));
+                }
+                Err(error) => {
+                    self.document.keep_swap();
+                    self.status_message =
+                        StatusMessage::error(message!("cannot_recover", error = error));
+                }
+            },
+            Some("n") => {
+                if let Err(error) = self.document.discard_swap() {
+                    self.document.keep_swap();
+                    self.status_message =
+                        StatusMessage::error(message!("cannot_delete_swap", error = error));
+                }
+            }
+            _ => self.document.keep_swap(),
+        }
+        Ok(())
+    }
+    fn scroll(&mut self) {
+        let Position { x, y } = self.cursor_position;
+        let Area { width, height, .. } = self.text_area();
+        // never ask for more context than fits around the cursor line
+        let margin = cmp::min(self.config.scrolloff, height.saturating_sub(1) / 2);
+        if self.config.wrap {
+            self.scroll_wrapped(width, height, margin);
+            return;
+        }
+        // the cursor can sit one line past the last row, don't scroll beyond that
+        let max_offset_y = self.document.len().saturating_add(1).saturating_sub(height);
+        let offset = &mut self.offset;
+        if y < offset.y.saturating_add(margin) {
+            offset.y = y.saturating_sub(margin);
+        } else if y.saturating_add(margin) >= offset.y.saturating_add(height) {
+            offset.y = cmp::min(
+                y.saturating_add(margin)
+                    .saturating_sub(height)
+                    .saturating_add(1),
+                max_offset_y,
+            );
+        }
+        if x < offset.x {
+            offset.x = x;
+        } else {
+            let tab_width = self.config.tab_width;
+            let start = self.document.row(y).map_or_else(
+                || x.saturating_sub(width.saturating_sub(1)),
+                |row| row.start_for(x, width, tab_width),
+            );
+            offset.x = cmp::max(offset.x, start);
+        }
+    }
+    // like scroll, counting screen lines rather than rows
+    fn scroll_wrapped(&mut self, width: usize, height: usize, margin: usize) {
+        let y = self.cursor_position.y;
+        self.offset.x = 0;
+        if y < self.offset.y {
+            self.offset.y = y;
+        }
+        // the screen line of the cursor, counted from the top of the view
+        let mut line = self.wrapped_cursor(width).1;
+        while line.saturating_add(margin) >= height && self.offset.y < y {
+            line = line.saturating_sub(self.row_height(self.offset.y, width));
+            self.offset.y = self.offset.y.saturating_add(1);
+        }
+        while line < margin && self.offset.y > 0 {
+            self.offset.y = self.offset.y.saturating_sub(1);
+            line = line.saturating_add(self.row_height(self.offset.y, width));
+        }
+    }
+    // where each screen line of row `index` starts when wrapped at `width`
+    fn segments(&self, index: usize, width: usize) -> Vec<usize> {
+        let width = cmp::max(width, 1);
+        match self.document.row(index) {
+            Some(row) if row.len() <= self.config.max_line_length => {
+                row.wrap(width, self.config.tab_width)
+            }
+            row => {
+                // long rows are drawn a grapheme per column
+                let len = row.map_or(0, |row| cmp::min(row.len(), self.config.max_line_length));
+                (0..=len / width)
+                    .map(|line| line.saturating_mul(width))
+                    .collect()
+            }
+        }
+    }
+    fn row_height(&self, index: usize, width: usize) -> usize {
+        self.segments(index, width).len()
+    }
+    // the column and screen line of the cursor in wrapping mode
+    fn wrapped_cursor(&self, width: usize) -> (usize, usize) {
+        let Position { x, y } = self.cursor_position;
+        let above: usize = (self.offset.y..y)
+            .map(|index| self.row_height(index, width))
+            .sum();
+        let segments = self.segments(y, width);
+        let line = segments.iter().rposition(|start| *start <= x).unwrap_or(0);
+        let start = segments.get(line).copied().unwrap_or(0);
+        let column = self
+            .visual_column(y, start, x, width)
+            .unwrap_or_else(|| self.columns(y, start, x));
+        (column, above.saturating_add(line))
+    }
+    // the screen columns between two positions of row `y`
+    fn columns(&self, y: usize, start: usize, end: usize) -> usize {
+        let tab_width = self.config.tab_width;
+        self.document
+            .row(y)
+            .map_or(end.saturating_sub(start), |row| {
+                row.columns(start, end, tab_width)
+                    .saturating_add(end.saturating_sub(cmp::max(start, row.len())))
+            })
+    }
+    // where the cursor is drawn, relative to the focused pane's text
+    fn cursor_on_screen(&self, width: usize) -> (usize, usize) {
+        let Position { x, y } = self.cursor_position;
+        if self.config.wrap {
+            self.wrapped_cursor(width)
+        } else {
+            let column = self
+                .visual_column(y, self.offset.x, x, width)
+                .unwrap_or_else(|| self.columns(y, self.offset.x, x));
+            (column, y.saturating_sub(self.offset.y))
+        }
+    }
+    // where the cursor is drawn in a row with right-to-left text, drawn from
+    // `start` in `width` columns, like render_row draws it
+    fn visual_column(&self, y: usize, start: usize, x: usize, width: usize) -> Option<usize> {
+        let row = self.document.row(y)?;
+        let mut widths = Vec::new();
+        let mut chars = Vec::new();
+        let mut used: usize = 0;
+        let column = row.columns(0, start, self.config.tab_width);
+        for grapheme in row.as_str().graphemes(true).skip(start).take(width) {
+            if used >= width {
+                break;
+            }
+            let columns = if grapheme == "\t" {
+                let stop = row::tab_columns(column.saturating_add(used), self.config.tab_width);
+                cmp::min(stop, width.saturating_sub(used))
+            } else {
+                1
+            };
+            used = used.saturating_add(columns);
+            widths.push(columns);
+            chars.push(grapheme.chars().next().unwrap_or(' '));
+        }
+        let order = bidi::visual_order(&chars)?;
+        let logical = x.checked_sub(start)?;
+        // the end of the row is drawn after all of it
+        let place = order
+            .iter()
+            .position(|&index| index == logical)
+            .unwrap_or(order.len());
+        Some(order[..place].iter().map(|&index| widths[index]).sum())
+    }
+    // Left and Right in a row with right-to-left text go by where the
+    // characters are drawn rather than by their order; the end of the row
+    // is drawn after its rightmost character. None for other keys and rows.
+    fn move_visually(&self, key: Key) -> Option<Position> {
+        let Position { x, y } = self.cursor_position;
+        if self.config.wrap || !matches!(key, Key::Left | Key::Right) {
+            return None;
+        }
+        let row = self.document.row(y)?;
+        let chars: Vec<char> = row
+            .as_str()
+            .graphemes(true)
+            .map(|grapheme| grapheme.chars().next().unwrap_or(' '))
+            .collect();
+        let order = bidi::visual_order(&chars)?;
+        let place = order
+            .iter()
+            .position(|&index| index == x)
+            .unwrap_or(order.len());
+        let position = match key {
+            Key::Left if place == 0 && y > 0 => Position {
+                x: self.document.row(y - 1).map_or(0, Row::len),
+                y: y - 1,
+            },
+            Key::Left if place == 0 => self.cursor_position,
+            Key::Left => Position {
+                x: order[place - 1],
+                y,
+            },
+            _ if place + 1 < order.len() => Position {
+                x: order[place + 1],
+                y,
+            },
+            // off the rightmost character to the end of the row, then on to
+            // the next row
+            _ if place + 1 == order.len() => Position { x: row.len(), y },
+            _ => Position {
+                x: 0,
+                y: cmp::min(y.saturating_add(1), self.document.len()),
+            },
+        };
+        Some(position)
+    }
+    // move_cursor for the arrow keys, which go by what is on the screen
+    fn move_arrow(&mut self, key: Key) {
+        match self.move_visually(key) {
+            Some(position) => self.cursor_position = position,
+            None => self.move_cursor(key),
+        }
+    }
+    // up and down by screen line within wrapped rows
+    fn move_wrapped(&mut self, key: Key) {
+        let width = self.text_area().width;
+        let Position { x, y } = self.cursor_position;
+        let segments = self.segments(y, width);
+        let line = segments.iter().rposition(|start| *start <= x).unwrap_or(0);
+        let column = self.columns(y, segments.get(line).copied().unwrap_or(0), x);
+        let (y, line) = match key {
+            Key::Up if line > 0 => (y, line - 1),
+            Key::Up if y > 0 => (y - 1, self.row_height(y - 1, width).saturating_sub(1)),
+            Key::Down if line + 1 < segments.len() => (y, line + 1),
+            Key::Down if y < self.document.len() => (y + 1, 0),
+            _ => return,
+        };
+        let segments = self.segments(y, width);
+        let start = segments.get(line).copied().unwrap_or(0);
+        let x = self.document.row(y).map_or(0, |row| {
+            // stay on this screen line rather than the start of the next
+            let end = segments
+                .get(line + 1)
+                .map_or(row.len(), |next| next.saturating_sub(1));
+            row.index_at(start, end, column, self.config.tab_width)
+        });
+        self.cursor_position = Position { x, y };
+    }
+    fn move_cursor(&mut self, key: Key) {
+        if self.config.wrap && matches!(key, Key::Up | Key::Down) {
+            self.move_wrapped(key);
+            return;
+        }
+        let Position { mut x, mut y } = self.cursor_position;
+        let height = self.document.len();
+        let terminal_height = self.view_area().height;
+        let width = if let Some(row) = self.document.row(y) {
+            row.len()
+        } else {
+            0
+        };
+        match key {
+            Key::Up => y = y.saturating_sub(1),
+            Key::Down if y < height => y = y.saturating_add(1),
+            Key::Left => {
+                if x > 0 {
+                    x -= 1;
+                } else if y > 0 {
+                    y -= 1;
+                    if let Some(row) = self.document.row(y) {
+                        x = row.len();
+                    } else {
+                        x = 0;
+                    }
+                }
+            }
+            Key::Right => {
+                if x < width {
+                    x += 1;
+                } else if y < height {
+                    y += 1;
+                    x = 0;
+                }
+            }
+            Key::PageUp => {
+                y = if y > terminal_height {
+                    y.saturating_sub(terminal_height)
+                } else {
+                    0
+                }
+            }
+            Key::PageDown => {
+                y = if y.saturating_add(terminal_height) < height {
                     y.saturating_add(terminal_height)
                 } else {
                     height
@@ -352,43 +3934,1135 @@ impl Editor {
         }
         self.cursor_position = Position { x, y }
     }
-    fn draw_row(&self, row: &Row, ps: &SyntaxSet, ts: &ThemeSet) {
-        let width = self.terminal.size().width as usize;
-        let start = self.offset.x;
-        let end = self.offset.x.saturating_add(width);
-        let row = row.render(start, end);
-
-        let syntax = ps.find_syntax_by_extension("rs").unwrap();
-        let mut h = HighlightLines::new(syntax, &ts.themes["base16-ocean.dark"]);
-        let ranges: Vec<(Style, &str)> = h.highlight_line(row.as_str(), &ps).unwrap();
-        let escaped = as_24_bit_terminal_escaped(&ranges[..], true);
-        println!("{escaped}\r");
-    }
-    fn draw_rows(&self, ps: &SyntaxSet, ts: &ThemeSet) {
-        let height = self.terminal.size().height;
-        for terminal_row in 0..height {
-            Terminal::clear_current_line();
-            if let Some(row) = self
+    // one row of a pane and the number of cells it takes up
+    #[allow(clippy::too_many_arguments)]
+    fn render_row(
+        &self,
+        row: &Row,
+        offset: Position,
+        width: usize,
+        selected: Option<Range<usize>>,
+        line_color: Option<color::Rgb>,
+        brackets: &[usize],
+    ) -> (String, usize) {
+        let start = offset.x;
+        let end = offset.x.saturating_add(width);
+        if row.len() > self.config.max_line_length {
+            return self.render_long_row(row, start, end);
+        }
+        let matches: Vec<Range<usize>> = self
+            .search_highlight
+            .as_ref()
+            .map_or_else(Vec::new, |regex| row.regex_matches(regex, ""))
+            .into_iter()
+            .map(|(range, _)| range.start.saturating_sub(start)..range.end.saturating_sub(start))
+            .collect();
+        let source = row.substring(start, end);
+        let guidance = self.document.is_commit_message() && row.as_str().starts_with('#');
+        let ranges = match row.highlighted() {
+            _ if guidance => vec![(self.comment, row.as_str())],
+            Some(highlighted) if !self.slow => highlighted.ranges(row.as_str()),
+            _ => vec![(self.plain, row.as_str())],
+        };
+        let mut cells = render::cells(&ranges);
+        cells.truncate(cmp::min(end, row.len()));
+        cells.drain(..cmp::min(start, cells.len()));
+        if self.redact && self.config.is_secret(row.as_str()) {
+            for cell in &mut cells {
+                cell.text = render::mask(&cell.text, self.config.glyphs().mask);
+            }
+        }
+        if let Some(line) = line_color {
+            render::mark_line(&mut cells, self.plain.background, render::to_color(line));
+        }
+        // a swatch is a color, which is no use to a screen reader and which
+        // terminals drawing in ASCII may not show right either
+        if self.config.color_swatches && !self.config.screen_reader && !self.config.ascii {
+            render::mark_colors(&mut cells);
+        }
+        if self.config.mark_suspicious {
+            let warning = self.ui.warning_bg.map(|color| render::to_color(color.0));
+            render::mark_suspicious(&mut cells, warning, self.config.glyphs().invisible);
+        }
+        self.mark_checked(&mut cells, row, offset);
+        let match_colors = (
+            self.ui.match_bg.map(|color| color.0),
+            self.ui.match_fg.map(|color| color.0),
+        );
+        render::mark_matches(&mut cells, &matches, match_colors);
+        let brackets: Vec<usize> = brackets
+            .iter()
+            .filter_map(|column| column.checked_sub(start))
+            .collect();
+        render::mark_brackets(
+            &mut cells,
+            &brackets,
+            self.ui.bracket_bg.map(|color| render::to_color(color.0)),
+        );
+        if let Some(columns) = selected {
+            let colors = (
+                self.ui.selection_bg.map(|color| render::to_color(color.0)),
+                self.ui.selection_fg.map(|color| render::to_color(color.0)),
+            );
+            render::mark_selection(
+                &mut cells,
+                columns.start.saturating_sub(start)..columns.end.saturating_sub(start),
+                colors,
+            );
+        }
+        let tab_width = self.config.tab_width;
+        render::expand_tabs(
+            &mut cells,
+            &source,
+            row.columns(0, start, tab_width),
+            tab_width,
+            width,
+        );
+        if self.config.show_invisibles {
+            let color = self
+                .ui
+                .invisible_fg
+                .or(self.ui.gutter_fg)
+                .map_or(LINE_NUMBER_COLOR, |color| color.0);
+            let trailing = row.trailing_spaces().saturating_sub(start);
+            render::mark_invisibles(
+                &mut cells,
+                &source,
+                trailing,
+                self.config.glyphs(),
+                render::to_color(color),
+            );
+        }
+        render::reorder(&mut cells);
+        let len = cells.iter().map(|cell| cell.text.chars().count()).sum();
+        let mut text = render::escape(&cells);
+        if let Some(line) = line_color {
+            // the mark runs to the edge of the pane, and the row after goes
+            // on with the theme's background like a row without one
+            let plain = self.plain.background;
+            text = format!(
+                "{text}{}{}{}",
+                Terminal::bg(line),
+                " ".repeat(width.saturating_sub(len)),
+                Terminal::bg(color::Rgb(plain.r, plain.g, plain.b))
+            );
+            return (text, cmp::max(len, width));
+        }
+        (text, len)
+    }
+    // marks what spell checking and the last check found on the row, the
+    // cells starting at `offset`
+    fn mark_checked(&self, cells: &mut [render::Cell], row: &Row, offset: Position) {
+        let start = offset.x;
+        let misspelled: Vec<Range<usize>> = self
+            .misspelled(row)
+            .into_iter()
+            .map(|range| range.start.saturating_sub(start)..range.end.saturating_sub(start))
+            .collect();
+        render::mark_misspelled(
+            cells,
+            &misspelled,
+            self.ui.misspelled_fg.map(|color| render::to_color(color.0)),
+        );
+        let diagnostics: Vec<(Range<usize>, Severity)> = self
+            .document
+            .diagnostics()
+            .iter()
+            .filter_map(|diagnostic| {
+                Some((
+                    diagnostic.columns(offset.y, row.len())?,
+                    diagnostic.severity,
+                ))
+            })
+            .map(|(range, severity)| {
+                (
+                    range.start.saturating_sub(start)..range.end.saturating_sub(start),
+                    severity,
+                )
+            })
+            .collect();
+        render::mark_diagnostics(cells, &diagnostics);
+    }
+    // pathological rows (minified files and the like) skip highlighting and
+    // stop at the line length limit
+    fn render_long_row(&self, row: &Row, start: usize, end: usize) -> (String, usize) {
+        let limit = self.config.max_line_length;
+        // drawn in the columns from `start` to `end`, which tabs take more of
+        let mut text: String = row
+            .render(
+                start,
+                cmp::min(end, limit),
+                self.config.tab_width,
+                self.config.show_invisibles.then(|| self.config.glyphs()),
+            )
+            .chars()
+            .take(end.saturating_sub(start))
+            .collect();
+        if self.redact && self.config.is_secret(row.as_str()) {
+            text = render::mask(&text, self.config.glyphs().mask);
+        }
+        let mut len = text.graphemes(true).count();
+        if end > limit {
+            let room = end.saturating_sub(cmp::max(start, limit));
+            let marker = &self.config.glyphs().truncated;
+            let indicator: String = message!("line_truncated", marker = marker)
+                .chars()
+                .take(room)
+                .collect();
+            len = len.saturating_add(indicator.chars().count());
+            text = format!(
+                "{text}{}{indicator}{}",
+                Terminal::fg(LONG_LINE_COLOR),
+                color::Fg(color::Reset)
+            );
+        }
+        (text, len)
+    }
+    // brings the highlighting of every row down to the bottom of the lowest
+    // pane up to date, before they are drawn
+    fn highlight(&mut self, ps: &SyntaxSet, ts: &ThemeSet) {
+        let height = usize::from(self.terminal.size().height);
+        let top = self.split.as_ref().map_or(self.offset.y, |split| {
+            cmp::max(self.offset.y, split.other.offset.y)
+        });
+        let base = syntax::for_document(&self.document, ps);
+        let syntaxes = syntax::for_rows(&self.document, base, ps, top.saturating_add(height));
+        let theme = self.theme(ts);
+        self.document
+            .highlight(&syntaxes, ps, theme, self.config.max_line_length);
+    }
+    // the screen lines of one pane; `pad` fills each out to the pane's width
+    // so something can be drawn to the right of it
+    fn render_pane(&self, pane: Pane, focused: bool, area: Area, pad: bool) -> Vec<String> {
+        let gutter = self.gutter_width();
+        let numbers = self.numbers_width();
+        let full_width = area.width.saturating_sub(gutter);
+        let column = self.column_area(Area {
+            x: 0,
+            width: full_width,
+            ..area
+        });
+        let (margin, width) = (column.x, column.width);
+        let brackets = if focused {
+            self.bracket_pair(&pane.cursor_position)
+        } else {
+            None
+        };
+        let mut lines = Vec::with_capacity(area.height);
+        let mut index = pane.offset.y;
+        while lines.len() < area.height {
+            // with wrapping a row may take several screen lines
+            let segments = if self.config.wrap {
+                self.segments(index, width)
+            } else {
+                vec![pane.offset.x]
+            };
+            for (segment, segment_start) in segments.into_iter().enumerate() {
+                if lines.len() == area.height {
+                    break;
+                }
+                let number = match self.config.line_numbers {
+                    _ if numbers == 0 || segment > 0 || index >= self.document.len() => {
+                        " ".repeat(numbers)
+                    }
+                    LineNumbers::Relative if index != pane.cursor_position.y => {
+                        let distance = index.abs_diff(pane.cursor_position.y);
+                        format!("{distance:>0$} ", numbers.saturating_sub(1))
+                    }
+                    _ => format!(
+                        "{:>1$} ",
+                        index.saturating_add(1),
+                        numbers.saturating_sub(1)
+                    ),
+                };
+                let mark = if gutter == numbers {
+                    String::new()
+                } else {
+                    self.gutter_mark(index, segment)
+                };
+                let offset = Position {
+                    x: segment_start,
+                    y: index,
+                };
+                let (mut line, len) = if let Some(row) = self.document.row(index) {
+                    let selected = Self::selected_columns(&pane, index, row.len());
+                    let current = focused && index == pane.cursor_position.y;
+                    let line_color = self
+                        .ui
+                        .current_line_bg
+                        .filter(|_| current && !self.config.screen_reader)
+                        .map(|color| color.0);
+                    let columns: Vec<usize> = brackets
+                        .iter()
+                        .flat_map(|&(bracket, pair)| [bracket, pair])
+                        .filter(|position| position.y == index)
+                        .map(|position| position.x)
+                        .collect();
+                    self.render_row(row, offset, width, selected, line_color, &columns)
+                } else {
+                    let text = if self.config.screen_reader {
+                        String::new()
+                    } else if self.document.is_empty() && lines.len() == area.height / 3 {
+                        message!("welcome", version = VERSION)
+                    } else {
+                        self.config.glyphs().empty_row.clone()
+                    };
+                    let text: String = text.chars().take(width).collect();
+                    let len = text.chars().count();
+                    (text, len)
+                };
+                if pad {
+                    let right = full_width.saturating_sub(margin).saturating_sub(len);
+                    line = format!("{line}{}{}", style::Reset, " ".repeat(right));
+                }
+                if margin > 0 {
+                    line = format!("{}{line}", " ".repeat(margin));
+                }
+                if gutter > 0 {
+                    line = format!(
+                        "{}{mark}{number}{}{line}",
+                        Terminal::fg(self.gutter_color()),
+                        color::Fg(color::Reset)
+                    );
+                }
+                lines.push(line);
+            }
+            index = index.saturating_add(1);
+        }
+        lines
+    }
+    // the configured theme, or the default one if it doesn't exist
+    fn theme<'a>(&self, ts: &'a ThemeSet) -> &'a Theme {
+        ts.themes
+            .get(&self.config.theme)
+            .unwrap_or_else(|| &ts.themes[config::DEFAULT_THEME])
+    }
+    fn command_line(&mut self) -> Result<(), std::io::Error> {
+        let themes = self.theme_names.clone();
+        let line = self.prompt_full(
+            ":",
+            |_, _| (),
+            |line| command_line::complete(line, &themes),
+            &[],
+        )?;
+        let Some(line) = line else {
+            return Ok(());
+        };
+        let action = match command_line::parse(&line) {
+            Ok(action) => action,
+            Err(message) => {
+                self.status_message = StatusMessage::from(message);
+                return Ok(());
+            }
+        };
+        match action {
+            Action::Run(command) => {
+                if !self.is_edit(Key::Null, Some(command)) || self.confirm_edit()? {
+                    self.run_command(command)?;
+                }
+            }
+            Action::Write => self.save(),
+            Action::Quit => self.run_command(Command::Quit)?,
+            Action::WriteQuit => {
+                self.save();
+                if !self.document.is_dirty() {
+                    self.run_command(Command::Quit)?;
+                }
+            }
+            Action::Goto(line) => {
+                self.cursor_position = Position {
+                    x: 0,
+                    y: line
+                        .saturating_sub(1)
+                        .min(self.document.len().saturating_sub(1)),
+                };
+                self.scroll();
+            }
+            Action::Set(setting) => {
+                let set = self.set_global(&setting);
+                self.report_setting(set, message!("set", setting = setting));
+            }
+            Action::SetLocal(setting) => {
+                let set = self.set_local(&setting);
+                self.report_setting(set, message!("set_local", setting = setting));
+            }
+            Action::Theme(name) => self.set_theme(&name),
+            Action::Help(topic) => self.help(&topic),
+            Action::Sort(order) if self.confirm_edit()? => self.sort_rows(Some(order)),
+            Action::Uniq if self.confirm_edit()? => self.sort_rows(None),
+            Action::Undo(None) => self.show_undo_tree(),
+            Action::Undo(Some(state)) if self.confirm_edit()? => self.undo_to(state),
+            Action::Sort(_) | Action::Uniq | Action::Undo(Some(_)) => (),
+        }
+        Ok(())
+    }
+    // says what `:set` or `:setlocal` did, with the screen redrawn for it
+    fn report_setting(&mut self, set: Result<(), String>, done: String) {
+        let message = match set {
+            Ok(()) => {
+                self.offset.x = 0;
+                self.scroll();
+                done
+            }
+            Err(message) => message,
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+    // sorts the selected rows, or all of them without a selection, or with
+    // no `order` drops the ones repeating the row before
+    fn sort_rows(&mut self, order: Option<Order>) {
+        let (first, last) = match self.selection() {
+            Some((start, end)) if end.x == 0 && end.y > start.y => (start.y, end.y - 1),
+            Some((start, end)) => (start.y, end.y),
+            None => (0, self.document.len().saturating_sub(1)),
+        };
+        let last = cmp::min(last, self.document.len().saturating_sub(1));
+        let count = last.saturating_sub(first).saturating_add(1);
+        let message = match order {
+            Some(order) if self.document.sort_rows(first, last, order) => {
+                message!("sorted", count = count)
+            }
+            Some(_) => message!("sorted_already"),
+            None => match self.document.dedupe_rows(first, last) {
+                0 => message!("no_duplicates"),
+                removed => message!("deduped", count = removed),
+            },
+        };
+        self.selection_anchor = None;
+        self.clamp_cursor();
+        self.status_message = StatusMessage::from(message);
+    }
+    // opens the help in a buffer of its own, at `topic` unless that is empty
+    fn help(&mut self, topic: &str) {
+        let text = help::text(&self.config);
+        let y = if topic.is_empty() {
+            Some(0)
+        } else {
+            help::find(&text, topic)
+        };
+        let Some(y) = y else {
+            self.status_message = StatusMessage::from(message!("no_help", topic = topic));
+            return;
+        };
+        let previous = self.switch_to(Buffer::from(Document::read_only(&text)));
+        self.shelve(previous);
+        self.cursor_position = Position { x: 0, y };
+        self.scroll();
+        let key = self.config.key_name(Command::CloseBuffer);
+        self.status_message = StatusMessage::from(message!("help_opened", key = key));
+    }
+    // fills the paragraph at the cursor, the rows around it up to blank
+    // ones, out to the reflow column
+    fn reflow(&mut self) {
+        let blank = |y| {
+            self.document
+                .row(y)
+                .is_none_or(|row: &Row| row.as_str().trim().is_empty())
+        };
+        let y = self.cursor_position.y;
+        if blank(y) {
+            self.status_message = StatusMessage::from(message!("no_paragraph"));
+            return;
+        }
+        let mut first = y;
+        while first > 0 && !blank(first - 1) {
+            first -= 1;
+        }
+        let mut last = y;
+        while !blank(last.saturating_add(1)) {
+            last = last.saturating_add(1);
+        }
+        let lines: Vec<&str> = (first..=last)
+            .filter_map(|y| self.document.row(y).map(Row::as_str))
+            .collect();
+        // short of the column itself, where soft wrapping would put the
+        // cursor at the end of a full line
+        let width = cmp::max(self.config.reflow_column().saturating_sub(1), 1);
+        let text = reflow::fill(&lines, width).join("\n");
+        self.cursor_position = self.document.replace_rows(first, last, &text);
+        self.scroll();
+    }
+    // the selected rows, leaving out the last when the selection ends at its
+    // start, or the cursor's row
+    fn toggle_comment(&mut self) {
+        let Some(comment) = language::comment(self.document.file_name.as_deref()) else {
+            self.status_message = StatusMessage::from(message!("no_comments"));
+            return;
+        };
+        let (start, end) = self.span();
+        let last = if end.x == 0 && end.y > start.y {
+            end.y - 1
+        } else {
+            end.y
+        };
+        let lines: Vec<&str> = (start.y..=last)
+            .filter_map(|y| self.document.row(y).map(Row::as_str))
+            .collect();
+        if lines.is_empty() {
+            return;
+        }
+        let toggled = language::toggle(&lines, comment);
+        let last = start.y.saturating_add(toggled.len()).saturating_sub(1);
+        let text: Vec<&str> = toggled.iter().map(|row| row.text.as_str()).collect();
+        self.document.replace_rows(start.y, last, &text.join("\n"));
+        let moved = |position: Position| Position {
+            x: position
+                .y
+                .checked_sub(start.y)
+                .and_then(|index| toggled.get(index))
+                .map_or(position.x, |row| row.moved(position.x)),
+            y: position.y,
+        };
+        self.cursor_position = moved(self.cursor_position);
+        if let Some(anchor) = self.selection_anchor {
+            self.selection_anchor = Some(moved(anchor));
+        }
+        self.scroll();
+    }
+    // the dictionary, or false after saying why there is none and turning
+    // spell checking off
+    fn load_dictionary(&mut self) -> bool {
+        if self.dictionary.is_none() {
+            match spell::Dictionary::load(&self.config.spell_dictionary) {
+                Ok(dictionary) => self.dictionary = Some(dictionary),
+                Err(error) => {
+                    self.set_global("nospell_check").ok();
+                    self.status_message =
+                        StatusMessage::error(message!("no_dictionary", error = error));
+                    return false;
+                }
+            }
+        }
+        true
+    }
+    // the misspelled words of `row`, in prose throughout and in code in its
+    // comments, none for a language whose comments aren't known
+    fn misspelled(&self, row: &Row) -> Vec<Range<usize>> {
+        let Some(dictionary) = self.dictionary.as_ref().filter(|_| self.config.spell_check) else {
+            return Vec::new();
+        };
+        let file_name = self.document.file_name.as_deref();
+        if spell::is_prose(file_name) {
+            return spell::misspelled(dictionary, row.as_str(), None);
+        }
+        language::comment(file_name).map_or_else(Vec::new, |comment| {
+            spell::misspelled(dictionary, row.as_str(), Some(comment))
+        })
+    }
+    // replaces the name before the cursor with the snippet it names, its
+    // first field selected
+    fn expand_snippet(&mut self) {
+        let at = self.cursor_position;
+        let before = self
+            .document
+            .row(at.y)
+            .map_or_else(String::new, |row| row.substring(0, at.x));
+        let name_len = before
+            .graphemes(true)
+            .rev()
+            .take_while(|grapheme| grapheme.chars().all(|c| c.is_alphanumeric() || c == '_'))
+            .count();
+        let name: String = before
+            .graphemes(true)
+            .skip(at.x.saturating_sub(name_len))
+            .collect();
+        if name.is_empty() {
+            self.status_message = StatusMessage::from(message!("no_snippet_name"));
+            return;
+        }
+        let file_name = self.document.file_name.clone();
+        let snippet = match snippets::find(&name, file_name.as_deref()) {
+            Ok(Some(snippet)) => snippet,
+            Ok(None) => {
+                self.status_message = StatusMessage::from(message!("no_snippet", name = name));
+                return;
+            }
+            Err(error) => {
+                self.status_message = StatusMessage::error(message!("error", error = error));
+                return;
+            }
+        };
+        let indent: String = before
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        let expansion = snippets::expand(&snippet, &indent, file_name.as_deref(), &self.config);
+        let start = Position {
+            x: at.x.saturating_sub(name_len),
+            y: at.y,
+        };
+        self.document
+            .replace_range(at.y, start.x..at.x, &expansion.text);
+        let fields = expansion.fields_at(start);
+        let more = fields.len() > 1;
+        self.document.snippet = Some(snippets::Snippet { fields, current: 0 });
+        self.select_field();
+        if more {
+            self.mode = EditorMode::Insert;
+            self.status_message = StatusMessage::from(message!("snippet_fields"));
+        } else {
+            self.document.snippet = None;
+        }
+    }
+    // to the snippet's next field or the one before, leaving the snippet at
+    // its last
+    fn next_field(&mut self, forward: bool) {
+        let Some(snippet) = &mut self.document.snippet else {
+            return;
+        };
+        snippet.current = if forward {
+            snippet.current.saturating_add(1)
+        } else {
+            snippet.current.saturating_sub(1)
+        };
+        let last = snippet.current.saturating_add(1) >= snippet.fields.len();
+        self.select_field();
+        if last {
+            self.document.snippet = None;
+        }
+    }
+    fn select_field(&mut self) {
+        let Some(field) = self
+            .document
+            .snippet
+            .as_ref()
+            .and_then(|snippet| snippet.fields.get(snippet.current).copied())
+        else {
+            return;
+        };
+        self.cursor_position = field.end;
+        self.clamp_cursor();
+        self.selection_anchor = (field.start != field.end).then_some(field.start);
+    }
+    // replaces the misspelled word at the cursor with the first suggestion
+    // for it, or the suggestion last put in with the next one
+    fn spell_suggest(&mut self) {
+        let Position { x, y } = self.cursor_position;
+        if let Some(mut spelling) = self.spelling.take() {
+            let end = spelling
+                .start
+                .saturating_add(spelling.current().graphemes(true).count());
+            let unchanged = self
                 .document
-                .row(self.offset.y.saturating_add(terminal_row as usize))
-            {
-                self.draw_row(row, ps, ts);
-            } else if self.document.is_empty() && terminal_row == height / 3 {
-                println!("Byron's Code Editor -- version {VERSION}\r");
+                .row(y)
+                .is_some_and(|row| row.substring(spelling.start, end) == spelling.current());
+            if y == spelling.y && (spelling.start..=end).contains(&x) && unchanged {
+                spelling.index =
+                    (spelling.index + 1) % spelling.suggestions.len().saturating_add(1);
+                self.put_suggestion(spelling, end);
+                return;
+            }
+        }
+        if !self.load_dictionary() {
+            return;
+        }
+        let found = self.document.row(y).and_then(|row| {
+            let range = self
+                .misspelled(row)
+                .into_iter()
+                .find(|range| (range.start..=range.end).contains(&x))?;
+            Some((row.substring(range.start, range.end), range))
+        });
+        let (Some((word, range)), Some(dictionary)) = (found, self.dictionary.as_ref()) else {
+            self.status_message = StatusMessage::from(message!("not_misspelled"));
+            return;
+        };
+        let suggestions = dictionary.suggest(&word);
+        if suggestions.is_empty() {
+            self.status_message = StatusMessage::from(message!("no_suggestions", word = word));
+            return;
+        }
+        let spelling = Spelling {
+            y,
+            start: range.start,
+            word,
+            suggestions,
+            index: 0,
+        };
+        self.put_suggestion(spelling, range.end);
+    }
+    // puts the current word of `spelling` in for what is up to `end`
+    fn put_suggestion(&mut self, spelling: Spelling, end: usize) {
+        let current = spelling.current().to_string();
+        self.document
+            .replace_range(spelling.y, spelling.start..end, &current);
+        self.cursor_position = Position {
+            x: spelling
+                .start
+                .saturating_add(current.graphemes(true).count()),
+            y: spelling.y,
+        };
+        let message = if spelling.index < spelling.suggestions.len() {
+            message!(
+                "suggestion",
+                number = spelling.index.saturating_add(1),
+                count = spelling.suggestions.len(),
+                word = spelling.word
+            )
+        } else {
+            message!("suggestion_original", word = spelling.word)
+        };
+        self.status_message = StatusMessage::from(message);
+        self.spelling = Some(spelling);
+        self.scroll();
+    }
+    fn choose_theme(&mut self) {
+        if let Some(name) = self.prompt(&message!("theme_prompt")).unwrap_or(None) {
+            self.set_theme(&name);
+        }
+    }
+    fn set_theme(&mut self, name: &str) {
+        let message = if self.theme_names.iter().any(|theme| theme == name) {
+            self.config.theme = name.to_string();
+            self.global_config.theme = name.to_string();
+            self.document.clear_highlighting();
+            for buffer in &mut self.buffers {
+                buffer.document.clear_highlighting();
+            }
+            message!("switched_theme", name = name)
+        } else {
+            message!("no_theme", name = name, names = self.theme_names.join(", "))
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+    fn choose_ui_theme(&mut self) {
+        let Some(name) = self.prompt(&message!("ui_theme_prompt")).unwrap_or(None) else {
+            return;
+        };
+        let message = match load_ui_theme(&name, &self.config) {
+            Ok(ui) => {
+                self.ui = ui;
+                self.config.ui_theme.clone_from(&name);
+                message!("switched_ui_theme", name = name)
+            }
+            Err(error) => message!("no_ui_theme", error = error, names = ui::names().join(", ")),
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+    fn draw_rows(&self, ts: &ThemeSet) {
+        let area = self.panes_area();
+        let (width, height) = (area.width, area.height);
+        let focused = self.view();
+        let lines = if let (Some(split), Some((first_area, second_area))) =
+            (&self.split, self.pane_areas())
+        {
+            let (first, second) = if split.second_focused {
+                (split.other, focused)
+            } else {
+                (focused, split.other)
+            };
+            let pad = matches!(split.direction, Direction::Vertical) || self.outline.is_some();
+            let first = self.render_pane(first, !split.second_focused, first_area, pad);
+            let second = self.render_pane(
+                second,
+                split.second_focused,
+                second_area,
+                self.outline.is_some(),
+            );
+            match split.direction {
+                Direction::Horizontal => {
+                    let (bg, fg) = self.ui.status_colors(self.theme(ts));
+                    let divider = format!(
+                        "{}{}{}{}",
+                        Terminal::bg(bg),
+                        Terminal::fg(fg),
+                        self.config
+                            .glyphs()
+                            .divider_horizontal
+                            .to_string()
+                            .repeat(width),
+                        style::Reset
+                    );
+                    first
+                        .into_iter()
+                        .chain(iter::once(divider))
+                        .chain(second)
+                        .collect()
+                }
+                Direction::Vertical => first
+                    .into_iter()
+                    .zip(second)
+                    .map(|(left, right)| {
+                        format!("{left}{}{right}", self.config.glyphs().divider_vertical)
+                    })
+                    .collect(),
+            }
+        } else {
+            self.render_pane(focused, true, area, self.outline.is_some())
+        };
+        let lines = self.with_outline(self.with_file_tree(lines));
+        // placed line by line, as a newline after the last one would scroll
+        // the screen when there are no bars below
+        for (y, line) in lines.into_iter().take(height).enumerate() {
+            Terminal::cursor_position(&Position { x: 0, y });
+            Terminal::clear_current_line();
+            print!("{line}");
+        }
+    }
+    // the lines of the panes with the file tree in front of them, its
+    // selected entry in reverse video while it has the keys and bold
+    // otherwise
+    fn with_file_tree(&self, lines: Vec<String>) -> Vec<String> {
+        let width = self.tree_width();
+        let Some(tree) = self.file_tree.as_ref().filter(|_| width > 0) else {
+            return lines;
+        };
+        let glyphs = self.config.glyphs();
+        let selected = tree.selected.checked_sub(tree.offset);
+        let entries = tree.lines(width, lines.len(), glyphs);
+        entries
+            .into_iter()
+            .zip(lines)
+            .enumerate()
+            .map(|(y, (entry, line))| {
+                let look = match () {
+                    () if Some(y) != selected => String::new(),
+                    () if tree.focused => style::Invert.to_string(),
+                    () => style::Bold.to_string(),
+                };
+                format!(
+                    "{look}{entry}{}{}{line}",
+                    style::Reset,
+                    glyphs.divider_vertical
+                )
+            })
+            .collect()
+    }
+    // the lines with the outline after them, its selected symbol in
+    // reverse video while it has the keys and bold otherwise
+    fn with_outline(&self, lines: Vec<String>) -> Vec<String> {
+        let width = self.outline_width();
+        let Some(outline) = self.outline.as_ref().filter(|_| width > 0) else {
+            return lines;
+        };
+        let selected = outline.selected.checked_sub(outline.offset);
+        let entries = outline.lines(width, lines.len());
+        lines
+            .into_iter()
+            .zip(entries)
+            .enumerate()
+            .map(|(y, (line, entry))| {
+                let look = match () {
+                    () if Some(y) != selected || outline.is_empty() => String::new(),
+                    () if outline.focused => style::Invert.to_string(),
+                    () => style::Bold.to_string(),
+                };
+                let divider = self.config.glyphs().divider_vertical;
+                format!(
+                    "{line}{}{divider}{look}{entry}{}",
+                    style::Reset,
+                    style::Reset
+                )
+            })
+            .collect()
+    }
+    // the areas of both panes, None without a split or the room for one, in
+    // which case the focused pane takes the screen
+    fn pane_areas(&self) -> Option<(Area, Area)> {
+        let split = self.split.as_ref()?;
+        let area = self.panes_area();
+        let (mut first, mut second) = pane::layout(split.direction, area.width, area.height);
+        first.x = first.x.saturating_add(area.x);
+        second.x = second.x.saturating_add(area.x);
+        let fits = first.width > 0 && first.height > 0 && second.width > 0 && second.height > 0;
+        fits.then_some((first, second))
+    }
+    // where on screen the focused pane is drawn
+    fn view_area(&self) -> Area {
+        match (&self.split, self.pane_areas()) {
+            (Some(split), Some((first, second))) => {
+                if split.second_focused {
+                    second
+                } else {
+                    first
+                }
+            }
+            _ => self.panes_area(),
+        }
+    }
+    // how wide the file tree is drawn, 0 while it is hidden
+    fn tree_width(&self) -> usize {
+        if self.file_tree.is_none() {
+            return 0;
+        }
+        cmp::min(TREE_WIDTH, usize::from(self.terminal.size().width) / 3)
+    }
+    fn outline_width(&self) -> usize {
+        if self.outline.is_none() {
+            return 0;
+        }
+        cmp::min(OUTLINE_WIDTH, usize::from(self.terminal.size().width) / 3)
+    }
+    // the screen between the file tree and the outline and the dividers
+    // next to them
+    fn panes_area(&self) -> Area {
+        let size = self.terminal.size();
+        let (width, height) = (usize::from(size.width), usize::from(size.height));
+        let beside = |panel: usize| {
+            if panel == 0 {
+                0
             } else {
-                println!("~\r");
+                panel.saturating_add(1)
+            }
+        };
+        let (left, right) = (beside(self.tree_width()), beside(self.outline_width()));
+        Area {
+            x: left,
+            y: 0,
+            width: width.saturating_sub(left).saturating_sub(right),
+            height,
+        }
+    }
+    // the focused pane without its line number gutter
+    fn text_area(&self) -> Area {
+        let area = self.view_area();
+        let gutter = cmp::min(self.gutter_width(), area.width);
+        self.column_area(Area {
+            x: area.x.saturating_add(gutter),
+            width: area.width.saturating_sub(gutter),
+            ..area
+        })
+    }
+    // the part of a pane's text area that wrapped text takes, no wider than
+    // the wrap column
+    fn column_area(&self, area: Area) -> Area {
+        let column = self.config.wrap_column;
+        if !self.config.wrap || column == 0 || column >= area.width {
+            return area;
+        }
+        let margin = match self.config.wrap_align {
+            WrapAlign::Left => 0,
+            WrapAlign::Center => (area.width - column) / 2,
+        };
+        Area {
+            x: area.x.saturating_add(margin),
+            width: column,
+            ..area
+        }
+    }
+    fn gutter_width(&self) -> usize {
+        self.marks_width().saturating_add(self.numbers_width())
+    }
+    // room for a mark's letter and the sign of a diagnostic after it, while
+    // there are marks or diagnostics
+    fn marks_width(&self) -> usize {
+        if self.document.has_marks() || !self.document.diagnostics().is_empty() {
+            2
+        } else {
+            0
+        }
+    }
+    fn gutter_color(&self) -> color::Rgb {
+        self.ui.gutter_fg.map_or(LINE_NUMBER_COLOR, |color| color.0)
+    }
+    // the mark on row `index` and the sign of the worst diagnostic there, on
+    // the first line of the row only
+    fn gutter_mark(&self, index: usize, segment: usize) -> String {
+        if segment > 0 {
+            return "  ".to_string();
+        }
+        let mark = self.document.mark_on(index).unwrap_or(' ');
+        let glyphs = self.config.glyphs();
+        let sign = match self.document.diagnostic_on(index) {
+            Some(severity) => {
+                let glyph = match severity {
+                    Severity::Error => glyphs.error_sign,
+                    Severity::Warning => glyphs.warning_sign,
+                    Severity::Note => glyphs.note_sign,
+                };
+                format!(
+                    "{}{glyph}{}",
+                    Terminal::fg(severity.to_color()),
+                    Terminal::fg(self.gutter_color())
+                )
             }
+            None => " ".to_string(),
+        };
+        format!("{mark}{sign}")
+    }
+    // room for the largest line number and a space after it
+    fn numbers_width(&self) -> usize {
+        match self.config.line_numbers {
+            LineNumbers::Off => 0,
+            LineNumbers::Absolute | LineNumbers::Relative => self
+                .document
+                .len()
+                .max(1)
+                .to_string()
+                .len()
+                .saturating_add(1),
+        }
+    }
+    fn cycle_line_numbers(&mut self) {
+        let (line_numbers, message) = match self.config.line_numbers {
+            LineNumbers::Off => (LineNumbers::Absolute, message!("line_numbers_on")),
+            LineNumbers::Absolute => (LineNumbers::Relative, message!("relative_line_numbers_on")),
+            LineNumbers::Relative => (LineNumbers::Off, message!("line_numbers_off")),
+        };
+        self.config.line_numbers = line_numbers;
+        self.global_config.line_numbers = line_numbers;
+        self.status_message = StatusMessage::from(message);
+    }
+    // no split, then stacked panes, then side by side ones, then back again
+    fn cycle_split(&mut self) {
+        let pane = self.view();
+        self.split = match self.split.take() {
+            None => Some(Split {
+                direction: Direction::Horizontal,
+                other: pane,
+                second_focused: false,
+            }),
+            Some(Split {
+                direction: Direction::Horizontal,
+                other,
+                second_focused,
+            }) => Some(Split {
+                direction: Direction::Vertical,
+                other,
+                second_focused,
+            }),
+            Some(Split {
+                direction: Direction::Vertical,
+                ..
+            }) => None,
+        };
+        let message = match self.split.as_ref().map(|split| split.direction) {
+            Some(Direction::Horizontal) => message!("split_horizontally"),
+            Some(Direction::Vertical) => message!("split_vertically"),
+            None => message!("closed_split"),
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+    fn focus_pane(&mut self, second: bool) {
+        let Some(split) = &mut self.split else {
+            return;
+        };
+        if split.second_focused == second {
+            return;
+        }
+        if let Some(target) = self.scroll_target.take() {
+            self.offset.y = target;
         }
+        let view = Pane {
+            cursor_position: self.cursor_position,
+            offset: self.offset,
+            selection_anchor: self.selection_anchor,
+        };
+        split.second_focused = second;
+        // each pane keeps its own selection
+        let other = mem::replace(&mut split.other, view);
+        self.cursor_position = other.cursor_position;
+        self.offset = other.offset;
+        // the other pane's edits may have moved the text out from under it
+        self.selection_anchor = other
+            .selection_anchor
+            .map(|anchor| self.document.clamp_end(&anchor));
+        self.clamp_cursor();
+    }
+    // where the focused pane is, as the other one is kept
+    fn view(&self) -> Pane {
+        Pane {
+            cursor_position: self.cursor_position,
+            offset: self.offset,
+            selection_anchor: self.selection_anchor,
+        }
+    }
+    fn clamp_cursor(&mut self) {
+        let y = cmp::min(self.cursor_position.y, self.document.len());
+        let x = self
+            .document
+            .row(y)
+            .map_or(0, |row| cmp::min(self.cursor_position.x, row.len()));
+        self.cursor_position = Position { x, y };
     }
     fn prompt(&mut self, prompt: &str) -> Result<Option<String>, std::io::Error> {
-        let mut result = String::new();
-        let ps = SyntaxSet::load_defaults_newlines();
-        let ts = ThemeSet::load_defaults();
+        self.prompt_with(prompt, |_, _| ())
+    }
+    // like prompt, calling `on_change` with the answer so far after each edit
+    fn prompt_with<C>(
+        &mut self,
+        prompt: &str,
+        on_change: C,
+    ) -> Result<Option<String>, std::io::Error>
+    where
+        C: FnMut(&mut Self, &str),
+    {
+        self.prompt_full(prompt, on_change, |_| Vec::new(), &[])
+    }
+    // like prompt_with, Tab going through what `complete` offers for the
+    // answer as it was typed, and Up and Down through the answers given
+    // before in `history`, oldest first. The prompt starts with the last of
+    // them, which Enter gives again and the first character typed replaces.
+    fn prompt_full<C, F>(
+        &mut self,
+        prompt: &str,
+        mut on_change: C,
+        complete: F,
+        history: &[String],
+    ) -> Result<Option<String>, std::io::Error>
+    where
+        C: FnMut(&mut Self, &str),
+        F: Fn(&str) -> Vec<String>,
+    {
+        if self.batch {
+            return Err(self.refuse_prompt(prompt));
+        }
+        let mut result = history.last().cloned().unwrap_or_default();
+        let mut completions: Vec<String> = Vec::new();
+        let mut completion = 0;
+        // the answer from `history` showing, and what was typed before
+        // going back to it
+        let mut recalled = history.len().checked_sub(1);
+        let mut typed = String::new();
+        let mut fresh = !result.is_empty();
+        if fresh {
+            on_change(self, &result);
+        }
+        let (ps, ts) = (Arc::clone(&self.ps), Arc::clone(&self.ts));
         loop {
             self.status_message = StatusMessage::from(format!("{prompt}{result}"));
             self.refresh_screen(&ps, &ts)?;
-            match Terminal::read_key()? {
-                Key::Backspace => result.truncate(result.len().saturating_sub(1)),
+            let key = self.terminal.read_key()?;
+            if key != Key::Char('\t') {
+                completions.clear();
+            }
+            let replacing = fresh && matches!(key, Key::Char(c) if c != '\n' && c != '\t');
+            fresh = false;
+            match key {
+                Key::Up | Key::Down => {
+                    let index = match (key, recalled) {
+                        (Key::Up, None) => history.len().checked_sub(1),
+                        (Key::Up, Some(index)) => Some(index.saturating_sub(1)),
+                        (_, Some(index)) => {
+                            Some(index.saturating_add(1)).filter(|&next| next < history.len())
+                        }
+                        (_, None) => continue,
+                    };
+                    if recalled.is_none() {
+                        typed.clone_from(&result);
+                    }
+                    recalled = index;
+                    result = index
+                        .and_then(|index| history.get(index))
+                        .map_or_else(|| typed.clone(), String::clone);
+                    on_change(self, &result);
+                }
+                Key::Char('\t') => {
+                    if completions.is_empty() {
+                        completions = complete(&result);
+                        completion = 0;
+                    }
+                    if let Some(next) = completions.get(completion) {
+                        result.clone_from(next);
+                        completion = (completion + 1) % completions.len();
+                        on_change(self, &result);
+                    }
+                }
+                Key::Backspace => {
+                    result.pop();
+                    recalled = None;
+                    on_change(self, &result);
+                }
                 Key::Ctrl('c') | Key::Esc => {
                     result.truncate(0);
                     break;
@@ -397,7 +5071,12 @@ impl Editor {
                     if c == '\n' {
                         break;
                     }
+                    if replacing {
+                        result.clear();
+                    }
                     result.push(c);
+                    recalled = None;
+                    on_change(self, &result);
                 }
                 _ => (),
             }
@@ -410,7 +5089,179 @@ impl Editor {
     }
 }
 
+// what auto-close puts after `opener`
+fn closer(opener: char) -> Option<char> {
+    match opener {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '"' => Some('"'),
+        _ => None,
+    }
+}
+
+// the same file by different paths, like `./src/main.rs` and `src/main.rs`
+fn same_file(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+// the last line of a regex error, which says what is wrong
+fn pattern_error(error: &regex::Error) -> String {
+    let error = error.to_string();
+    let error = error.lines().last().unwrap_or_default();
+    message!("invalid_pattern", error = error)
+}
+
+// the buffers, tutor and keys to inject that the command line asks for,
+// with any problem with them left in `status`
+fn open_args(
+    args: &[String],
+    config: &mut Config,
+    status: &mut StatusMessage,
+) -> (VecDeque<Buffer>, Option<Tutor>, Vec<Key>) {
+    let mut buffers = VecDeque::new();
+    let mut tutor = None;
+    let mut keys = Vec::new();
+    let mut args = args.iter();
+    while let Some(file_name) = args.next() {
+        if file_name == "--recover" {
+            let number = args.next().map_or("", String::as_str);
+            match recovery::find(number).map(|snapshot| Document::recover(&snapshot)) {
+                Some(Ok(doc)) => buffers.push_back(Buffer::from(doc)),
+                _ => *status = StatusMessage::error(message!("no_snapshot", number = number)),
+            }
+        } else if file_name == "--tutor" {
+            buffers.push_back(Buffer::from(Document::scratch(&tutor::text(config))));
+            tutor = Some(Tutor::new());
+        } else if file_name == "--keys" {
+            let sequence = args.next().map_or("", String::as_str);
+            match keys::parse(sequence) {
+                Ok(parsed) => keys = parsed,
+                Err(error) => *status = StatusMessage::error(message!("bad_keys", error = error)),
+            }
+        } else if file_name == "--session" {
+            match session::load_last() {
+                Ok(last) => {
+                    if let Some(theme) = last.theme {
+                        config.theme = theme;
+                    }
+                    buffers.extend(open_session(last.buffers));
+                }
+                Err(error) => *status = StatusMessage::error(message!("error", error = error)),
+            }
+        } else if file_name == "--batch" {
+            // taken up by Editor::default
+        } else if file_name == "--wait" {
+            // the editor always runs until it is quit, which is what tools
+            // passing --wait for graphical editors are after
+        } else if let Ok(doc) = Document::open(file_name) {
+            buffers.push_back(Buffer::from(doc));
+        } else {
+            *status = StatusMessage::error(message!("cannot_open_file", file = file_name));
+        }
+    }
+    (buffers, tutor, keys)
+}
+
+// the files of a session that can still be opened, each where it was left
+fn open_session(entries: Vec<Entry>) -> VecDeque<Buffer> {
+    entries
+        .into_iter()
+        .filter_map(|entry| {
+            let mut buffer = Buffer::from(Document::open(&entry.file_name).ok()?);
+            buffer.cursor_position = Position {
+                x: entry.x,
+                y: entry.y,
+            };
+            buffer.offset = Position {
+                x: entry.offset_x,
+                y: entry.offset_y,
+            };
+            Some(buffer)
+        })
+        .collect()
+}
+
+fn help(config: &Config) -> String {
+    message!(
+        "help",
+        find = config.key_name(Command::Find),
+        save = config.key_name(Command::Save),
+        quit = config.key_name(Command::Quit)
+    )
+}
+
+fn width_of(text: &str) -> usize {
+    text.chars().count()
+}
+
+// cuts `text` down to `width` characters, marking where it was cut with
+// `ellipsis`
+fn ellipsize_end(text: &str, width: usize, ellipsis: &str) -> String {
+    if width_of(text) <= width {
+        return text.to_string();
+    }
+    // an ellipsis on its own says nothing
+    if width <= width_of(ellipsis) {
+        return String::new();
+    }
+    let mut cut: String = text
+        .chars()
+        .take(width.saturating_sub(width_of(ellipsis)))
+        .collect();
+    cut.push_str(ellipsis);
+    cut
+}
+
+// a UI theme over the config's status bar colors
+fn load_ui_theme(name: &str, config: &Config) -> Result<UiTheme, String> {
+    let mut ui = UiTheme::load(name)?;
+    ui.status_bg = ui.status_bg.or(config.status_bg_color);
+    ui.status_fg = ui.status_fg.or(config.status_fg_color);
+    Ok(ui)
+}
+
+// loads the syntaxes and themes on a thread, they take a while to read and
+// the editor can start out without them
+fn load_assets() -> Receiver<Assets> {
+    let (sender, assets) = mpsc::channel();
+    thread::spawn(move || {
+        let ps = SyntaxSet::load_defaults_newlines();
+        let (ts, error) = load_themes();
+        let _ = sender.send((ps, ts, error));
+    });
+    assets
+}
+
+// what the editor draws with while the assets load, plain text in white on
+// black
+fn placeholder_assets() -> (SyntaxSet, ThemeSet) {
+    let mut builder = SyntaxSetBuilder::new();
+    builder.add_plain_text_syntax();
+    let mut ts = ThemeSet::new();
+    ts.themes
+        .insert(config::DEFAULT_THEME.to_string(), Theme::default());
+    (builder.build(), ts)
+}
+
+// the built-in themes and those in the themes directory
+fn load_themes() -> (ThemeSet, Option<String>) {
+    let mut ts = ThemeSet::load_defaults();
+    for (name, theme) in highlighting::built_in_themes() {
+        ts.themes.insert(name.to_string(), theme);
+    }
+    let error = config::themes_dir()
+        .filter(|dir| dir.is_dir())
+        .and_then(|dir| ts.add_from_folder(&dir).err())
+        .map(|error| message!("cannot_load_themes", error = error));
+    (ts, error)
+}
+
 fn die(e: &std::io::Error) {
+    Terminal::restore_title();
     Terminal::clear_screen();
     panic!("{}", e);
 }
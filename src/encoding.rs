@@ -0,0 +1,109 @@
+use std::io;
+
+use crate::messages::message;
+
+const UTF8_BOM: [u8; 3] = [0xef, 0xbb, 0xbf];
+const UTF16_LE_BOM: [u8; 2] = [0xff, 0xfe];
+const UTF16_BE_BOM: [u8; 2] = [0xfe, 0xff];
+
+// how a file's text is stored, kept so saving writes it back the same way
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    // UTF-8 starting with a byte order mark, as some Windows tools write it
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    // ISO-8859-1, what a file that isn't valid UTF-8 is taken to be, as
+    // every byte is a character in it
+    Latin1,
+}
+
+impl Encoding {
+    // what the status bar shows
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "UTF-8",
+            Self::Utf8Bom => "UTF-8 BOM",
+            Self::Utf16Le => "UTF-16LE",
+            Self::Utf16Be => "UTF-16BE",
+            Self::Latin1 => "Latin-1",
+        }
+    }
+}
+
+// for text only part of which is at hand
+pub fn has_utf8_bom(bytes: &[u8]) -> bool {
+    bytes.starts_with(&UTF8_BOM)
+}
+
+// UTF-16 is only recognized by its byte order mark, without one there is no
+// telling it from other bytes for sure
+pub fn detect(bytes: &[u8]) -> Encoding {
+    let valid = |bytes| std::str::from_utf8(bytes).is_ok();
+    if bytes.strip_prefix(&UTF8_BOM).is_some_and(valid) {
+        Encoding::Utf8Bom
+    } else if bytes.starts_with(&UTF16_LE_BOM) {
+        Encoding::Utf16Le
+    } else if bytes.starts_with(&UTF16_BE_BOM) {
+        Encoding::Utf16Be
+    } else if valid(bytes) {
+        Encoding::Utf8
+    } else {
+        Encoding::Latin1
+    }
+}
+
+// the text of `bytes`, with the encoding it was found to be in
+pub fn decode(bytes: &[u8]) -> io::Result<(String, Encoding)> {
+    let encoding = detect(bytes);
+    let text = match encoding {
+        Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+        Encoding::Utf8Bom => String::from_utf8_lossy(&bytes[UTF8_BOM.len()..]).into_owned(),
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let body = &bytes[UTF16_LE_BOM.len()..];
+            if !body.len().is_multiple_of(2) {
+                return Err(invalid(&message!("utf16_odd_length")));
+            }
+            let units = body.chunks_exact(2).map(|pair| {
+                let pair = [pair[0], pair[1]];
+                if encoding == Encoding::Utf16Le {
+                    u16::from_le_bytes(pair)
+                } else {
+                    u16::from_be_bytes(pair)
+                }
+            });
+            char::decode_utf16(units)
+                .collect::<Result<String, _>>()
+                .map_err(|_| invalid("invalid UTF-16"))?
+        }
+        Encoding::Latin1 => bytes.iter().copied().map(char::from).collect(),
+    };
+    Ok((text, encoding))
+}
+
+// fails for text that has characters the encoding has no bytes for
+pub fn encode(text: &str, encoding: Encoding) -> io::Result<Vec<u8>> {
+    let bytes = match encoding {
+        Encoding::Utf8 => text.as_bytes().to_vec(),
+        Encoding::Utf8Bom => UTF8_BOM.iter().chain(text.as_bytes()).copied().collect(),
+        Encoding::Utf16Le => UTF16_LE_BOM
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect(),
+        Encoding::Utf16Be => UTF16_BE_BOM
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect(),
+        Encoding::Latin1 => text
+            .chars()
+            .map(|c| u8::try_from(c).map_err(|_| invalid(&message!("not_latin1", character = c))))
+            .collect::<io::Result<_>>()?,
+    };
+    Ok(bytes)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
@@ -0,0 +1,300 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::glyphs::Glyphs;
+use crate::messages::message;
+use crate::project_search::Filter;
+
+// a file or directory on a line of the tree
+pub struct Entry {
+    // from the working directory
+    pub path: PathBuf,
+    pub depth: usize,
+    pub directory: bool,
+}
+
+// the files under the working directory as an outline, directories first,
+// the ones opened showing what is in them
+#[derive(Default)]
+pub struct FileTree {
+    entries: Vec<Entry>,
+    expanded: HashSet<PathBuf>,
+    pub selected: usize,
+    // the first entry on screen
+    pub offset: usize,
+    // keys go to the tree rather than the text
+    pub focused: bool,
+    // the project's include and exclude globs
+    pub filter: Filter,
+}
+
+impl FileTree {
+    // reads the directories again, keeping the selection on the same path
+    // where it is still there
+    pub fn refresh(&mut self) {
+        let selected = self.selected().map(|entry| entry.path.clone());
+        self.entries.clear();
+        self.add_children(Path::new(""), 0);
+        // directories opened before that are gone
+        let entries = &self.entries;
+        self.expanded
+            .retain(|path| entries.iter().any(|entry| entry.path == *path));
+        if let Some(index) =
+            selected.and_then(|path| self.entries.iter().position(|entry| entry.path == path))
+        {
+            self.selected = index;
+        }
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+    }
+    fn add_children(&mut self, dir: &Path, depth: usize) {
+        let readable = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let Ok(read) = fs::read_dir(readable) else {
+            return;
+        };
+        let mut children: Vec<(bool, PathBuf)> = read
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name() != ".git")
+            .map(|entry| {
+                let directory = entry.file_type().is_ok_and(|kind| kind.is_dir());
+                (!directory, dir.join(entry.file_name()))
+            })
+            .filter(|(file, path)| !self.filter.excludes(&path.to_string_lossy(), !file))
+            .collect();
+        children.sort();
+        for (file, path) in children {
+            let expanded = !file && self.expanded.contains(&path);
+            self.entries.push(Entry {
+                path: path.clone(),
+                depth,
+                directory: !file,
+            });
+            if expanded {
+                self.add_children(&path, depth.saturating_add(1));
+            }
+        }
+    }
+    pub fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.selected)
+    }
+    // up or down by `count`, kept to the entries there are
+    pub fn select(&mut self, down: bool, count: usize) {
+        self.selected = if down {
+            self.selected
+                .saturating_add(count)
+                .min(self.entries.len().saturating_sub(1))
+        } else {
+            self.selected.saturating_sub(count)
+        };
+    }
+    pub fn is_expanded(&self, entry: &Entry) -> bool {
+        self.expanded.contains(&entry.path)
+    }
+    // opens the selected directory or closes it
+    pub fn toggle(&mut self) {
+        let Some(entry) = self.selected().filter(|entry| entry.directory) else {
+            return;
+        };
+        let path = entry.path.clone();
+        if !self.expanded.remove(&path) {
+            self.expanded.insert(path);
+        }
+        self.refresh();
+    }
+    // closes the selected directory, or goes up to the one it is in
+    pub fn collapse(&mut self) {
+        let Some(entry) = self.selected() else {
+            return;
+        };
+        if entry.directory && self.is_expanded(entry) {
+            self.toggle();
+            return;
+        }
+        let parent = entry.path.parent().map(Path::to_path_buf);
+        if let Some(index) =
+            parent.and_then(|parent| self.entries.iter().position(|entry| entry.path == parent))
+        {
+            self.selected = index;
+        }
+    }
+    // where a new file goes: into the selected directory when it is open,
+    // beside the selected entry otherwise
+    fn target_dir(&self) -> PathBuf {
+        match self.selected() {
+            Some(entry) if entry.directory && self.is_expanded(entry) => entry.path.clone(),
+            Some(entry) => entry
+                .path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            None => PathBuf::new(),
+        }
+    }
+    // makes the file `name`, or the directory for a name ending in `/`,
+    // selecting it
+    pub fn create(&mut self, name: &str) -> io::Result<PathBuf> {
+        let dir = self.target_dir();
+        if !dir.as_os_str().is_empty() {
+            self.expanded.insert(dir.clone());
+        }
+        let path = dir.join(name.trim_end_matches('/'));
+        if name.ends_with('/') {
+            fs::create_dir_all(&path)?;
+        } else {
+            if let Some(parent) = path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+            {
+                fs::create_dir_all(parent)?;
+            }
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+        }
+        self.refresh();
+        self.select_path(&path);
+        Ok(path)
+    }
+    // gives the selected entry the name `name` in the same directory,
+    // returning its old and new paths
+    pub fn rename(&mut self, name: &str) -> io::Result<(PathBuf, PathBuf)> {
+        let old = self
+            .selected()
+            .map(|entry| entry.path.clone())
+            .ok_or_else(nothing_selected)?;
+        let new = old.with_file_name(name);
+        if new.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists", new.display()),
+            ));
+        }
+        fs::rename(&old, &new)?;
+        if self.expanded.remove(&old) {
+            self.expanded.insert(new.clone());
+        }
+        self.refresh();
+        self.select_path(&new);
+        Ok((old, new))
+    }
+    // moves the selected file or directory into `trash`, returning where
+    // it was and where it went
+    pub fn delete(&mut self, trash: &Path) -> io::Result<(PathBuf, PathBuf)> {
+        let path = self
+            .selected()
+            .map(|entry| entry.path.clone())
+            .ok_or_else(nothing_selected)?;
+        fs::create_dir_all(trash)?;
+        let name = path
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        // the same name deleted twice in a second
+        let mut trashed = trash.join(format!("{stamp}-{name}"));
+        let mut copy: usize = 1;
+        while trashed.symlink_metadata().is_ok() {
+            copy = copy.saturating_add(1);
+            trashed = trash.join(format!("{stamp}-{copy}-{name}"));
+        }
+        move_path(&path, &trashed)?;
+        self.refresh();
+        Ok((path, trashed))
+    }
+    // moves what `delete` put at `trashed` back to `path`, selecting it
+    pub fn restore(&mut self, path: &Path, trashed: &Path) -> io::Result<()> {
+        if path.symlink_metadata().is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists", path.display()),
+            ));
+        }
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+            self.expanded.insert(parent.to_path_buf());
+        }
+        move_path(trashed, path)?;
+        self.refresh();
+        self.select_path(path);
+        Ok(())
+    }
+    fn select_path(&mut self, path: &Path) {
+        if let Some(index) = self.entries.iter().position(|entry| entry.path == path) {
+            self.selected = index;
+        }
+    }
+    // keeps the selection among the `height` entries on screen
+    pub fn scroll(&mut self, height: usize) {
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset.saturating_add(height) {
+            self.offset = self.selected.saturating_add(1).saturating_sub(height);
+        }
+    }
+    // the `height` lines on screen, indented by depth, each cut or padded
+    // to `width` characters
+    pub fn lines(&self, width: usize, height: usize, glyphs: &Glyphs) -> Vec<String> {
+        (self.offset..self.offset.saturating_add(height))
+            .map(|index| {
+                let text = self.entries.get(index).map_or_else(String::new, |entry| {
+                    let marker = match (entry.directory, self.is_expanded(entry)) {
+                        (false, _) => ' ',
+                        (true, true) => glyphs.folder_open,
+                        (true, false) => glyphs.folder_closed,
+                    };
+                    let name = entry
+                        .path
+                        .file_name()
+                        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+                    let slash = if entry.directory { "/" } else { "" };
+                    format!("{}{marker} {name}{slash}", "  ".repeat(entry.depth))
+                });
+                let text: String = text.chars().take(width).collect();
+                format!("{text:<width$}")
+            })
+            .collect()
+    }
+}
+
+// renames `from` to `to`, or copies it there and removes it where they
+// are on different file systems, as a trash directory under the home
+// directory can be
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(error) if error.kind() == io::ErrorKind::CrossesDevices => (),
+        renamed => return renamed,
+    }
+    copy_path(from, to)?;
+    if from.is_dir() {
+        fs::remove_dir_all(from)
+    } else {
+        fs::remove_file(from)
+    }
+}
+
+fn copy_path(from: &Path, to: &Path) -> io::Result<()> {
+    if !from.is_dir() {
+        return fs::copy(from, to).map(drop);
+    }
+    fs::create_dir(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_path(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+fn nothing_selected() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, message!("nothing_selected"))
+}
@@ -0,0 +1,81 @@
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+// the characters the UI draws besides the text. The `ascii` setting draws
+// these in place of the usual ones, for terminals and fonts that show
+// anything beyond ASCII poorly.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Glyphs {
+    // on the rows past the end of the document
+    pub empty_row: String,
+    // where a name or the status bar was cut to fit
+    pub ellipsis: String,
+    // ahead of `line truncated` at the end of an overlong row
+    pub truncated: String,
+    // between split panes
+    pub divider_horizontal: char,
+    pub divider_vertical: char,
+    // in place of an invisible character
+    pub invisible: char,
+    // what the `show_invisibles` setting draws for a tab, a trailing space
+    // and a non-breaking space
+    pub tab: char,
+    pub space: char,
+    pub nbsp: char,
+    // in place of every character of a redacted line
+    pub mask: char,
+    // ahead of a directory in the file tree, closed and open
+    pub folder_closed: char,
+    pub folder_open: char,
+    // in the gutter beside a row `cargo check` found an error, a warning or
+    // a note on
+    pub error_sign: char,
+    pub warning_sign: char,
+    pub note_sign: char,
+}
+
+impl Default for Glyphs {
+    fn default() -> Self {
+        Self {
+            empty_row: "~".to_string(),
+            ellipsis: "...".to_string(),
+            truncated: ">>".to_string(),
+            divider_horizontal: '-',
+            divider_vertical: '|',
+            invisible: '?',
+            tab: '>',
+            space: '.',
+            nbsp: '_',
+            mask: '#',
+            folder_closed: '+',
+            folder_open: '-',
+            error_sign: 'E',
+            warning_sign: 'W',
+            note_sign: 'i',
+        }
+    }
+}
+
+// what is drawn unless the `ascii` setting is on
+pub fn unicode() -> &'static Glyphs {
+    static UNICODE: OnceLock<Glyphs> = OnceLock::new();
+    UNICODE.get_or_init(|| Glyphs {
+        empty_row: "~".to_string(),
+        ellipsis: "\u{2026}".to_string(),
+        truncated: "\u{bb}".to_string(),
+        divider_horizontal: '\u{2500}',
+        divider_vertical: '\u{2502}',
+        invisible: '\u{25cc}',
+        tab: '\u{2192}',
+        space: '\u{b7}',
+        nbsp: '\u{2423}',
+        mask: '\u{2588}',
+        folder_closed: '\u{25b8}',
+        folder_open: '\u{25be}',
+        error_sign: '\u{25cf}',
+        warning_sign: '\u{25b2}',
+        note_sign: '\u{25cb}',
+    })
+}
@@ -0,0 +1,131 @@
+use crate::command_line;
+use crate::config::{self, Command, Config};
+
+const TITLE: &str = "Help";
+
+// what each command does, as the keys section of the help says
+fn describe(command: Command) -> &'static str {
+    match command {
+        Command::Quit => "Quit, asking first if there are unsaved changes",
+        Command::Copy => "Copy the selection, or the row without one",
+        Command::Cut => "Cut the selection, or without one move rows, or a block selected with Shift, with Up and Down",
+        Command::Paste => "Paste",
+        Command::DeleteRow => "Delete the row",
+        Command::Find => "Search, a leading / makes it a regex",
+        Command::FindNext => "Next match of the last search",
+        Command::FindPrevious => "Previous match of the last search",
+        Command::Replace => "Replace matches of a regex, asking at each one; b at the question does every open buffer",
+        Command::InspectGrapheme => "Describe the character under the cursor",
+        Command::ToggleWarnings => "Turn invisible character warnings on or off",
+        Command::ToggleInvisibles => "Show tabs and trailing spaces, or stop showing them",
+        Command::Save => "Save",
+        Command::Undo => "Undo",
+        Command::Redo => "Redo",
+        Command::InsertFile => "Insert a file, or the output of !command",
+        Command::Open => "Open a file in a new buffer",
+        Command::NextBuffer => "Switch to the next buffer; Ctrl-Alt-Left and Right go either way",
+        Command::CloseBuffer => "Close the buffer",
+        Command::ReopenBuffer => "Open the last closed buffer again",
+        Command::SelectionBytes => "Count the bytes of the selection",
+        Command::Split => "Split the window, or close the split",
+        Command::NextPane => "Go to the other pane",
+        Command::LineNumbers => "Cycle line numbers: on, relative, off",
+        Command::Wrap => "Turn line wrapping on or off",
+        Command::InsertVariable => "Insert a variable like ${date}",
+        Command::Theme => "Choose the syntax theme",
+        Command::UiTheme => "Choose the UI theme",
+        Command::ExpandSelection => "Grow the selection to what is around it",
+        Command::ShrinkSelection => "Shrink the selection back",
+        Command::SaveSession => "Save the open files as a session",
+        Command::LoadSession => "Open the files of a session",
+        Command::ShowPath => "Show the full path of the file",
+        Command::Redact => "Mask lines that look like they hold secrets",
+        Command::LineEnding => "Switch between LF and CRLF line endings",
+        Command::Execute => "Type a command, like :goto 120",
+        Command::Reflow => "Fill the paragraph out to the wrap column",
+        Command::SetMark => "Name the cursor's place by a letter",
+        Command::GoToMark => "Go back to a place a letter names",
+        Command::Locations => "Find file:line places, in the buffer or the clipboard",
+        Command::NextLocation => "Go to the next file:line place",
+        Command::PreviousLocation => "Go to the previous file:line place",
+        Command::GoToDefinition => "Go to the definition of the word at the cursor, from the tags file or, without one, the index of the project",
+        Command::SearchTags => "List the tags with a name like the one asked for",
+        Command::JumpBack => "Go back to where the cursor was before going to a tag",
+        Command::FileTree => "Show the file tree, go to it, or hide it; n, r and d make, rename and trash files, u restores the last",
+        Command::Outline => "Show the declarations of the file, go to them, or hide them; Enter goes to one",
+        Command::CargoCheck => "Run cargo check, marking its errors and warnings in the files",
+        Command::NextDiagnostic => "Go to the next error or warning of the check",
+        Command::PreviousDiagnostic => "Go to the previous error or warning of the check",
+        Command::ProjectSearch => "Search the files under the directory, Enter on a match opens it",
+        Command::MatchBracket => "Go to the bracket pairing with the one at the cursor",
+        Command::ToggleComment => "Comment out the row or selected rows, or uncomment them",
+        Command::SpellSuggest => "Replace the misspelled word with a suggestion, the next one each time",
+        Command::ExpandSnippet => "Expand the snippet named before the cursor, Tab and Shift-Tab go through its fields",
+        Command::Help => "Show this help",
+    }
+}
+
+// what each of the command line's own commands does
+fn describe_line(name: &str) -> &'static str {
+    match name {
+        "w" => "Save",
+        "q" => "Quit",
+        "wq" => "Save, then quit",
+        "goto" => "Go to a line, also just the number",
+        "set" => "Change a setting, see below",
+        "setlocal" => "Change a setting for this buffer only, over its filetype's",
+        "theme" => "Switch to a syntax theme",
+        "help" => "Open this help at a topic, like :help save",
+        "sort" => "Sort the selected rows or all, :sort r backwards, :sort n by number",
+        "uniq" => "Remove rows that repeat the row before them",
+        "undo" => "Show the undo tree, Enter on a state goes to it, :undo N goes to state N",
+        _ => "",
+    }
+}
+
+// the help, with the keys as they are bound now. Each line starts with its
+// key or command, then the name a topic is looked up by.
+pub fn text(config: &Config) -> String {
+    let mut lines = vec![
+        TITLE.to_string(),
+        String::new(),
+        "Keys, changed in the [keys] table of the config".to_string(),
+    ];
+    for command in config::commands() {
+        lines.push(format!(
+            "  {:<12} {:<20} {}",
+            config.key_name(command),
+            command.name(),
+            describe(command)
+        ));
+    }
+    lines.push(String::new());
+    lines.push(format!(
+        "Command line, on {} or : in Normal mode; any command above by name too",
+        config.key_name(Command::Execute)
+    ));
+    for name in command_line::NAMES {
+        lines.push(format!("  :{name:<11} {name:<20} {}", describe_line(name)));
+    }
+    lines.push(String::new());
+    lines.push("Settings, as :set name, :set noname or :set name=number".to_string());
+    for setting in config::SETTINGS {
+        lines.push(format!("  {}", setting.trim_end_matches('=')));
+    }
+    lines.join("\n")
+}
+
+// the row of the help that `topic` names: a command, a setting, or the
+// first word of a heading
+pub fn find(text: &str, topic: &str) -> Option<usize> {
+    let topic = topic.trim().trim_start_matches(':');
+    text.lines().position(|line| {
+        let heading = !line.starts_with(' ');
+        let words = line
+            .split_whitespace()
+            .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric() && c != '_'));
+        words
+            .take(if heading { 1 } else { 2 })
+            .any(|word| word.eq_ignore_ascii_case(topic))
+    })
+}
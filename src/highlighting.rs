@@ -1,19 +1,222 @@
+use syntect::highlighting::{
+    Color, FontStyle, HighlightIterator, HighlightState, Highlighter, Style, StyleModifier, Theme,
+    ThemeItem, ThemeSettings,
+};
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet};
 use termion::color;
 
-#[derive(PartialEq)]
-#[derive(Clone)]
+use crate::ui;
+use crate::Row;
+
+// the scopes the built-in themes color, with the foreground and font style
+// of each. Keywords, functions and errors also stand out by their style, so
+// they don't rest on telling hues apart.
+type Palette = [(&'static str, [u8; 3], FontStyle); 8];
+
+// white and bright colors on black, for low vision
+const HIGH_CONTRAST: Palette = [
+    ("comment", [200, 200, 200], FontStyle::ITALIC),
+    ("string", [255, 255, 0], FontStyle::empty()),
+    ("constant", [255, 160, 255], FontStyle::empty()),
+    ("keyword, storage", [0, 255, 255], FontStyle::BOLD),
+    (
+        "entity.name.function, support.function",
+        [255, 255, 255],
+        FontStyle::BOLD,
+    ),
+    (
+        "entity.name.type, support.type, entity.name.class",
+        [140, 255, 140],
+        FontStyle::empty(),
+    ),
+    ("variable.parameter", [255, 200, 120], FontStyle::empty()),
+    (
+        "invalid",
+        [255, 90, 90],
+        FontStyle::BOLD.union(FontStyle::UNDERLINE),
+    ),
+];
+
+// the Okabe-Ito colors, which stay apart with red-green and blue-yellow
+// color blindness
+const COLORBLIND: Palette = [
+    ("comment", [153, 153, 153], FontStyle::ITALIC),
+    ("string", [86, 180, 233], FontStyle::empty()),
+    ("constant", [204, 121, 167], FontStyle::empty()),
+    ("keyword, storage", [230, 159, 0], FontStyle::BOLD),
+    (
+        "entity.name.function, support.function",
+        [240, 228, 66],
+        FontStyle::empty(),
+    ),
+    (
+        "entity.name.type, support.type, entity.name.class",
+        [0, 158, 115],
+        FontStyle::empty(),
+    ),
+    ("variable.parameter", [230, 230, 230], FontStyle::ITALIC),
+    (
+        "invalid",
+        [213, 94, 0],
+        FontStyle::BOLD.union(FontStyle::UNDERLINE),
+    ),
+];
+
+#[derive(PartialEq, Clone)]
 pub enum Type {
     None,
-    Number,
     Match,
+    Misspelled,
+    // what `cargo check` found, by severity
+    Error,
+    Warning,
+    Note,
 }
 
 impl Type {
-    pub fn to_color(&self) -> impl color::Color {
+    pub fn to_color(&self) -> color::Rgb {
         match self {
-            Type::Number => color::Rgb(220, 163, 163),
             Type::Match => color::Rgb(38, 139, 210),
-            _ => color::Rgb(255, 255, 255),
+            Type::Misspelled | Type::Error => color::Rgb(220, 50, 47),
+            Type::Warning => color::Rgb(181, 137, 0),
+            Type::Note => color::Rgb(42, 161, 152),
+            Type::None => color::Rgb(255, 255, 255),
+        }
+    }
+}
+
+// the syntax themes that come with the editor, named like the UI themes
+// they go with
+pub fn built_in_themes() -> [(&'static str, Theme); 2] {
+    [
+        (
+            ui::HIGH_CONTRAST,
+            theme(
+                ui::HIGH_CONTRAST,
+                [0, 0, 0],
+                [255, 255, 255],
+                &HIGH_CONTRAST,
+            ),
+        ),
+        (
+            ui::COLORBLIND,
+            theme(ui::COLORBLIND, [24, 24, 24], [230, 230, 230], &COLORBLIND),
+        ),
+    ]
+}
+
+fn theme(name: &str, background: [u8; 3], foreground: [u8; 3], palette: &Palette) -> Theme {
+    let color = |[r, g, b]: [u8; 3]| Color { r, g, b, a: 0xFF };
+    Theme {
+        name: Some(name.to_string()),
+        settings: ThemeSettings {
+            foreground: Some(color(foreground)),
+            background: Some(color(background)),
+            ..ThemeSettings::default()
+        },
+        scopes: palette
+            .iter()
+            .map(|&(selector, foreground, font_style)| ThemeItem {
+                scope: selector.parse().expect("invalid scope selector"),
+                style: StyleModifier {
+                    foreground: Some(color(foreground)),
+                    background: None,
+                    font_style: Some(font_style),
+                },
+            })
+            .collect(),
+        ..Theme::default()
+    }
+}
+
+// how `theme` draws text that isn't highlighted
+pub fn plain(theme: &Theme) -> Style {
+    Style {
+        foreground: theme.settings.foreground.unwrap_or(Color::WHITE),
+        background: theme.settings.background.unwrap_or(Color::BLACK),
+        font_style: FontStyle::empty(),
+    }
+}
+
+// how `theme` draws comments
+pub fn comment(theme: &Theme) -> Style {
+    let scope = Scope::new("comment").expect("invalid comment scope");
+    Highlighter::new(theme).style_for_stack(&[scope])
+}
+
+// where the parser and highlighter are at the end of a row
+#[derive(Clone, PartialEq)]
+pub struct State {
+    parse: ParseState,
+    highlight: HighlightState,
+}
+
+// a row's highlighting, kept until the row changes
+#[derive(Clone)]
+pub struct Highlighted {
+    // the syntax the row was highlighted in
+    pub scope: Scope,
+    // styled runs covering the row's text, as lengths in bytes
+    runs: Vec<(Style, usize)>,
+    pub state: State,
+}
+
+impl State {
+    fn start(syntax: &SyntaxReference, highlighter: &Highlighter) -> Self {
+        Self {
+            parse: ParseState::new(syntax),
+            highlight: HighlightState::new(highlighter, ScopeStack::new()),
         }
     }
-}
\ No newline at end of file
+}
+
+impl Highlighted {
+    // highlights `row` carrying on from `before`, or from the start of
+    // `syntax` without it. Rows longer than `max_len` are left plain and the
+    // row after them starts afresh.
+    pub fn new(
+        row: &Row,
+        syntax: &SyntaxReference,
+        before: Option<&State>,
+        ps: &SyntaxSet,
+        highlighter: &Highlighter,
+        max_len: usize,
+    ) -> Self {
+        let text = row.as_str();
+        let plain = vec![(highlighter.get_default(), text.len())];
+        if row.len() > max_len {
+            return Self {
+                scope: syntax.scope,
+                runs: plain,
+                state: State::start(syntax, highlighter),
+            };
+        }
+        let mut state = before.map_or_else(|| State::start(syntax, highlighter), State::clone);
+        // the syntaxes are the newline kind, which need to see the line end
+        let line = format!("{text}\n");
+        let runs = match state.parse.parse_line(&line, ps) {
+            Ok(ops) => HighlightIterator::new(&mut state.highlight, &ops, &line, highlighter)
+                .map(|(style, piece)| (style, piece.len()))
+                .collect(),
+            Err(_) => plain,
+        };
+        Self {
+            scope: syntax.scope,
+            runs,
+            state,
+        }
+    }
+    // the runs laid over `text`, the row they were made from
+    pub fn ranges<'a>(&self, text: &'a str) -> Vec<(Style, &'a str)> {
+        let mut start: usize = 0;
+        let mut ranges = Vec::with_capacity(self.runs.len());
+        for &(style, len) in &self.runs {
+            let end = start.saturating_add(len);
+            if let Some(piece) = text.get(start..end.min(text.len())) {
+                ranges.push((style, piece));
+            }
+            start = end;
+        }
+        ranges
+    }
+}
@@ -0,0 +1,62 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+// how a file indents its lines, kept so new lines and Tab go on the same way
+#[derive(Clone, Copy, PartialEq)]
+pub enum Indent {
+    Tabs,
+    // this many spaces a level
+    Spaces(usize),
+}
+
+impl Indent {
+    // what the status bar shows
+    pub fn name(self) -> String {
+        match self {
+            Self::Tabs => "Tabs".to_string(),
+            Self::Spaces(width) => format!("Spaces:{width}"),
+        }
+    }
+    // what one level of indentation is
+    pub fn unit(self) -> String {
+        match self {
+            Self::Tabs => "\t".to_string(),
+            Self::Spaces(width) => " ".repeat(width),
+        }
+    }
+}
+
+// the way most indented `lines` are indented, None for text with no
+// indented lines to tell by. The width of a space indent is the step seen
+// most between one line and the next, the narrower one winning a tie.
+pub fn detect<'a>(lines: impl Iterator<Item = &'a str>) -> Option<Indent> {
+    let (mut tabs, mut spaces) = (0_usize, 0_usize);
+    let mut steps: HashMap<usize, usize> = HashMap::new();
+    let mut previous = 0;
+    for line in lines.filter(|line| !line.trim().is_empty()) {
+        if line.starts_with('\t') {
+            tabs = tabs.saturating_add(1);
+            continue;
+        }
+        let width = line.chars().take_while(|c| *c == ' ').count();
+        // one space is how block comments like this line up their `*`s,
+        // rather than a level
+        let step = width.abs_diff(previous);
+        if (2..=8).contains(&step) {
+            spaces = spaces.saturating_add(1);
+            let count = steps.entry(step).or_default();
+            *count = count.saturating_add(1);
+        }
+        previous = width;
+    }
+    if tabs == 0 && spaces == 0 {
+        None
+    } else if tabs >= spaces {
+        Some(Indent::Tabs)
+    } else {
+        steps
+            .into_iter()
+            .max_by_key(|&(step, count)| (count, Reverse(step)))
+            .map(|(step, _)| Indent::Spaces(step))
+    }
+}
@@ -0,0 +1,187 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::project_search::{self, Filter};
+use crate::symbols;
+use crate::tags::{Tag, Tags};
+
+// the walk rests PAUSE after every BATCH files, so indexing a big tree
+// leaves the disk and a core to everything else
+const BATCH: usize = 200;
+const PAUSE: Duration = Duration::from_millis(20);
+// files bigger than this are listed but not read for declarations
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+// a NUL in this much of the start of a file makes it binary
+const BINARY_SAMPLE: usize = 8_000;
+// an index this old is walked again the next time it is asked for
+const REFRESH: Duration = Duration::from_mins(1);
+
+// a file the walk came to, by its name from the root, with what it declares
+struct Found {
+    name: String,
+    tags: Vec<Tag>,
+}
+
+// the files under the working directory that project search looks in, and
+// the declarations in them for tag lookups without a tags file. The walk is
+// on a thread, what it found is served until the next walk is done, and
+// files the editor writes or the file tree changes are taken in at once.
+pub struct Index {
+    root: PathBuf,
+    filter: Filter,
+    files: Option<Vec<String>>,
+    tags: Option<Tags>,
+    // the walk going on, with what it found so far
+    walking: Option<(Receiver<Found>, Vec<String>, Vec<Tag>)>,
+    walked: Instant,
+    // a directory changed, which only walking again takes in
+    stale: bool,
+}
+
+impl Index {
+    // starts walking `root`, leaving out what .gitignore files and `filter`
+    // do
+    pub fn start(root: PathBuf, filter: Filter) -> Self {
+        let mut index = Self {
+            root,
+            filter,
+            files: None,
+            tags: None,
+            walking: None,
+            walked: Instant::now(),
+            stale: false,
+        };
+        index.walk();
+        index
+    }
+    fn walk(&mut self) {
+        let (sender, found) = mpsc::channel();
+        let (root, filter) = (self.root.clone(), self.filter.clone());
+        thread::spawn(move || walk(&root, &filter, &sender));
+        self.walking = Some((found, Vec::new(), Vec::new()));
+        self.stale = false;
+    }
+    // walks again if the last walk is old, unless one is going on
+    pub fn refresh(&mut self) {
+        if self.walking.is_none() && (self.stale || self.walked.elapsed() >= REFRESH) {
+            self.walk();
+        }
+    }
+    // takes what the walk found since, true if it got anything
+    pub fn receive(&mut self) -> bool {
+        let Some((found, files, tags)) = &mut self.walking else {
+            return false;
+        };
+        let mut received = false;
+        loop {
+            match found.try_recv() {
+                Ok(Found { name, tags: own }) => {
+                    files.push(name);
+                    tags.extend(own);
+                    received = true;
+                }
+                Err(TryRecvError::Empty) => return received,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        if let Some((_, files, tags)) = self.walking.take() {
+            self.files = Some(files);
+            self.tags = Some(Tags::from_entries(tags));
+            self.walked = Instant::now();
+        }
+        true
+    }
+    // how many files the walk going on came to so far, None when there is
+    // none
+    pub fn walking(&self) -> Option<usize> {
+        self.walking.as_ref().map(|(_, files, _)| files.len())
+    }
+    // None until the first walk is done
+    pub fn files(&self) -> Option<&[String]> {
+        self.files.as_deref()
+    }
+    pub fn tags(&self) -> Option<&Tags> {
+        self.tags.as_ref()
+    }
+    // reads the file or directory at `path` again, as it was written, made,
+    // renamed or deleted
+    pub fn touch(&mut self, path: &Path) {
+        let path: PathBuf = path
+            .strip_prefix(&self.root)
+            .unwrap_or(path)
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect();
+        if path.is_absolute() || self.files.is_none() {
+            return;
+        }
+        let full = self.root.join(&path);
+        if full.is_dir() {
+            self.stale = true;
+            return;
+        }
+        let name = path.to_string_lossy().into_owned();
+        let inside = format!("{name}/");
+        let (Some(files), Some(tags)) = (&mut self.files, &mut self.tags) else {
+            return;
+        };
+        files.retain(|file| *file != name && !file.starts_with(&inside));
+        let own = if full.is_file() && !project_search::passes_over(&self.root, &name, &self.filter)
+        {
+            let at = files.partition_point(|file| *file < name);
+            files.insert(at, name.clone());
+            declarations(&full, &name)
+        } else {
+            Vec::new()
+        };
+        tags.replace_file(&name, own);
+    }
+}
+
+fn walk(root: &Path, filter: &Filter, sender: &Sender<Found>) {
+    let mut count: usize = 0;
+    // the walk stops once the editor has let go of the index
+    project_search::files(root, filter, &mut |path, name| {
+        count = count.saturating_add(1);
+        if count.is_multiple_of(BATCH) {
+            thread::sleep(PAUSE);
+        }
+        let found = Found {
+            name: name.to_string(),
+            tags: declarations(path, name),
+        };
+        sender.send(found).is_ok()
+    })
+    .ok();
+}
+
+// the names declared in the file at `path`, in the languages symbols knows
+fn declarations(path: &Path, name: &str) -> Vec<Tag> {
+    let Some(language) = path
+        .extension()
+        .and_then(|extension| symbols::language_of(&extension.to_string_lossy()))
+    else {
+        return Vec::new();
+    };
+    if fs::metadata(path).map_or(true, |metadata| metadata.len() > MAX_FILE_SIZE) {
+        return Vec::new();
+    }
+    let Ok(bytes) = fs::read(path) else {
+        return Vec::new();
+    };
+    if bytes.iter().take(BINARY_SAMPLE).any(|byte| *byte == 0) {
+        return Vec::new();
+    }
+    String::from_utf8_lossy(&bytes)
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let label = symbols::declaration(language, line)?;
+            let declared = symbols::declared_name(&label)?;
+            Some(Tag::at_line(declared, name, index.saturating_add(1)))
+        })
+        .collect()
+}
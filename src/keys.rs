@@ -0,0 +1,50 @@
+use termion::event::Key;
+
+use crate::config;
+use crate::messages::message;
+
+// the keys of a sequence as `--keys` takes it: characters stand for
+// themselves, other keys are named in angle brackets, as `<Enter>`, `<Esc>`,
+// `<Up>` or `<ctrl-s>` and `<alt-x>` like in the `[keys]` table, and `<lt>`
+// is a `<`
+pub fn parse(sequence: &str) -> Result<Vec<Key>, String> {
+    let mut keys = Vec::new();
+    let mut rest = sequence;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        if c != '<' {
+            keys.push(Key::Char(c));
+            continue;
+        }
+        let Some((name, after)) = rest.split_once('>') else {
+            return Err(message!("unclosed_key"));
+        };
+        let key = named(name).ok_or_else(|| message!("unknown_key_name", name = name))?;
+        keys.push(key);
+        rest = after;
+    }
+    Ok(keys)
+}
+
+fn named(name: &str) -> Option<Key> {
+    let key = match name.to_ascii_lowercase().as_str() {
+        // termion reads the Enter key as a newline
+        "enter" | "cr" => Key::Char('\n'),
+        "tab" => Key::Char('\t'),
+        "space" => Key::Char(' '),
+        "lt" => Key::Char('<'),
+        "esc" => Key::Esc,
+        "bs" | "backspace" => Key::Backspace,
+        "del" | "delete" => Key::Delete,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        _ => return config::parse_key(name),
+    };
+    Some(key)
+}
@@ -0,0 +1,194 @@
+use std::cmp;
+use std::path::Path;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+// how a language writes a comment to the end of a line, or around one for
+// languages that only have block comments
+#[derive(Clone, Copy)]
+pub struct Comment {
+    pub leader: &'static str,
+    pub closer: &'static str,
+}
+
+const fn line(leader: &'static str) -> Comment {
+    Comment { leader, closer: "" }
+}
+
+// the languages by the extensions of their files, or by whole file names
+// for the ones that have none
+const LANGUAGES: [(&[&str], Comment); 8] = [
+    (
+        &[
+            "rs", "c", "h", "cc", "cpp", "hpp", "cs", "go", "java", "kt", "scala", "swift", "js",
+            "jsx", "ts", "tsx", "dart", "php", "zig", "jsonc",
+        ],
+        line("//"),
+    ),
+    (
+        &[
+            "py",
+            "sh",
+            "bash",
+            "zsh",
+            "fish",
+            "rb",
+            "pl",
+            "r",
+            "toml",
+            "yaml",
+            "yml",
+            "conf",
+            "ini",
+            "cfg",
+            "mk",
+            "cmake",
+            "nix",
+            "ex",
+            "exs",
+            "Makefile",
+            "Dockerfile",
+        ],
+        line("#"),
+    ),
+    (&["sql", "lua", "hs", "elm", "ada"], line("--")),
+    (&["lisp", "el", "clj", "scm", "asm"], line(";")),
+    (&["tex", "sty", "erl", "m"], line("%")),
+    (&["vim"], line("\"")),
+    (
+        &["html", "htm", "xml", "svg", "md", "markdown", "vue"],
+        Comment {
+            leader: "<!--",
+            closer: "-->",
+        },
+    ),
+    (
+        &["css", "scss", "less"],
+        Comment {
+            leader: "/*",
+            closer: "*/",
+        },
+    ),
+];
+
+// the comments of the language `file_name` is in, None for a language
+// that isn't known
+pub fn comment(file_name: Option<&str>) -> Option<Comment> {
+    type_names(file_name).into_iter().find_map(|name| {
+        LANGUAGES
+            .iter()
+            .find(|(names, _)| names.contains(&name.as_str()))
+            .map(|&(_, comment)| comment)
+    })
+}
+
+// what the type of `file_name` goes by, first to last: its extension, and
+// its whole name for files like Makefile that have none
+pub fn type_names(file_name: Option<&str>) -> Vec<String> {
+    let Some(file_name) = file_name else {
+        return Vec::new();
+    };
+    let path = Path::new(file_name);
+    [path.extension(), path.file_name()]
+        .into_iter()
+        .flatten()
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect()
+}
+
+// a row with a comment put in or taken out: `removed` graphemes at `at`
+// were replaced by `added` ones, which is how a cursor on it moves along
+pub struct Toggled {
+    pub text: String,
+    pub at: usize,
+    pub removed: usize,
+    pub added: usize,
+}
+
+impl Toggled {
+    // where what was at column `x` is now
+    pub fn moved(&self, x: usize) -> usize {
+        if x < self.at {
+            return x;
+        }
+        let x = cmp::max(x, self.at.saturating_add(self.removed));
+        let x = x.saturating_sub(self.removed).saturating_add(self.added);
+        cmp::min(x, self.text.graphemes(true).count())
+    }
+}
+
+// the lines commented out, at the indentation of the least indented one,
+// or if every line with text on it is a comment already, with their
+// comments taken out. Blank lines are left as they are.
+pub fn toggle(lines: &[&str], comment: Comment) -> Vec<Toggled> {
+    let text = |line: &&str| !line.trim().is_empty();
+    let commented = lines
+        .iter()
+        .filter(|line| text(line))
+        .all(|line| line.trim_start().starts_with(comment.leader));
+    let indent = lines
+        .iter()
+        .filter(|line| text(line))
+        .map(|line| line.chars().take_while(|c| c.is_whitespace()).count())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|&line| {
+            if !text(&line) {
+                Toggled {
+                    text: line.to_string(),
+                    at: 0,
+                    removed: 0,
+                    added: 0,
+                }
+            } else if commented {
+                uncomment(line, comment)
+            } else {
+                let (before, after) = line.split_at(
+                    line.char_indices()
+                        .nth(indent)
+                        .map_or(line.len(), |(index, _)| index),
+                );
+                let closer = if comment.closer.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {}", comment.closer)
+                };
+                Toggled {
+                    text: format!("{before}{} {after}{closer}", comment.leader),
+                    at: indent,
+                    removed: 0,
+                    added: comment.leader.graphemes(true).count().saturating_add(1),
+                }
+            }
+        })
+        .collect()
+}
+
+// takes the leader out with the space after it, and the closer with the
+// space before it
+fn uncomment(line: &str, comment: Comment) -> Toggled {
+    let body = line.trim_start();
+    let indent = &line[..line.len().saturating_sub(body.len())];
+    let after = body.strip_prefix(comment.leader).unwrap_or(body);
+    let spaced = after.strip_prefix(' ');
+    let removed = comment
+        .leader
+        .graphemes(true)
+        .count()
+        .saturating_add(usize::from(spaced.is_some()));
+    let mut rest = spaced.unwrap_or(after);
+    if !comment.closer.is_empty() {
+        let trimmed = rest.trim_end();
+        if let Some(inside) = trimmed.strip_suffix(comment.closer) {
+            rest = inside.strip_suffix(' ').unwrap_or(inside);
+        }
+    }
+    Toggled {
+        text: format!("{indent}{rest}"),
+        at: indent.chars().count(),
+        removed,
+        added: 0,
+    }
+}
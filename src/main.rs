@@ -1,8 +1,56 @@
 #![warn(clippy::all, clippy::pedantic)]
+#![allow(
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc,
+    clippy::must_use_candidate,
+    clippy::return_self_not_must_use
+)]
+mod bidi;
+mod buffer;
+mod command_line;
+mod config;
+mod diagnostics;
 mod document;
 mod editor;
+mod encoding;
+mod file_tree;
+mod glyphs;
+mod help;
+mod highlighting;
+mod indent;
+mod index;
+mod keys;
+mod language;
+mod messages;
+mod outline;
+mod palette;
+mod pane;
+mod paths;
+mod policy;
+mod project_search;
+mod quickfix;
+mod recovery;
+mod reflow;
+mod render;
 mod row;
+mod search;
+mod session;
+mod snippets;
+mod spell;
+mod swap;
+mod symbols;
+mod syntax;
+mod tags;
 mod terminal;
+mod tutor;
+mod ui;
+mod undo;
+mod unicode;
+mod variables;
+mod watch;
+
+use std::env;
+use std::process::ExitCode;
 
 pub use document::Document;
 use editor::Editor;
@@ -10,6 +58,22 @@ pub use editor::Position;
 pub use row::Row;
 pub use terminal::Terminal;
 
-fn main() {
-    Editor::default().run();
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let [flag] = args.as_slice() {
+        if flag == "--recover" {
+            // in the user's language
+            messages::init(&config::Config::load().0);
+            recovery::print_list();
+            return ExitCode::SUCCESS;
+        }
+    }
+    // nonzero when changes were thrown away, so that e.g. git aborts the
+    // commit the editor was opened for, or when a --batch run couldn't
+    // finish
+    if Editor::default().run() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
 }
@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::sync::OnceLock;
+
+use crate::config::{self, Config};
+
+// the messages the editor shows, by id, in English. A locale file in the
+// locales directory, e.g. `de.toml`, gives some or all of them in another
+// language as `id = "text"`, and may hold ids of its own for anything
+// else that shows text.
+const ENGLISH: [(&str, &str); 239] = [
+    ("help", "HELP: {find} = find | {save} = save | {quit} = quit"),
+    ("goodbye", "Goodbye."),
+    ("welcome", "Byron's Code Editor -- version {version}"),
+    ("no_name", "[No_Name]"),
+    ("error", "ERR: {error}"),
+    ("unknown_theme", "ERR: Unknown theme {theme}"),
+    ("no_snapshot", "ERR: No recovery snapshot {number}"),
+    ("bad_keys", "ERR: --keys: {error}"),
+    ("batch_prompt", "ERR: --batch: would have asked {question}"),
+    ("cannot_open_file", "ERR: Could not open file: {file}"),
+    ("cannot_load_themes", "ERR: Could not load themes: {error}"),
+    ("commit_message", "Lines starting with # are left out. Quit without saving your changes to abort."),
+    ("line_truncated", "{marker} line truncated"),
+    ("slow_terminal", "Slow terminal, drawing without highlighting."),
+    ("status_read_only", " [read-only]"),
+    ("status_modified", " (modified)"),
+    ("status_changed_on_disk", " (modified, changed on disk)"),
+    ("status_slow", " [slow terminal]"),
+    ("status_loading", " [loading {percent}%]"),
+    ("status_indexing", " [indexing, {count} files]"),
+    ("status_lines", " - {count} lines"),
+    ("status_counts", ", {words} words, {characters} characters"),
+    ("status_selected", "{lines} lines, {characters} characters, {words} words selected"),
+    ("status_pane_top", "[top] "),
+    ("status_pane_bottom", "[bottom] "),
+    ("status_pane_left", "[left] "),
+    ("status_pane_right", "[right] "),
+    ("mode_move_rows", "-- MOVE ROWS -- "),
+    ("mode_insert", "-- INSERT -- "),
+    ("mode_visual", "-- VISUAL -- "),
+    ("mode_normal", "-- NORMAL -- "),
+    ("redact_on", "Masking lines that look like they hold secrets."),
+    ("redact_off", "Showing all lines."),
+    ("line_endings", "Line endings are {ending} from the next save."),
+    ("no_file_yet", "The buffer has no file yet."),
+    ("warnings_on", "Invisible character warnings on."),
+    ("warnings_off", "Invisible character warnings off."),
+    ("invisibles_on", "Showing tabs and trailing spaces."),
+    ("invisibles_off", "Not showing tabs and trailing spaces."),
+    ("wrap_on", "Line wrapping on."),
+    ("wrap_off", "Line wrapping off."),
+    ("nothing_selected", "Nothing selected."),
+    ("selection_bytes", "Selection is {bytes} bytes."),
+    ("edit_read_only", "File is read-only, edit anyway? (y/n): "),
+    ("read_only", "File is read-only."),
+    ("search_prompt", "Search (/regex): "),
+    ("no_search", "No search yet, {key} starts one."),
+    ("not_found", "Not found :{query}."),
+    ("wrapped_to_top", "Search wrapped to the top."),
+    ("wrapped_to_bottom", "Search wrapped to the bottom."),
+    ("invalid_pattern", "Invalid pattern: {error}"),
+    ("replace_prompt", "Replace (regex): "),
+    ("replace_with_prompt", "Replace with: "),
+    ("replace_query", "Replace with {replacement}? [y]es, [n]o, [a]ll, all [b]uffers, [q]uit: "),
+    ("replaced_all", "Replaced {count} occurrences."),
+    ("replaced_in_buffers", "Replaced {count} occurrences in {buffers} buffers: {counts}"),
+    ("buffer_count", "{file} {count}"),
+    ("buffer_read_only", "{file} read-only, left as it is"),
+    ("insert_file_prompt", "Insert file (or !command): "),
+    ("command_failed", "Command failed: {status}. {error}"),
+    ("cannot_run_command", "Could not run command: {error}"),
+    ("cannot_read", "Could not read {file}: {error}"),
+    ("inserted_lines", "Inserted {count} lines."),
+    ("open_prompt", "Open: "),
+    ("cannot_open", "Could not open {file}: {error}"),
+    ("opened", "Opened {file}."),
+    ("close_dirty", "Buffer has unsaved changes, close anyway? (y/n): "),
+    ("closed", "Closed {file}."),
+    ("no_closed_buffers", "No closed buffers."),
+    ("reopened", "Reopened {file}."),
+    ("no_locations", "No file:line locations in the buffer or the clipboard."),
+    ("no_locations_yet", "No locations, {key} finds them."),
+    ("location", "Location {number} of {count}: {file}:{line}:{column}"),
+    ("no_other_buffers", "No other buffers open."),
+    ("switched_to", "Switched to {file}."),
+    ("save_session_prompt", "Save session as: "),
+    ("saved_session", "Saved session {name}."),
+    ("no_sessions", "No sessions saved for this project."),
+    ("load_session_prompt", "Load session ({names}): "),
+    ("session_unopened", "None of the files of session {name} could be opened."),
+    ("loaded_session", "Loaded session {name}."),
+    ("insert_variable_prompt", "Insert ({names}, or a ${{template}}): "),
+    ("no_value", "No value for {name}."),
+    ("nothing_to_undo", "Nothing to undo."),
+    ("nothing_to_redo", "Nothing to redo."),
+    ("undo_state", "Went to state {state} of the undo tree."),
+    ("no_undo_state", "There is no state {state} in the undo tree."),
+    ("undo_tree", "Undo states of {file}, > marks the one it is in"),
+    ("undo_root", "0 as opened"),
+    ("undo_node", "{state} from {parent}, {age}"),
+    ("undo_tree_opened", "Enter on a state goes to it, {key} closes the tree."),
+    ("undo_tree_gone", "The buffer of these undo states is closed."),
+    ("no_character", "No character under the cursor."),
+    ("set_mark_prompt", "Set mark: "),
+    ("go_to_mark_prompt", "Go to mark: "),
+    ("mark_set", "Mark {name} set."),
+    ("mark_not_letter", "Marks are named by a letter."),
+    ("no_mark", "No mark {name}."),
+    ("quit_dirty", "You will loose unsaved changes, enter to quit? esc to continue."),
+    ("save_as_prompt", "Save As: "),
+    ("save_aborted", "Save aborted."),
+    ("create_directory", "Directory {directory} does not exist, create it? (y/n): "),
+    ("cannot_create_directory", "Error creating {directory}: {error}"),
+    ("save_incomplete", "The file isn't fully loaded, saving now would cut it short."),
+    ("saved", "File saved successfully."),
+    ("saved_cleaned", "File saved, {count} lines cleaned up."),
+    ("saved_swap_left", "File saved, but its swap file is left: {error}"),
+    ("saved_with_violations", "File saved, but it has {violations}."),
+    ("cannot_write", "Error writing file: {error}"),
+    ("cannot_read_rest", "ERR: Could not read the rest of the file, showing the first {count} lines: {error}"),
+    ("changed_on_disk", "WARNING! File changed on disk, saving will overwrite it."),
+    ("reloaded", "File changed on disk, reloaded."),
+    ("buffer_changed_on_disk", "WARNING! {file} changed on disk, saving it will overwrite it."),
+    ("buffer_reloaded", "{file} changed on disk, reloaded its buffer."),
+    ("cannot_watch", "ERR: Cannot watch the open files, {error}. Looking at them every half second instead."),
+    ("cannot_snapshot", "ERR: Could not write recovery snapshot: {error}"),
+    ("cannot_write_swap", "ERR: Could not write swap file: {error}"),
+    ("found_swap", "Found {file}, recover its unsaved changes? (y/n, Esc to leave it): "),
+    ("recovered", "Recovered the unsaved changes, save to keep them."),
+    ("cannot_recover", "ERR: Could not recover: {error}"),
+    ("cannot_delete_swap", "ERR: Could not delete swap file: {error}"),
+    ("set", "Set {setting}."),
+    ("set_local", "Set {setting} for this buffer."),
+    ("no_paragraph", "No paragraph to reflow here."),
+    ("sorted", "Sorted {count} lines."),
+    ("sorted_already", "The lines are sorted already."),
+    ("deduped", "Removed {count} duplicate lines."),
+    ("no_duplicates", "No duplicate lines."),
+    ("theme_prompt", "Theme: "),
+    ("switched_theme", "Switched to the {name} theme."),
+    ("no_theme", "No theme {name}, there are: {names}"),
+    ("ui_theme_prompt", "UI theme: "),
+    ("switched_ui_theme", "Switched to the {name} UI theme."),
+    ("no_ui_theme", "{error} There are: {names}"),
+    ("line_numbers_on", "Line numbers on."),
+    ("relative_line_numbers_on", "Relative line numbers on."),
+    ("line_numbers_off", "Line numbers off."),
+    ("split_horizontally", "Split horizontally."),
+    ("split_vertically", "Split vertically."),
+    ("closed_split", "Closed the split."),
+    ("announce_line", "Line {number}: {text}"),
+    ("no_help", "No help on {topic}."),
+    ("no_matching_bracket", "No matching bracket."),
+    ("no_comments", "No comment syntax known for this file."),
+    ("file_tree_help", "Enter opens, n makes a file, r renames, d deletes, u undeletes, Esc goes back to the text."),
+    ("file_tree_new_prompt", "New file, ending in / for a directory: "),
+    ("file_tree_rename_prompt", "Rename to: "),
+    ("file_tree_delete", "Move {file} to the trash? (y/n): "),
+    ("file_tree_created", "Created {file}."),
+    ("file_tree_renamed", "Renamed {old} to {new}."),
+    ("file_tree_deleted", "Moved {file} to the trash, u takes it back out."),
+    ("file_tree_restored", "Restored {file}."),
+    ("file_tree_nothing_deleted", "Nothing deleted to restore."),
+    ("file_tree_no_trash", "No trash directory, set trash_dir or HOME."),
+    ("no_snippet_name", "Type a snippet's name, then expand it."),
+    ("no_snippet", "No snippet named {name}."),
+    ("snippet_fields", "Tab goes to the next field, Shift-Tab back, Esc leaves the snippet."),
+    ("no_word", "There is no word at the cursor."),
+    ("indexing_tags", "No tags file, so the files are being indexed for their declarations: {count} so far."),
+    ("no_tag", "No tag named {name}."),
+    ("search_tags_prompt", "Search tags: "),
+    ("no_tags_matching", "No tags match {query}."),
+    ("tags_listed", "{count} tags for {query}"),
+    ("tag", "At {file}:{line}: {text}"),
+    ("no_jumps", "There is nowhere to go back to."),
+    ("jumped_back", "Back at {file}:{line}."),
+    ("outline_help", "Enter goes to the declaration, Esc goes back to the text."),
+    ("no_symbols", "No declarations found in this file."),
+    ("checking", "Running cargo check..."),
+    ("check_running", "cargo check is still running."),
+    ("check_failed", "ERR: cargo check: {error}"),
+    ("check_clean", "cargo check found nothing."),
+    ("checked", "cargo check found {errors} errors and {warnings} warnings in {files} files, {key} goes to the next."),
+    ("no_diagnostics", "Nothing found here, {key} runs cargo check."),
+    ("diagnostic", "{number} of {count}, {severity}: {message}"),
+    ("diagnostic_here", "{severity}: {message}"),
+    ("severity_error", "error"),
+    ("severity_warning", "warning"),
+    ("severity_note", "note"),
+    ("project_search_prompt", "Search files (/regex): "),
+    ("project_results", "{count} matching lines in {files} files for {query}"),
+    ("project_results_capped", "The first {count} matching lines, in {files} files, for {query}"),
+    ("project_results_opened", "Enter on a line opens it, {next} goes to the next, {save} writes edited lines back."),
+    ("write_back_confirm", "Write {count} edited lines back to {files} files? (y/n): "),
+    ("written_back", "Wrote {count} edited lines back to {files} files."),
+    ("written_back_stale", "{count} changed in their files since they were listed and were left alone."),
+    ("written_back_unsaved", "Left unsaved in {files}, which had edits of their own."),
+    ("project_no_matches", "No matches for {query} in the files here."),
+    ("no_dictionary", "ERR: {error} Spell checking needs a dictionary, see spell_dictionary."),
+    ("not_misspelled", "No misspelled word at the cursor."),
+    ("no_suggestions", "No suggestions for {word}."),
+    ("suggestion", "Suggestion {number} of {count} for {word}, again for the next."),
+    ("suggestion_original", "Back to {word}, as it was."),
+    ("help_opened", "The help, for the keys as they are bound now. {key} closes it."),
+    ("no_snapshots", "No recovery snapshots."),
+    ("recover_hint", "Open one with --recover <number>, then save to restore it."),
+    ("seconds_ago", "{count} seconds ago"),
+    ("minutes_ago", "{count} minutes ago"),
+    ("hours_ago", "{count} hours ago"),
+    ("days_ago", "{count} days ago"),
+    ("announce_suspicious", "Line {number}, has invisible or lookalike characters: {text}"),
+    ("needs_line_number", "{name} needs a line number."),
+    ("needs_setting", "{name} needs a setting."),
+    ("needs_theme_name", "{name} needs a theme name."),
+    ("needs_state_number", "{name} needs a state number."),
+    ("sort_argument", "{name} takes r to reverse or n to sort by number."),
+    ("not_a_command", "Not a command: {name}"),
+    ("cannot_read_config", "ERR: Could not read {file}: {error}"),
+    ("config_error", "ERR: {file}: {error}"),
+    ("unknown_config_key", "unknown key \"{key}\""),
+    ("invalid_secret_pattern", "invalid secret pattern \"{pattern}\": {error}"),
+    ("not_a_number", "{value} isn't a number."),
+    ("no_number_setting", "No number setting {name}."),
+    ("no_setting", "No setting {name}."),
+    ("unclosed_key", "A < with no > after it, write <lt> for the key itself."),
+    ("unknown_key_name", "Unknown key <{name}>."),
+    ("no_session", "No session named {name}."),
+    ("no_last_session", "No session was saved on exit."),
+    ("bad_session_name", "\"{name}\" can't be used as a session name."),
+    ("cannot_write_file", "Could not write {file}: {error}"),
+    ("no_home", "HOME is not set."),
+    ("no_dictionary_found", "No dictionary {name} found."),
+    ("no_tags_file", "No tags file here or above, ctags -R makes one."),
+    ("cannot_run_cargo", "Could not run cargo: {error}"),
+    ("cargo_check_failed", "cargo check failed"),
+    ("no_cargo_toml", "No Cargo.toml here or above."),
+    ("unknown_ui_theme", "No UI theme named {name}."),
+    ("tutorial_done", "Every exercise is done, that's the tutorial!"),
+    ("exercise_done", "Exercise {number} done, {left} to go."),
+    ("tutorial_typing_modal", "Press i to type, and Esc to go back to Normal mode when you are done."),
+    ("tutorial_typing", "Just start typing, there is no mode to switch to."),
+    ("rule_tabs", "tabs"),
+    ("rule_trailing_whitespace", "trailing whitespace"),
+    ("violation", "{rule} on line {line}"),
+    ("list_and", " and "),
+    ("utf16_odd_length", "UTF-16 text with an odd number of bytes"),
+    ("not_latin1", "{character} can't be written in Latin-1"),
+    ("nothing_selected", "nothing selected"),
+    ("input_closed", "input closed"),
+];
+
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+// the message `id` in the user's language, with each `{name}` filled in by
+// `name = value`, like `message!("opened", file = file_name)`
+macro_rules! message {
+    ($id:expr) => {
+        $crate::messages::get($id, &[])
+    };
+    ($id:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::messages::get($id, &[$((stringify!($name), $value.to_string())),+])
+    };
+}
+pub(crate) use message;
+
+// picks the language, from `locale` in the config or else the environment,
+// telling what went wrong reading its file. English needs no file.
+pub fn init(config: &Config) -> Option<String> {
+    let mut catalog: HashMap<String, String> = ENGLISH
+        .iter()
+        .map(|&(id, text)| (id.to_string(), text.to_string()))
+        .collect();
+    let mut error = None;
+    if let Some(path) = locale(config).and_then(|locale| {
+        let dir = config::locales_dir()?;
+        // `pt_BR` may only be there as `pt`
+        let language = locale.split('_').next().unwrap_or_default().to_string();
+        [locale, language]
+            .into_iter()
+            .map(|name| dir.join(format!("{name}.toml")))
+            .find(|path| path.is_file())
+    }) {
+        let read = fs::read_to_string(&path).map_err(|error| error.to_string());
+        match read.and_then(|text| {
+            toml::from_str::<HashMap<String, String>>(&text)
+                .map_err(|error| error.message().to_string())
+        }) {
+            Ok(messages) => catalog.extend(messages),
+            Err(message) => error = Some(format!("ERR: {}: {message}", path.display())),
+        }
+    }
+    CATALOG.set(catalog).ok();
+    error
+}
+
+// like `de` or `pt_BR`, None for English
+fn locale(config: &Config) -> Option<String> {
+    let value = if config.locale.is_empty() {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()))?
+    } else {
+        config.locale.clone()
+    };
+    // `de_DE.UTF-8` and `ca_ES@valencia` name a language, then how it is
+    // written
+    let locale = value.split(['.', '@']).next().unwrap_or_default();
+    if matches!(locale, "" | "C" | "POSIX" | "en") || locale.starts_with("en_") {
+        return None;
+    }
+    Some(locale.to_string())
+}
+
+pub fn get(id: &str, values: &[(&str, String)]) -> String {
+    let text = CATALOG
+        .get()
+        .and_then(|catalog| catalog.get(id).map(String::as_str))
+        .or_else(|| {
+            ENGLISH
+                .iter()
+                .find(|(english, _)| *english == id)
+                .map(|(_, text)| *text)
+        })
+        .unwrap_or(id);
+    fill(text, values)
+}
+
+// `{name}` in `text` replaced by its value, `{{` and `}}` being the braces
+// themselves. Names that have no value are left as they are.
+fn fill(text: &str, values: &[(&str, String)]) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(['{', '}']) {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if rest[start..].starts_with("{{") || rest[start..].starts_with("}}") {
+            filled.push_str(&rest[start..=start]);
+            rest = &after[1..];
+            continue;
+        }
+        let value = after.split_once('}').and_then(|(name, tail)| {
+            let (_, value) = values.iter().find(|(known, _)| *known == name)?;
+            Some((value, tail))
+        });
+        if let Some((value, tail)) = value {
+            filled.push_str(value);
+            rest = tail;
+        } else {
+            filled.push_str(&rest[start..=start]);
+            rest = after;
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
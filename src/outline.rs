@@ -0,0 +1,72 @@
+use crate::symbols::{self, Symbol};
+use crate::Document;
+
+// the declarations of the current buffer, nested ones indented, for the
+// panel beside the text
+#[derive(Default)]
+pub struct Outline {
+    symbols: Vec<Symbol>,
+    pub selected: usize,
+    // the first symbol on screen
+    pub offset: usize,
+    // keys go to the outline rather than the text
+    pub focused: bool,
+    // the document changed, or another one is showing, since it was made
+    pub stale: bool,
+}
+
+impl Outline {
+    pub fn new() -> Self {
+        Self {
+            stale: true,
+            ..Self::default()
+        }
+    }
+    pub fn refresh(&mut self, document: &Document, language: &str) {
+        self.symbols = symbols::outline(document, language);
+        self.selected = self.selected.min(self.symbols.len().saturating_sub(1));
+        self.stale = false;
+    }
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+    pub fn selected(&self) -> Option<&Symbol> {
+        self.symbols.get(self.selected)
+    }
+    // up or down by `count`, kept to the symbols there are
+    pub fn select(&mut self, down: bool, count: usize) {
+        self.selected = if down {
+            self.selected
+                .saturating_add(count)
+                .min(self.symbols.len().saturating_sub(1))
+        } else {
+            self.selected.saturating_sub(count)
+        };
+    }
+    // selects the symbol row `y` is in, the last declared at or above it
+    pub fn follow(&mut self, y: usize) {
+        if let Some(index) = self.symbols.iter().rposition(|symbol| symbol.y <= y) {
+            self.selected = index;
+        }
+    }
+    // keeps the selection among the `height` symbols on screen
+    pub fn scroll(&mut self, height: usize) {
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset.saturating_add(height) {
+            self.offset = self.selected.saturating_add(1).saturating_sub(height);
+        }
+    }
+    // the `height` lines on screen, each cut or padded to `width` characters
+    pub fn lines(&self, width: usize, height: usize) -> Vec<String> {
+        (self.offset..self.offset.saturating_add(height))
+            .map(|index| {
+                let text = self.symbols.get(index).map_or_else(String::new, |symbol| {
+                    format!("{}{}", "  ".repeat(symbol.depth), symbol.label)
+                });
+                let text: String = text.chars().take(width).collect();
+                format!("{text:<width$}")
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,70 @@
+use termion::color::Rgb;
+
+// the levels of the 6x6x6 color cube in the 256-color palette
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+// the 16 colors as xterm draws them: black, red, green, yellow, blue,
+// magenta, cyan, white, then the bright ones in the same order
+const ANSI: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+// the closest of the 256-color palette's cube and gray ramp, which are the
+// same on every terminal unlike its first 16 entries
+pub fn nearest_256(color: Rgb) -> u8 {
+    let Rgb(r, g, b) = color;
+    let (ri, gi, bi) = (cube_index(r), cube_index(g), cube_index(b));
+    let cube = (CUBE[ri as usize], CUBE[gi as usize], CUBE[bi as usize]);
+    // the ramp runs from 8 to 238 in steps of 10
+    let average = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+    let step = u8::try_from(average.saturating_sub(3) / 10)
+        .unwrap_or(23)
+        .min(23);
+    let level = step.saturating_mul(10).saturating_add(8);
+    if distance(color, (level, level, level)) < distance(color, cube) {
+        step.saturating_add(232)
+    } else {
+        16 + 36 * ri + 6 * gi + bi
+    }
+}
+
+pub fn nearest_16(color: Rgb) -> u8 {
+    let mut nearest: u8 = 0;
+    let mut best = u32::MAX;
+    for (index, &entry) in (0..).zip(ANSI.iter()) {
+        let distance = distance(color, entry);
+        if distance < best {
+            best = distance;
+            nearest = index;
+        }
+    }
+    nearest
+}
+
+fn cube_index(value: u8) -> u8 {
+    match value {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (value - 35) / 40,
+    }
+}
+
+fn distance(color: Rgb, (r, g, b): (u8, u8, u8)) -> u32 {
+    let Rgb(cr, cg, cb) = color;
+    let square = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+    square(cr, r) + square(cg, g) + square(cb, b)
+}
@@ -0,0 +1,97 @@
+use crate::Position;
+
+// how the screen is divided: `Horizontal` stacks the panes, `Vertical` puts
+// them side by side
+#[derive(Clone, Copy)]
+pub enum Direction {
+    Horizontal,
+    Vertical,
+}
+
+// the view state of the pane that doesn't have focus. Both panes show the
+// same document, each with its own cursor, scroll and selection.
+#[derive(Clone, Copy)]
+pub struct Pane {
+    pub cursor_position: Position,
+    pub offset: Position,
+    pub selection_anchor: Option<Position>,
+}
+
+impl Pane {
+    // from the anchor to the cursor, whichever comes first first
+    pub fn selection(&self) -> Option<(Position, Position)> {
+        let anchor = self.selection_anchor?;
+        let cursor = self.cursor_position;
+        if (anchor.y, anchor.x) == (cursor.y, cursor.x) {
+            return None;
+        }
+        if (anchor.y, anchor.x) < (cursor.y, cursor.x) {
+            Some((anchor, cursor))
+        } else {
+            Some((cursor, anchor))
+        }
+    }
+}
+
+pub struct Split {
+    pub direction: Direction,
+    pub other: Pane,
+    // whether the focused pane is the bottom or right one
+    pub second_focused: bool,
+}
+
+// the part of the screen a pane is drawn in
+#[derive(Clone, Copy)]
+pub struct Area {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Area {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x..self.x.saturating_add(self.width)).contains(&x)
+            && (self.y..self.y.saturating_add(self.height)).contains(&y)
+    }
+}
+
+// the areas of the two panes, leaving a line between them for the divider
+pub fn layout(direction: Direction, width: usize, height: usize) -> (Area, Area) {
+    match direction {
+        Direction::Horizontal => {
+            let first = height.saturating_sub(1) / 2;
+            (
+                Area {
+                    x: 0,
+                    y: 0,
+                    width,
+                    height: first,
+                },
+                Area {
+                    x: 0,
+                    y: first.saturating_add(1),
+                    width,
+                    height: height.saturating_sub(first).saturating_sub(1),
+                },
+            )
+        }
+        Direction::Vertical => {
+            let first = width.saturating_sub(1) / 2;
+            (
+                Area {
+                    x: 0,
+                    y: 0,
+                    width: first,
+                    height,
+                },
+                Area {
+                    x: first.saturating_add(1),
+                    y: 0,
+                    width: width.saturating_sub(first).saturating_sub(1),
+                    height,
+                },
+            )
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+// `path` as `~/...` when it is in the home directory
+pub fn home_relative(path: &str) -> String {
+    let Some(home) = env::var_os("HOME").map(PathBuf::from) else {
+        return path.to_string();
+    };
+    match Path::new(path).strip_prefix(&home) {
+        Ok(rest) if !home.as_os_str().is_empty() => {
+            Path::new("~").join(rest).to_string_lossy().into_owned()
+        }
+        _ => path.to_string(),
+    }
+}
+
+// the absolute path, for when the short one is not enough
+pub fn full(path: &str) -> String {
+    env::current_dir()
+        .map_or_else(|_| PathBuf::from(path), |dir| dir.join(path))
+        .to_string_lossy()
+        .into_owned()
+}
+
+// shortens `path` to fit in `width` characters: the directories are cut to
+// their first letter from the outermost in, as in `~/p/r/src/editor.rs`,
+// and what still doesn't fit is cut out of the middle, leaving `ellipsis`
+pub fn abbreviate(path: &str, width: usize, ellipsis: &str) -> String {
+    let mut parts: Vec<String> = path.split('/').map(str::to_string).collect();
+    let directories = parts.len().saturating_sub(1);
+    for index in 0..directories {
+        if width_of(&parts.join("/")) <= width {
+            break;
+        }
+        parts[index] = initial(&parts[index]);
+    }
+    ellipsize_middle(&parts.join("/"), width, ellipsis)
+}
+
+// like cutting the end of a text, but keeps both ends, which say the most
+// about a path
+fn ellipsize_middle(text: &str, width: usize, ellipsis: &str) -> String {
+    let len = width_of(text);
+    if len <= width {
+        return text.to_string();
+    }
+    if width < width_of(ellipsis) {
+        return String::new();
+    }
+    let keep = width.saturating_sub(width_of(ellipsis));
+    let tail = keep / 2;
+    let head = keep.saturating_sub(tail);
+    let mut cut: String = text.chars().take(head).collect();
+    cut.push_str(ellipsis);
+    cut.extend(text.chars().skip(len.saturating_sub(tail)));
+    cut
+}
+
+// the first letter of a directory, along with the dot of a hidden one. `~`,
+// `.` and `..` are already as short as they get.
+fn initial(directory: &str) -> String {
+    if matches!(directory, "~" | "." | "..") {
+        return directory.to_string();
+    }
+    let hidden = directory.starts_with('.');
+    directory.chars().take(if hidden { 2 } else { 1 }).collect()
+}
+
+fn width_of(text: &str) -> usize {
+    text.chars().count()
+}
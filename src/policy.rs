@@ -0,0 +1,158 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::messages::message;
+use crate::project_search::Filter;
+use crate::{Document, Position};
+
+// looked for in the file's directory and the ones above it
+const FILE_NAME: &str = ".byron-editor.toml";
+
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    // the one most lines of `text` end with, Lf for text without any
+    pub fn detect(text: &str) -> Self {
+        let lines = text.matches('\n').count();
+        let crlf = text.matches("\r\n").count();
+        if crlf > lines.saturating_sub(crlf) {
+            Self::Crlf
+        } else {
+            Self::Lf
+        }
+    }
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::Crlf => "CRLF",
+        }
+    }
+}
+
+// what saving does about text that breaks a rule
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rule {
+    #[default]
+    Allow,
+    // saves as is and says where
+    Report,
+    Fix,
+}
+
+// how the files of a project are saved, from its .byron-editor.toml
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    // each file keeps the line ending it has unless this is set
+    pub line_ending: Option<LineEnding>,
+    // like line_ending, files keep theirs unless this is set
+    pub final_newline: Option<bool>,
+    // fixing replaces each tab with `tab_width` spaces
+    pub tabs: Rule,
+    pub trailing_whitespace: Rule,
+    // .gitignore-style globs from the project's directory: project search
+    // and the file tree leave out what exclude matches and, where include
+    // has any, the files it doesn't
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            line_ending: None,
+            final_newline: None,
+            tabs: Rule::Allow,
+            trailing_whitespace: Rule::Allow,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl Policy {
+    // the nearest project's policy, or the defaults outside of any project
+    pub fn for_file(file_name: &str) -> Result<Self, String> {
+        let path =
+            env::current_dir().map_or_else(|_| PathBuf::from(file_name), |dir| dir.join(file_name));
+        Ok(Self::nearest(path.parent().unwrap_or(&path))?
+            .map_or_else(Self::default, |(_, policy)| policy))
+    }
+    // the nearest project's include and exclude globs, for paths from `dir`
+    pub fn filter(dir: &Path) -> Result<Filter, String> {
+        let Some((project, policy)) = Self::nearest(dir)? else {
+            return Ok(Filter::default());
+        };
+        let prefix = dir.strip_prefix(&project).unwrap_or(Path::new(""));
+        Ok(Filter::new(
+            &prefix.to_string_lossy(),
+            &policy.include,
+            &policy.exclude,
+        ))
+    }
+    // the policy of `dir` or the nearest directory above it that has one,
+    // with that directory
+    fn nearest(dir: &Path) -> Result<Option<(PathBuf, Self)>, String> {
+        for dir in dir.ancestors() {
+            let candidate = dir.join(FILE_NAME);
+            let Ok(text) = fs::read_to_string(&candidate) else {
+                continue;
+            };
+            return toml::from_str(&text)
+                .map(|policy| Some((dir.to_path_buf(), policy)))
+                .map_err(|error| format!("{}: {}", candidate.display(), error.message()));
+        }
+        Ok(None)
+    }
+    // fixes what the rules say to fix, each rule as one undoable step, and
+    // describes where the text breaks the rules that are only reported
+    pub fn apply(&self, document: &mut Document, tab_width: usize) -> Vec<String> {
+        let mut violations = Vec::new();
+        let rules = [
+            (self.tabs, r"\t", " ".repeat(tab_width), "rule_tabs"),
+            (
+                self.trailing_whitespace,
+                r"[ \t]+$",
+                String::new(),
+                "rule_trailing_whitespace",
+            ),
+        ];
+        for (rule, pattern, fix, id) in rules {
+            let regex = Regex::new(pattern).expect("invalid policy pattern");
+            let Some(found) = document.find_regex(&regex, &Position::default()) else {
+                continue;
+            };
+            match rule {
+                Rule::Allow => (),
+                Rule::Report => {
+                    violations.push(message!(
+                        "violation",
+                        rule = message!(id),
+                        line = found.y.saturating_add(1)
+                    ));
+                }
+                Rule::Fix => {
+                    document.replace_all(&regex, &fix);
+                }
+            }
+        }
+        violations
+    }
+}
@@ -0,0 +1,535 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::quickfix::Location;
+
+// a search stops after this many matching lines
+pub const MAX_HITS: usize = 10_000;
+// files bigger than this are passed over, as they are rarely source
+const MAX_FILE_SIZE: u64 = 8 * 1024 * 1024;
+// a NUL in this much of the start of a file makes it binary
+const BINARY_SAMPLE: usize = 8_000;
+
+// a line of a file that matches, with where the first match on it starts
+pub struct Hit {
+    pub location: Location,
+    pub text: String,
+}
+
+// the files that matched and their lines, in the order the files are
+// named, and whether it stopped at MAX_HITS
+pub struct Results {
+    pub hits: Vec<Hit>,
+    pub files: usize,
+    pub capped: bool,
+}
+
+impl Results {
+    // a line a hit, as `path:line:column: text`, which Locations reads
+    pub fn listing(&self) -> Vec<String> {
+        self.hits
+            .iter()
+            .map(|hit| {
+                let Location {
+                    file_name,
+                    line,
+                    column,
+                } = &hit.location;
+                format!("{file_name}:{line}:{column}: {}", hit.text.trim())
+            })
+            .collect()
+    }
+}
+
+// a line of a results listing edited there, to be written over the line of
+// the file it came from. The texts are trimmed, as the listing has them.
+pub struct Edit {
+    pub location: Location,
+    pub listed: String,
+    pub edited: String,
+}
+
+// the lines of `rows`, a listing as edited, whose text is not what the same
+// place's was in `listed`, the listing as made. Lines that were taken out, or
+// whose place was edited, are left as they are in their files.
+pub fn edits(listed: &[String], rows: &[String]) -> Vec<Edit> {
+    let listed: Vec<(Location, &str)> = listed.iter().filter_map(|row| listed_line(row)).collect();
+    rows.iter()
+        .filter_map(|row| {
+            let (location, edited) = listed_line(row)?;
+            let (_, text) = listed.iter().find(|(place, _)| *place == location)?;
+            (*text != edited.trim()).then(|| Edit {
+                location,
+                listed: (*text).to_string(),
+                edited: edited.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+// `line` of a file with the edit's text in place of the listed one, keeping
+// its indent, None when the line isn't the one listed any more
+pub fn apply(edit: &Edit, line: &str) -> Option<String> {
+    if line.trim() != edit.listed {
+        return None;
+    }
+    let indent = &line[..line.len().saturating_sub(line.trim_start().len())];
+    Some(format!("{indent}{}", edit.edited))
+}
+
+// the place and text of a `path:line:column: text` line
+fn listed_line(row: &str) -> Option<(Location, &str)> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN
+        .get_or_init(|| Regex::new(r"^(.+?):(\d+):(\d+): ?").expect("invalid listing pattern"));
+    let captures = pattern.captures(row)?;
+    let location = Location {
+        file_name: captures[1].to_string(),
+        line: captures[2].parse().ok()?,
+        column: captures[3].parse().ok()?,
+    };
+    Some((location, &row[captures[0].len()..]))
+}
+
+// a .gitignore line: what it matches, relative to the directory the file
+// is in, and whether it is a `!` one taking a match back
+#[derive(Clone)]
+struct Rule {
+    pattern: Regex,
+    negated: bool,
+    directories_only: bool,
+}
+
+// the rules of the .gitignore files from the search's directory down to the
+// one being walked, each with the path of its directory from there
+type Rules = Vec<(String, Vec<Rule>)>;
+
+// a project's include and exclude globs, .gitignore lines taken from the
+// project's directory, which is `prefix` above the one walked
+#[derive(Clone, Default)]
+pub struct Filter {
+    prefix: String,
+    include: Rules,
+    exclude: Rules,
+}
+
+impl Filter {
+    pub fn new(prefix: &str, include: &[String], exclude: &[String]) -> Self {
+        let rules = |globs: &[String]| -> Rules {
+            let rules = parse(&globs.join("\n"));
+            if rules.is_empty() {
+                Vec::new()
+            } else {
+                vec![(String::new(), rules)]
+            }
+        };
+        Self {
+            prefix: prefix.trim_matches('/').to_string(),
+            include: rules(include),
+            exclude: rules(exclude),
+        }
+    }
+    // whether `path`, from the directory walked, is left out: what exclude
+    // matches, and files include doesn't where it has any. Directories are
+    // only left out by exclude, so the files include wants are found in them.
+    pub fn excludes(&self, path: &str, directory: bool) -> bool {
+        let path = if self.prefix.is_empty() {
+            path.to_string()
+        } else {
+            format!("{}/{path}", self.prefix)
+        };
+        ignored(&self.exclude, &path, directory)
+            || (!directory && !self.include.is_empty() && !ignored(&self.include, &path, false))
+    }
+}
+
+// the lines of the files under `root` that `regex` matches, leaving out
+// what .gitignore files ignore, what `filter` leaves out and the .git
+// directory
+pub fn search(root: &Path, regex: &Regex, filter: &Filter) -> io::Result<Results> {
+    let mut results = Results {
+        hits: Vec::new(),
+        files: 0,
+        capped: false,
+    };
+    walk(root, "", &mut Vec::new(), filter, &mut |path, name| {
+        search_file(path, name, regex, &mut results);
+        !results.capped
+    })?;
+    Ok(results)
+}
+
+// the same for files already found under `root`, as the index has them
+pub fn search_files(root: &Path, files: &[String], regex: &Regex) -> Results {
+    let mut results = Results {
+        hits: Vec::new(),
+        files: 0,
+        capped: false,
+    };
+    for name in files {
+        if results.capped {
+            break;
+        }
+        search_file(&root.join(name), name, regex, &mut results);
+    }
+    results
+}
+
+// hands `visit` each file `search` would look in, by its path and its name
+// from `root`, until it returns false
+pub fn files(
+    root: &Path,
+    filter: &Filter,
+    visit: &mut dyn FnMut(&Path, &str) -> bool,
+) -> io::Result<()> {
+    walk(root, "", &mut Vec::new(), filter, visit).map(drop)
+}
+
+// whether the walk from `root` passes over `name`, a path from it, or a
+// directory on the way to it
+pub fn passes_over(root: &Path, name: &str, filter: &Filter) -> bool {
+    let mut rules = Rules::new();
+    let mut relative = String::new();
+    let parts: Vec<&str> = name.split('/').collect();
+    for (index, part) in parts.iter().enumerate() {
+        if let Ok(text) = fs::read_to_string(root.join(&relative).join(".gitignore")) {
+            rules.push((relative.clone(), parse(&text)));
+        }
+        let path = if relative.is_empty() {
+            (*part).to_string()
+        } else {
+            format!("{relative}/{part}")
+        };
+        let directory = index.saturating_add(1) < parts.len();
+        if *part == ".git" || ignored(&rules, &path, directory) || filter.excludes(&path, directory)
+        {
+            return true;
+        }
+        relative = path;
+    }
+    false
+}
+
+// false once `visit` said to stop
+fn walk(
+    dir: &Path,
+    relative: &str,
+    rules: &mut Rules,
+    filter: &Filter,
+    visit: &mut dyn FnMut(&Path, &str) -> bool,
+) -> io::Result<bool> {
+    let own =
+        fs::read_to_string(dir.join(".gitignore")).map_or_else(|_| Vec::new(), |text| parse(&text));
+    let pushed = !own.is_empty();
+    if pushed {
+        rules.push((relative.to_string(), own));
+    }
+    let mut entries: Vec<_> = fs::read_dir(dir)?.filter_map(Result::ok).collect();
+    entries.sort_by_key(fs::DirEntry::file_name);
+    let mut going = true;
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = if relative.is_empty() {
+            name.clone()
+        } else {
+            format!("{relative}/{name}")
+        };
+        // links are left alone, so a loop of them isn't walked forever
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if name == ".git"
+            || kind.is_symlink()
+            || ignored(rules, &path, kind.is_dir())
+            || filter.excludes(&path, kind.is_dir())
+        {
+            continue;
+        }
+        if kind.is_dir() {
+            // a directory that can't be read is passed over like a file
+            going = walk(&entry.path(), &path, rules, filter, visit).unwrap_or(true);
+        } else if kind.is_file() {
+            going = visit(&entry.path(), &path);
+        }
+        if !going {
+            break;
+        }
+    }
+    if pushed {
+        rules.pop();
+    }
+    Ok(going)
+}
+
+fn search_file(path: &Path, name: &str, regex: &Regex, results: &mut Results) {
+    if fs::metadata(path).map_or(true, |metadata| metadata.len() > MAX_FILE_SIZE) {
+        return;
+    }
+    let Ok(bytes) = fs::read(path) else {
+        return;
+    };
+    if bytes.iter().take(BINARY_SAMPLE).any(|byte| *byte == 0) {
+        return;
+    }
+    let text = String::from_utf8_lossy(&bytes);
+    let mut matched = false;
+    for (index, line) in text.lines().enumerate() {
+        let Some(found) = regex.find(line) else {
+            continue;
+        };
+        if results.hits.len() == MAX_HITS {
+            results.capped = true;
+            break;
+        }
+        matched = true;
+        results.hits.push(Hit {
+            location: Location {
+                file_name: name.to_string(),
+                line: index.saturating_add(1),
+                column: line[..found.start()]
+                    .graphemes(true)
+                    .count()
+                    .saturating_add(1),
+            },
+            text: line.to_string(),
+        });
+    }
+    if matched {
+        results.files = results.files.saturating_add(1);
+    }
+}
+
+// whether the last rule matching `path` ignores it, the rules of deeper
+// .gitignore files coming after those above them
+fn ignored(rules: &Rules, path: &str, directory: bool) -> bool {
+    let mut ignored = false;
+    for (base, rules) in rules {
+        let relative = if base.is_empty() {
+            path
+        } else {
+            match path
+                .strip_prefix(base.as_str())
+                .and_then(|rest| rest.strip_prefix('/'))
+            {
+                Some(rest) => rest,
+                None => continue,
+            }
+        };
+        for rule in rules {
+            if (directory || !rule.directories_only) && rule.pattern.is_match(relative) {
+                ignored = !rule.negated;
+            }
+        }
+    }
+    ignored
+}
+
+// the rules of a .gitignore file. A pattern with a slash before its end is
+// taken from the file's directory, one without matches a name at any depth.
+fn parse(text: &str) -> Vec<Rule> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (directories_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            let glob = glob(line.strip_prefix('/').unwrap_or(line));
+            let pattern = if anchored {
+                format!("^{glob}$")
+            } else {
+                format!("(?:^|/){glob}$")
+            };
+            Some(Rule {
+                pattern: Regex::new(&pattern).ok()?,
+                negated,
+                directories_only,
+            })
+        })
+        .collect()
+}
+
+// a gitignore glob as a regex: `*` and `?` within a name, `**` across
+// directories, and `[...]` as it is
+fn glob(pattern: &str) -> String {
+    let mut regex = String::new();
+    let mut rest = pattern;
+    while let Some(c) = rest.chars().next() {
+        let (piece, len) = if let Some(after) = rest.strip_prefix("**/") {
+            (
+                "(?:.*/)?".to_string(),
+                rest.len().saturating_sub(after.len()),
+            )
+        } else if rest.starts_with("**") {
+            (".*".to_string(), 2)
+        } else {
+            match c {
+                '*' => ("[^/]*".to_string(), 1),
+                '?' => ("[^/]".to_string(), 1),
+                '[' => match rest.find(']') {
+                    Some(end) => {
+                        let class = &rest[1..end];
+                        let class = class
+                            .strip_prefix('!')
+                            .map_or_else(|| class.to_string(), |class| format!("^{class}"));
+                        (format!("[{class}]"), end.saturating_add(1))
+                    }
+                    None => (regex::escape("["), 1),
+                },
+                _ => (regex::escape(&c.to_string()), c.len_utf8()),
+            }
+        };
+        regex.push_str(&piece);
+        rest = &rest[len..];
+    }
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the rules of .gitignore files, each with its directory from the root
+    fn rules(files: &[(&str, &str)]) -> Rules {
+        files
+            .iter()
+            .map(|(base, text)| ((*base).to_string(), parse(text)))
+            .collect()
+    }
+
+    fn file_ignored(rules: &Rules, path: &str) -> bool {
+        ignored(rules, path, false)
+    }
+
+    #[test]
+    fn edits_are_the_listed_lines_whose_text_changed() {
+        let listed = lines(&[
+            "3 matches",
+            "a.rs:1:5: let x = 1;",
+            "a.rs:4:1: x",
+            "b.rs:2:3: x + 1",
+        ]);
+        let rows = lines(&[
+            "3 matches",
+            "a.rs:1:5: let y = 1;",
+            "b.rs:2:3: x + 1",
+            "b.rs:9:1: new",
+        ]);
+        let edits = edits(&listed, &rows);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].location.file_name, "a.rs");
+        assert_eq!(edits[0].location.line, 1);
+        assert_eq!(
+            apply(&edits[0], "    let x = 1;").as_deref(),
+            Some("    let y = 1;")
+        );
+        assert_eq!(apply(&edits[0], "    let z = 1;"), None);
+    }
+
+    fn lines(rows: &[&str]) -> Vec<String> {
+        rows.iter().map(|row| (*row).to_string()).collect()
+    }
+
+    #[test]
+    fn unanchored_patterns_match_a_name_at_any_depth() {
+        let rules = rules(&[("", "*.log\nbuild")]);
+        assert!(file_ignored(&rules, "a.log"));
+        assert!(file_ignored(&rules, "src/deep/b.log"));
+        assert!(file_ignored(&rules, "src/build"));
+        assert!(!file_ignored(&rules, "a.log.txt"));
+        assert!(!file_ignored(&rules, "rebuild"));
+    }
+
+    #[test]
+    fn anchored_patterns_match_only_from_their_directory() {
+        let rules = rules(&[("", "/build\ndocs/*.html")]);
+        assert!(file_ignored(&rules, "build"));
+        assert!(!file_ignored(&rules, "src/build"));
+        assert!(file_ignored(&rules, "docs/index.html"));
+        assert!(!file_ignored(&rules, "src/docs/index.html"));
+        assert!(!file_ignored(&rules, "docs/api/index.html"));
+    }
+
+    #[test]
+    fn negation_takes_back_an_earlier_match_only() {
+        let later = rules(&[("", "*.log\n!keep.log")]);
+        assert!(!file_ignored(&later, "keep.log"));
+        assert!(file_ignored(&later, "other.log"));
+        let earlier = rules(&[("", "!keep.log\n*.log")]);
+        assert!(file_ignored(&earlier, "keep.log"));
+    }
+
+    #[test]
+    fn nested_gitignore_files_come_after_the_ones_above() {
+        let rules = rules(&[("", "*.txt"), ("sub", "!notes.txt\n*.md")]);
+        assert!(!file_ignored(&rules, "sub/notes.txt"));
+        assert!(file_ignored(&rules, "notes.txt"));
+        assert!(file_ignored(&rules, "sub/deeper/other.txt"));
+        assert!(file_ignored(&rules, "sub/readme.md"));
+        assert!(!file_ignored(&rules, "readme.md"));
+        assert!(!file_ignored(&rules, "subway/readme.md"));
+    }
+
+    #[test]
+    fn trailing_slash_matches_directories_only() {
+        let rules = rules(&[("", "target/")]);
+        assert!(ignored(&rules, "target", true));
+        assert!(ignored(&rules, "crate/target", true));
+        assert!(!ignored(&rules, "target", false));
+    }
+
+    #[test]
+    fn double_star_crosses_directories_and_single_star_does_not() {
+        let rules = rules(&[("", "**/gen/*.rs\na/**/b\nlogs/*")]);
+        assert!(file_ignored(&rules, "gen/one.rs"));
+        assert!(file_ignored(&rules, "x/y/gen/one.rs"));
+        assert!(!file_ignored(&rules, "gen/sub/one.rs"));
+        assert!(file_ignored(&rules, "a/b"));
+        assert!(file_ignored(&rules, "a/x/y/b"));
+        assert!(file_ignored(&rules, "logs/today"));
+        assert!(!file_ignored(&rules, "logs/2024/today"));
+    }
+
+    #[test]
+    fn classes_question_marks_comments_and_escapes() {
+        let rules = rules(&[("", "# a comment\n[!a]*.c\nfile[0-9]\nv?.o\n\\#hash")]);
+        assert!(file_ignored(&rules, "b.c"));
+        assert!(!file_ignored(&rules, "a.c"));
+        assert!(file_ignored(&rules, "file7"));
+        assert!(!file_ignored(&rules, "filex"));
+        assert!(file_ignored(&rules, "v1.o"));
+        assert!(!file_ignored(&rules, "v12.o"));
+        assert!(file_ignored(&rules, "#hash"));
+        assert!(!file_ignored(&rules, "# a comment"));
+    }
+
+    #[test]
+    fn filter_excludes_and_includes_from_the_project_directory() {
+        let globs =
+            |globs: &[&str]| -> Vec<String> { globs.iter().map(ToString::to_string).collect() };
+        let filter = Filter::new("", &globs(&["*.rs", "docs/*.md"]), &globs(&["vendor/"]));
+        assert!(filter.excludes("vendor", true));
+        assert!(!filter.excludes("src", true));
+        assert!(!filter.excludes("src/main.rs", false));
+        assert!(!filter.excludes("docs/guide.md", false));
+        assert!(filter.excludes("src/guide.md", false));
+        // walked from a directory under the project's
+        let below = Filter::new("crates/a", &[], &globs(&["/crates/a/generated"]));
+        assert!(below.excludes("generated", true));
+        assert!(!below.excludes("src", true));
+        assert!(!Filter::default().excludes("anything", false));
+    }
+}
@@ -0,0 +1,46 @@
+use std::path::Path;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+// a place that compiler output points at, as `path:line:column` or
+// `path:line`, counting from 1
+#[derive(PartialEq)]
+pub struct Location {
+    pub file_name: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+// the locations in `text`, in order. Only paths of files that exist are
+// taken, which leaves out times and the like that look the same.
+pub fn parse(text: &str) -> Vec<Location> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        Regex::new(r#"([^\s:()\[\]'"`]+):(\d+)(?::(\d+))?"#).expect("invalid location pattern")
+    });
+    let mut locations: Vec<Location> = Vec::new();
+    for captures in pattern.captures_iter(text) {
+        let file_name = captures[1].to_string();
+        let Ok(line) = captures[2].parse::<usize>() else {
+            continue;
+        };
+        let column = captures
+            .get(3)
+            .and_then(|column| column.as_str().parse().ok())
+            .unwrap_or(1);
+        if line == 0 || !Path::new(&file_name).is_file() {
+            continue;
+        }
+        let location = Location {
+            file_name,
+            line,
+            column,
+        };
+        // compilers often name the same place twice in a row
+        if locations.last() != Some(&location) {
+            locations.push(location);
+        }
+    }
+    locations
+}
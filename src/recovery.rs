@@ -0,0 +1,131 @@
+use std::cmp::Reverse;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::messages::message;
+
+// snapshots kept per document, the oldest are removed first
+const KEEP: usize = 5;
+const UNTITLED: &str = "untitled";
+
+// a copy of a document's text taken while it had unsaved edits, stored as
+// `<escaped path>~<unix time>` in the recovery directory
+pub struct Snapshot {
+    pub path: PathBuf,
+    // the document's file, None for one that was never saved
+    pub file_name: Option<String>,
+    pub time: u64,
+}
+
+pub fn save(file_name: Option<&str>, contents: &str) -> io::Result<()> {
+    let dir = dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+    fs::create_dir_all(&dir)?;
+    let key = key(file_name);
+    fs::write(dir.join(format!("{key}~{}", now())), contents)?;
+    let own: Vec<Snapshot> = list()
+        .into_iter()
+        .filter(|snapshot| key_of(snapshot) == key)
+        .collect();
+    for old in own.iter().skip(KEEP) {
+        fs::remove_file(&old.path)?;
+    }
+    Ok(())
+}
+
+// every snapshot, newest first
+pub fn list() -> Vec<Snapshot> {
+    let Some(entries) = dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut snapshots: Vec<Snapshot> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?.to_string();
+            let (key, time) = name.rsplit_once('~')?;
+            Some(Snapshot {
+                file_name: file_name(key),
+                time: time.parse().ok()?,
+                path,
+            })
+        })
+        .collect();
+    snapshots.sort_by_key(|snapshot| Reverse(snapshot.time));
+    snapshots
+}
+
+// what `--recover` without a number prints
+pub fn print_list() {
+    let snapshots = list();
+    if snapshots.is_empty() {
+        println!("{}", message!("no_snapshots"));
+        return;
+    }
+    let now = now();
+    for (number, snapshot) in snapshots.iter().enumerate() {
+        let name = snapshot
+            .file_name
+            .clone()
+            .unwrap_or_else(|| message!("no_name"));
+        let age = age(now.saturating_sub(snapshot.time));
+        println!("{:>3}  {name}  ({age})", number.saturating_add(1));
+    }
+    println!("{}", message!("recover_hint"));
+}
+
+// the snapshot numbered `number` in print_list
+pub fn find(number: &str) -> Option<Snapshot> {
+    let index = number.parse::<usize>().ok()?.checked_sub(1)?;
+    list().into_iter().nth(index)
+}
+
+fn dir() -> Option<PathBuf> {
+    let home = env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(".local/share/byron-editor/recovery"))
+}
+
+// the absolute path with `%` and `/` percent-encoded
+fn key(file_name: Option<&str>) -> String {
+    let Some(file_name) = file_name else {
+        return UNTITLED.to_string();
+    };
+    let path =
+        env::current_dir().map_or_else(|_| PathBuf::from(file_name), |dir| dir.join(file_name));
+    path.to_string_lossy()
+        .replace('%', "%25")
+        .replace('/', "%2F")
+}
+
+fn key_of(snapshot: &Snapshot) -> String {
+    snapshot
+        .path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.rsplit_once('~'))
+        .map(|(key, _)| key.to_string())
+        .unwrap_or_default()
+}
+
+fn file_name(key: &str) -> Option<String> {
+    if key == UNTITLED {
+        return None;
+    }
+    Some(key.replace("%2F", "/").replace("%25", "%"))
+}
+
+pub fn age(seconds: u64) -> String {
+    match seconds {
+        0..=59 => message!("seconds_ago", count = seconds),
+        60..=3599 => message!("minutes_ago", count = seconds / 60),
+        3600..=86_399 => message!("hours_ago", count = seconds / 3600),
+        _ => message!("days_ago", count = seconds / 86_400),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
@@ -0,0 +1,42 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+// the lines of a paragraph filled out to `width` columns: its words joined
+// by single spaces, each line broken before the word that would go past
+// `width`. Every line gets the indentation of the first, and a word longer
+// than a line has it to itself.
+pub fn fill(lines: &[&str], width: usize) -> Vec<String> {
+    let indent: String = lines
+        .first()
+        .map(|line| line.chars().take_while(|c| c.is_whitespace()).collect())
+        .unwrap_or_default();
+    let start = |word: &str| {
+        let used = indent
+            .graphemes(true)
+            .count()
+            .saturating_add(word.graphemes(true).count());
+        (format!("{indent}{word}"), used)
+    };
+    let mut filled = Vec::new();
+    // the line being filled, with the columns it takes so far
+    let mut line: Option<(String, usize)> = None;
+    for word in lines.iter().flat_map(|line| line.split_whitespace()) {
+        line = Some(match line.take() {
+            Some((mut text, used)) => {
+                let with_word = used
+                    .saturating_add(1)
+                    .saturating_add(word.graphemes(true).count());
+                if with_word > width {
+                    filled.push(text);
+                    start(word)
+                } else {
+                    text.push(' ');
+                    text.push_str(word);
+                    (text, with_word)
+                }
+            }
+            None => start(word),
+        });
+    }
+    filled.extend(line.map(|(text, _)| text));
+    filled
+}
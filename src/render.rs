@@ -0,0 +1,382 @@
+use std::cmp;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use syntect::highlighting::{Color, FontStyle, Style};
+use termion::color;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::bidi;
+use crate::diagnostics::Severity;
+use crate::glyphs::Glyphs;
+use crate::highlighting;
+use crate::row;
+use crate::unicode;
+use crate::Terminal;
+
+const WARNING_BG_COLOR: Color = Color {
+    r: 204,
+    g: 102,
+    b: 0,
+    a: 0xFF,
+};
+const BRACKET_BG_COLOR: Color = Color {
+    r: 88,
+    g: 88,
+    b: 88,
+    a: 0xFF,
+};
+const COLOR_LITERAL: &str = r"#([0-9a-fA-F]{6}|[0-9a-fA-F]{3})\b|\b[Rr]gb\(\s*(\d{1,3})\s*,\s*(\d{1,3})\s*,\s*(\d{1,3})\s*\)";
+
+// a single grapheme of a rendered row and the style it is drawn with
+pub struct Cell {
+    pub style: Style,
+    pub text: String,
+    // drawn in reverse video, the terminal swapping the style's colors
+    pub inverse: bool,
+}
+
+// one cell per grapheme, drawn like Row::render draws it
+pub fn cells(ranges: &[(Style, &str)]) -> Vec<Cell> {
+    ranges
+        .iter()
+        .flat_map(|(style, text)| {
+            text.graphemes(true).map(move |grapheme| {
+                let text = if grapheme == "\t" {
+                    " ".to_string()
+                } else {
+                    grapheme.chars().take(1).collect()
+                };
+                Cell {
+                    style: *style,
+                    text,
+                    inverse: false,
+                }
+            })
+        })
+        .collect()
+}
+
+// widens the cells drawn for tabs in `source` to `tab_width` spaces, then
+// drops the cells that no longer fit in `width` columns
+pub fn expand_tabs(
+    cells: &mut Vec<Cell>,
+    source: &str,
+    column: usize,
+    tab_width: usize,
+    width: usize,
+) {
+    let mut used: usize = 0;
+    let mut fits = cells.len();
+    for (index, (cell, grapheme)) in cells.iter_mut().zip(source.graphemes(true)).enumerate() {
+        if used >= width {
+            fits = index;
+            break;
+        }
+        if grapheme == "\t" {
+            let stop = row::tab_columns(column.saturating_add(used), tab_width);
+            let spaces = cmp::min(stop, width.saturating_sub(used));
+            cell.text = " ".repeat(spaces);
+            used = used.saturating_add(spaces);
+        } else {
+            used = used.saturating_add(1);
+        }
+    }
+    cells.truncate(fits);
+}
+
+// draws the tabs, the spaces from `trailing` on and the non-breaking spaces
+// of `source`, whose tabs are expanded already, as the `glyphs` for them in
+// `color`
+pub fn mark_invisibles(
+    cells: &mut [Cell],
+    source: &str,
+    trailing: usize,
+    glyphs: &Glyphs,
+    color: Color,
+) {
+    for (index, (cell, grapheme)) in cells.iter_mut().zip(source.graphemes(true)).enumerate() {
+        let glyph = match grapheme {
+            "\t" => glyphs.tab,
+            " " if index >= trailing => glyphs.space,
+            "\u{a0}" => glyphs.nbsp,
+            _ => continue,
+        };
+        let rest: String = cell.text.chars().skip(1).collect();
+        cell.text = format!("{glyph}{rest}");
+        cell.style.foreground = color;
+    }
+}
+
+// puts the cells in the order they are drawn in when some of them are
+// right-to-left text
+pub fn reorder(cells: &mut Vec<Cell>) {
+    let chars: Vec<char> = cells
+        .iter()
+        .map(|cell| cell.text.chars().next().unwrap_or(' '))
+        .collect();
+    let Some(order) = bidi::visual_order(&chars) else {
+        return;
+    };
+    let mut logical: Vec<Option<Cell>> = cells.drain(..).map(Some).collect();
+    cells.extend(order.into_iter().filter_map(|index| logical[index].take()));
+}
+
+// joins cells of the same style back into runs and turns them into escapes,
+// ending with bold, italic, underline and reverse video off
+pub fn escape(cells: &[Cell]) -> String {
+    let mut runs: Vec<((Style, bool), String)> = Vec::new();
+    for cell in cells {
+        let look = (cell.style, cell.inverse);
+        if let Some((_, text)) = runs.last_mut().filter(|(of, _)| *of == look) {
+            text.push_str(&cell.text);
+        } else {
+            runs.push((look, cell.text.clone()));
+        }
+    }
+    let rgb = |Color { r, g, b, .. }: Color| color::Rgb(r, g, b);
+    let mut escaped = String::new();
+    let mut attributes = (FontStyle::empty(), false);
+    for ((style, inverse), text) in &runs {
+        if (style.font_style, *inverse) != attributes {
+            attributes = (style.font_style, *inverse);
+            escaped.push_str(&sgr(attributes.0, attributes.1));
+        }
+        escaped.push_str(&Terminal::bg(rgb(style.background)));
+        escaped.push_str(&Terminal::fg(rgb(style.foreground)));
+        escaped.push_str(text);
+    }
+    if attributes != (FontStyle::empty(), false) {
+        escaped.push_str(&sgr(FontStyle::empty(), false));
+    }
+    escaped
+}
+
+// turns off the attributes the cells use, then on those of `font_style`
+fn sgr(font_style: FontStyle, inverse: bool) -> String {
+    let mut codes = String::from("\x1b[22;23;24;27");
+    for (attribute, code) in [
+        (FontStyle::BOLD, ";1"),
+        (FontStyle::ITALIC, ";3"),
+        (FontStyle::UNDERLINE, ";4"),
+    ] {
+        if font_style.contains(attribute) {
+            codes.push_str(code);
+        }
+    }
+    if inverse {
+        codes.push_str(";7");
+    }
+    codes.push('m');
+    codes
+}
+
+// `mask` in place of everything but whitespace
+pub fn mask(text: &str, mask: char) -> String {
+    text.chars()
+        .map(|c| if c.is_whitespace() { c } else { mask })
+        .collect()
+}
+
+// gives invisible and lookalike characters a warning background, or
+// `color`, and bold, drawing the invisible ones as `placeholder` so they take
+// up a cell
+pub fn mark_suspicious(cells: &mut [Cell], color: Option<Color>, placeholder: char) {
+    let first_chars: Vec<Option<char>> =
+        cells.iter().map(|cell| cell.text.chars().next()).collect();
+    for (index, cell) in cells.iter_mut().enumerate() {
+        let Some(c) = first_chars.get(index).copied().flatten() else {
+            continue;
+        };
+        let previous = index
+            .checked_sub(1)
+            .and_then(|index| first_chars.get(index).copied().flatten());
+        let next = first_chars.get(index.saturating_add(1)).copied().flatten();
+        if unicode::is_suspicious(c, previous, next) {
+            cell.style.background = color.unwrap_or(WARNING_BG_COLOR);
+            cell.style.font_style.insert(FontStyle::BOLD);
+            if unicode::is_invisible(c) {
+                cell.text = placeholder.to_string();
+            }
+        }
+    }
+}
+
+// gives the cells of each search match the match highlight, or the
+// background and foreground in `colors`, and underlines them
+pub fn mark_matches(
+    cells: &mut [Cell],
+    matches: &[Range<usize>],
+    colors: (Option<color::Rgb>, Option<color::Rgb>),
+) {
+    let mut highlighting = vec![highlighting::Type::None; cells.len()];
+    for range in matches {
+        for highlight in highlighting.iter_mut().take(range.end).skip(range.start) {
+            *highlight = highlighting::Type::Match;
+        }
+    }
+    for (cell, highlight) in cells.iter_mut().zip(highlighting) {
+        if highlight != highlighting::Type::None {
+            let background = colors.0.unwrap_or_else(|| highlight.to_color());
+            cell.style.background = to_color(background);
+            if let Some(foreground) = colors.1 {
+                cell.style.foreground = to_color(foreground);
+            }
+            cell.style.font_style.insert(FontStyle::UNDERLINE);
+        }
+    }
+}
+
+// underlines the cells of misspelled words, in the misspelled color or
+// `color`
+pub fn mark_misspelled(cells: &mut [Cell], words: &[Range<usize>], color: Option<Color>) {
+    let color = color.unwrap_or_else(|| to_color(highlighting::Type::Misspelled.to_color()));
+    for range in words {
+        for cell in cells.iter_mut().take(range.end).skip(range.start) {
+            cell.style.foreground = color;
+            cell.style.font_style.insert(FontStyle::UNDERLINE);
+        }
+    }
+}
+
+// underlines the cells of each range a check found something in, in the
+// color of its severity, errors in reverse video too so they show without
+// colors. The worst goes over the others where they overlap.
+pub fn mark_diagnostics(cells: &mut [Cell], diagnostics: &[(Range<usize>, Severity)]) {
+    let mut worst: Vec<Option<Severity>> = vec![None; cells.len()];
+    for (range, severity) in diagnostics {
+        for cell in worst.iter_mut().take(range.end).skip(range.start) {
+            *cell = Some(cell.map_or(*severity, |known| known.min(*severity)));
+        }
+    }
+    for (cell, severity) in cells.iter_mut().zip(worst) {
+        let Some(severity) = severity else {
+            continue;
+        };
+        cell.style.foreground = to_color(severity.to_color());
+        cell.style.font_style.insert(FontStyle::UNDERLINE);
+        if severity == Severity::Error {
+            cell.inverse = true;
+        }
+    }
+}
+
+// gives the bracket cells at `columns` a background, or `color`, and bold
+// underlined text
+pub fn mark_brackets(cells: &mut [Cell], columns: &[usize], color: Option<Color>) {
+    for &column in columns {
+        if let Some(cell) = cells.get_mut(column) {
+            cell.style.background = color.unwrap_or(BRACKET_BG_COLOR);
+            cell.style
+                .font_style
+                .insert(FontStyle::BOLD | FontStyle::UNDERLINE);
+        }
+    }
+}
+
+// draws the selected cells in `colors`, background first, or with their
+// own colors swapped, in reverse video so it shows without colors too
+pub fn mark_selection(
+    cells: &mut [Cell],
+    columns: Range<usize>,
+    colors: (Option<Color>, Option<Color>),
+) {
+    for cell in cells.iter_mut().take(columns.end).skip(columns.start) {
+        // the terminal swaps them back
+        if let Some(background) = colors.0 {
+            cell.style.foreground = background;
+        }
+        if let Some(foreground) = colors.1 {
+            cell.style.background = foreground;
+        }
+        cell.inverse = true;
+    }
+}
+
+// gives the cells drawn on the theme's background `line` instead
+pub fn mark_line(cells: &mut [Cell], background: Color, line: Color) {
+    for cell in cells
+        .iter_mut()
+        .filter(|cell| cell.style.background == background)
+    {
+        cell.style.background = line;
+    }
+}
+
+pub fn to_color(color::Rgb(r, g, b): color::Rgb) -> Color {
+    Color { r, g, b, a: 0xFF }
+}
+
+// draws color literals such as `#1e90ff` or `Rgb(30, 144, 255)` on a
+// background of the color they describe
+pub fn mark_colors(cells: &mut [Cell]) {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| Regex::new(COLOR_LITERAL).expect("invalid color pattern"));
+    let line: String = cells.iter().map(|cell| cell.text.as_str()).collect();
+    // byte offset in `line` at which each cell starts
+    let mut starts = Vec::with_capacity(cells.len());
+    let mut offset: usize = 0;
+    for cell in cells.iter() {
+        starts.push(offset);
+        offset = offset.saturating_add(cell.text.len());
+    }
+    for captures in pattern.captures_iter(&line) {
+        let Some(color) = parse_color(&captures) else {
+            continue;
+        };
+        let Some(literal) = captures.get(0) else {
+            continue;
+        };
+        let foreground = contrasting(color);
+        for (cell, start) in cells.iter_mut().zip(&starts) {
+            if (literal.start()..literal.end()).contains(start) {
+                cell.style.background = color;
+                cell.style.foreground = foreground;
+            }
+        }
+    }
+}
+
+fn parse_color(captures: &regex::Captures) -> Option<Color> {
+    if let Some(hex) = captures.get(1) {
+        let hex = hex.as_str();
+        let channel = |index: usize, len: usize| {
+            let digits =
+                hex.get(index.saturating_mul(len)..index.saturating_add(1).saturating_mul(len))?;
+            let value = u8::from_str_radix(digits, 16).ok()?;
+            // #abc is shorthand for #aabbcc
+            Some(if len == 1 {
+                value.saturating_mul(17)
+            } else {
+                value
+            })
+        };
+        let len = hex.len() / 3;
+        return Some(Color {
+            r: channel(0, len)?,
+            g: channel(1, len)?,
+            b: channel(2, len)?,
+            a: 0xFF,
+        });
+    }
+    let channel = |index: usize| captures.get(index)?.as_str().parse::<u8>().ok();
+    Some(Color {
+        r: channel(2)?,
+        g: channel(3)?,
+        b: channel(4)?,
+        a: 0xFF,
+    })
+}
+
+// black or white, whichever reads better on `color`
+fn contrasting(color: Color) -> Color {
+    let luminance = u32::from(color.r) * 299 + u32::from(color.g) * 587 + u32::from(color.b) * 114;
+    let value = if luminance > 128_000 { 0 } else { 0xFF };
+    Color {
+        r: value,
+        g: value,
+        b: value,
+        a: 0xFF,
+    }
+}
@@ -1,10 +1,17 @@
+use crate::glyphs::Glyphs;
+use crate::highlighting::Highlighted;
+use regex::Regex;
 use std::cmp;
+use std::ops::Range;
 use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Default, Clone)]
 pub struct Row {
     string: String,
+    // kept in step with `string` on every edit
     len: usize,
+    // dropped on every edit, see Document::highlight
+    highlighted: Option<Highlighted>,
 }
 
 impl From<&str> for Row {
@@ -12,34 +19,58 @@ impl From<&str> for Row {
         let mut row = Self {
             string: String::from(slice),
             len: 0,
+            highlighted: None,
         };
-        row.update_len();
+        row.update_cache();
         row
     }
 }
 
 impl Row {
-    pub fn render(&self, start: usize, end: usize) -> String {
+    // the graphemes from `start` up to `end` a character each, tabs as the
+    // spaces up to the next tab stop. With `invisibles` tabs, trailing spaces
+    // and non-breaking spaces are drawn as its glyphs for them.
+    pub fn render(
+        &self,
+        start: usize,
+        end: usize,
+        tab_width: usize,
+        invisibles: Option<&Glyphs>,
+    ) -> String {
         let end = cmp::min(end, self.string.len());
         let start = cmp::min(start, end);
+        let trailing = self.trailing_spaces();
         let mut result = String::new();
         #[allow(clippy::arithmetic_side_effects)]
-        for (index, grapheme) in self.string[..]
+        for (index, (grapheme, width)) in self.string[..]
             .graphemes(true)
+            .zip(self.widths(tab_width))
             .enumerate()
             .skip(start)
             .take(end - start)
         {
-            if let Some(c) = grapheme.chars().next() {
-                if grapheme == "\t" {
-                    result.push_str(" ");
-                } else {
-                    result.push(c);
+            let Some(c) = grapheme.chars().next() else {
+                continue;
+            };
+            match (invisibles, c) {
+                (Some(glyphs), '\t') => {
+                    result.push(glyphs.tab);
+                    result.push_str(&" ".repeat(width.saturating_sub(1)));
                 }
+                (None, '\t') => result.push_str(&" ".repeat(width)),
+                (Some(glyphs), ' ') if index >= trailing => result.push(glyphs.space),
+                (Some(glyphs), '\u{a0}') => result.push(glyphs.nbsp),
+                _ => result.push(c),
             }
         }
         result
     }
+    // where the spaces and tabs at the end of the row start
+    pub fn trailing_spaces(&self) -> usize {
+        let text = self.string.trim_end_matches([' ', '\t']);
+        self.len
+            .saturating_sub(self.string[text.len()..].graphemes(true).count())
+    }
     pub fn insert(&mut self, x_position: usize, c: char) {
         if x_position >= self.len() {
             self.string.push(c);
@@ -50,24 +81,34 @@ impl Row {
             result.push_str(&split);
             self.string = result;
         }
-        self.update_len();
+        self.update_cache();
+    }
+    pub fn insert_str(&mut self, x_position: usize, text: &str) {
+        let mut result: String = self.string[..].graphemes(true).take(x_position).collect();
+        let split: String = self.string[..].graphemes(true).skip(x_position).collect();
+        result.push_str(text);
+        result.push_str(&split);
+        self.string = result;
+        self.update_cache();
     }
     pub fn append(&mut self, new: &Self) {
         self.string = format!("{}{}", self.string, new.string);
-        self.update_len()
-    }
-    pub fn find(&self, query: &str) -> Option<usize> {
-        let matching_byte_index = self.string.find(query);
-        if let Some(matching_byte_index) = matching_byte_index {
-            for (grapheme_index, (byte_index, _)) in
-                self.string[..].grapheme_indices(true).enumerate()
-            {
-                if matching_byte_index == byte_index {
-                    return Some(grapheme_index);
-                }
-            }
-        }
-        None
+        self.update_cache();
+    }
+    // the grapheme range of each match of `regex`, with what `replacement`
+    // expands to for it ($1, ${name} and so on)
+    pub fn regex_matches(&self, regex: &Regex, replacement: &str) -> Vec<(Range<usize>, String)> {
+        regex
+            .captures_iter(&self.string)
+            .filter_map(|captures| {
+                let found = captures.get(0)?;
+                let start = self.string[..found.start()].graphemes(true).count();
+                let len = found.as_str().graphemes(true).count();
+                let mut expanded = String::new();
+                captures.expand(replacement, &mut expanded);
+                Some((start..start.saturating_add(len), expanded))
+            })
+            .collect()
     }
     #[allow(clippy::arithmetic_side_effects)]
     pub fn delete(&mut self, at: usize) {
@@ -82,28 +123,238 @@ impl Row {
         result.push_str(&split);
         self.string = result;
 
-        self.update_len();
+        self.update_cache();
     }
     pub fn split(&mut self, at: usize) -> Self {
         let result: String = self.string[..].graphemes(true).take(at).collect();
         let new_row: String = self.string[..].graphemes(true).skip(at).collect();
         self.string = result;
-        self.update_len();
+        self.update_cache();
         Self::from(&new_row[..])
     }
+    pub fn grapheme(&self, at: usize) -> Option<&str> {
+        self.string[..].graphemes(true).nth(at)
+    }
+    // the graphemes from `start` up to, but not including, `end`
+    pub fn substring(&self, start: usize, end: usize) -> String {
+        self.string[..]
+            .graphemes(true)
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect()
+    }
+    // the screen columns taken by the graphemes from `start` up to `end`
+    pub fn columns(&self, start: usize, end: usize, tab_width: usize) -> usize {
+        self.widths(tab_width)
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .sum()
+    }
+    // the screen columns of each grapheme, with tab stops every `tab_width`
+    // columns from the start of the row
+    fn widths(&self, tab_width: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut column: usize = 0;
+        self.string[..].graphemes(true).map(move |grapheme| {
+            let width = if grapheme == "\t" {
+                tab_columns(column, tab_width)
+            } else {
+                1
+            };
+            column = column.saturating_add(width);
+            width
+        })
+    }
+    // the first grapheme from which position `end` is still less than
+    // `width` columns away
+    pub fn start_for(&self, end: usize, width: usize, tab_width: usize) -> usize {
+        let beyond = end.saturating_sub(self.len);
+        let mut used = beyond;
+        let mut start = end.saturating_sub(beyond);
+        if used >= width {
+            return end.saturating_sub(width.saturating_sub(1));
+        }
+        let after = self.len.saturating_sub(start);
+        let widths: Vec<usize> = self.widths(tab_width).collect();
+        for columns in widths.into_iter().rev().skip(after) {
+            let next = used.saturating_add(columns);
+            if next >= width {
+                break;
+            }
+            used = next;
+            start = start.saturating_sub(1);
+        }
+        start
+    }
+    // the grapheme at `column` of the screen line starting at grapheme
+    // `start`, not going past `end`
+    pub fn index_at(&self, start: usize, end: usize, column: usize, tab_width: usize) -> usize {
+        let mut used: usize = 0;
+        let mut index = start;
+        for width in self
+            .widths(tab_width)
+            .skip(start)
+            .take(end.saturating_sub(start))
+        {
+            used = used.saturating_add(width);
+            if used > column {
+                break;
+            }
+            index = index.saturating_add(1);
+        }
+        cmp::min(index, end)
+    }
+    // where each screen line starts when the row is wrapped at `width`
+    // columns. A full last line is followed by an empty one for the cursor
+    // to sit on at the end of the row.
+    pub fn wrap(&self, width: usize, tab_width: usize) -> Vec<usize> {
+        let mut starts = vec![0];
+        let mut used: usize = 0;
+        for (index, columns) in self.widths(tab_width).enumerate() {
+            if used > 0 && used.saturating_add(columns) > width {
+                starts.push(index);
+                used = 0;
+            }
+            used = used.saturating_add(columns);
+        }
+        if used >= width {
+            starts.push(self.len);
+        }
+        starts
+    }
+    // where a word-wise move right from `x` ends: past any whitespace, then
+    // past a run of word characters or of punctuation. `word_chars` are
+    // counted as word characters on top of letters, digits and `_`.
+    pub fn next_word(&self, x: usize, word_chars: &str) -> usize {
+        let classes: Vec<Class> = self.string[..]
+            .graphemes(true)
+            .map(|grapheme| class(grapheme, word_chars))
+            .collect();
+        let mut x = cmp::min(x, classes.len());
+        while classes.get(x) == Some(&Class::Whitespace) {
+            x = x.saturating_add(1);
+        }
+        if let Some(&run) = classes.get(x) {
+            while classes.get(x) == Some(&run) {
+                x = x.saturating_add(1);
+            }
+        }
+        x
+    }
+    // the start of the word before `x`, the mirror image of next_word
+    pub fn previous_word(&self, x: usize, word_chars: &str) -> usize {
+        let classes: Vec<Class> = self.string[..]
+            .graphemes(true)
+            .map(|grapheme| class(grapheme, word_chars))
+            .collect();
+        let mut x = cmp::min(x, classes.len());
+        let before = |x: usize| {
+            x.checked_sub(1)
+                .and_then(|index| classes.get(index))
+                .copied()
+        };
+        while before(x) == Some(Class::Whitespace) {
+            x = x.saturating_sub(1);
+        }
+        if let Some(run) = before(x) {
+            while before(x) == Some(run) {
+                x = x.saturating_sub(1);
+            }
+        }
+        x
+    }
+    // the run of word characters at or just before `x`
+    pub fn word_at(&self, x: usize, word_chars: &str) -> Option<Range<usize>> {
+        let classes: Vec<Class> = self.string[..]
+            .graphemes(true)
+            .map(|grapheme| class(grapheme, word_chars))
+            .collect();
+        let is_word = |index: usize| classes.get(index) == Some(&Class::Word);
+        let at = if is_word(x) {
+            x
+        } else {
+            x.checked_sub(1).filter(|&x| is_word(x))?
+        };
+        let mut start = at;
+        while start > 0 && is_word(start.saturating_sub(1)) {
+            start = start.saturating_sub(1);
+        }
+        let mut end = at;
+        while is_word(end) {
+            end = end.saturating_add(1);
+        }
+        Some(start..end)
+    }
+    // the quoted strings of the row, quotes included. A backslash escapes
+    // the character after it.
+    pub fn quoted(&self) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut open: Option<(usize, &str)> = None;
+        let mut escaped = false;
+        for (index, grapheme) in self.string[..].graphemes(true).enumerate() {
+            if escaped {
+                escaped = false;
+                continue;
+            }
+            match (open, grapheme) {
+                (_, "\\") => escaped = true,
+                (Some((start, quote)), _) if grapheme == quote => {
+                    ranges.push(start..index.saturating_add(1));
+                    open = None;
+                }
+                (None, "\"" | "'" | "`") => open = Some((index, grapheme)),
+                _ => (),
+            }
+        }
+        ranges
+    }
     pub fn len(&self) -> usize {
         self.len
     }
     pub fn is_empty(&self) -> bool {
         self.len == 0
     }
-    fn update_len(&mut self) {
+    pub fn highlighted(&self) -> Option<&Highlighted> {
+        self.highlighted.as_ref()
+    }
+    pub fn set_highlighted(&mut self, highlighted: Option<Highlighted>) {
+        self.highlighted = highlighted;
+    }
+    fn update_cache(&mut self) {
         self.len = self.string[..].graphemes(true).count();
+        self.highlighted = None;
     }
     pub fn as_bytes(&self) -> &[u8] {
         self.string.as_bytes()
     }
+    pub fn as_str(&self) -> &str {
+        &self.string
+    }
     pub fn is_equal(&self, line: &str) -> bool {
         self.string == line
     }
 }
+
+#[derive(Clone, Copy, PartialEq)]
+enum Class {
+    Word,
+    Whitespace,
+    Punctuation,
+}
+
+fn class(grapheme: &str, word_chars: &str) -> Class {
+    let c = grapheme.chars().next().unwrap_or(' ');
+    if c.is_whitespace() {
+        Class::Whitespace
+    } else if c.is_alphanumeric() || c == '_' || word_chars.contains(c) {
+        Class::Word
+    } else {
+        Class::Punctuation
+    }
+}
+
+// how wide a tab at `column` is, reaching to the next multiple of
+// `tab_width`
+pub fn tab_columns(column: usize, tab_width: usize) -> usize {
+    let tab_width = cmp::max(tab_width, 1);
+    tab_width.saturating_sub(column % tab_width)
+}
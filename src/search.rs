@@ -0,0 +1,328 @@
+use std::fs;
+use std::io;
+
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+use crate::config;
+use crate::messages::message;
+use crate::{Document, Position};
+
+// how many past queries are remembered
+const HISTORY_LEN: usize = 100;
+
+// how letters of the query match letters of the text
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Case {
+    // ignored unless the query has an uppercase letter
+    #[default]
+    Smart,
+    Sensitive,
+    Insensitive,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+impl Direction {
+    pub fn reverse(self) -> Self {
+        match self {
+            Self::Forward => Self::Backward,
+            Self::Backward => Self::Forward,
+        }
+    }
+}
+
+// where a match is, `end` being just after it on the same row
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Match {
+    pub start: Position,
+    pub end: Position,
+    // whether it was found only by going around the end of the document
+    pub wrapped: bool,
+}
+
+// a search as it was typed, kept to step through its matches. A query
+// starting with `/` is a regular expression, anything else literal text.
+#[derive(Clone)]
+pub struct SearchState {
+    pub query: String,
+    pub direction: Direction,
+    // going on from the other end of the document after the last match
+    pub wrap: bool,
+    regex: Regex,
+}
+
+impl SearchState {
+    pub fn new(query: &str, case: Case) -> Result<Self, regex::Error> {
+        let (pattern, literal) = match query.strip_prefix('/') {
+            Some(pattern) => (pattern.to_string(), false),
+            None => (regex::escape(query), true),
+        };
+        // for a regex it's the letters outside of escapes like \S that count,
+        // which isn't worth telling apart
+        let text = if literal { query } else { pattern.as_str() };
+        let ignore_case = match case {
+            Case::Smart => !text.chars().any(char::is_uppercase),
+            Case::Sensitive => false,
+            Case::Insensitive => true,
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(ignore_case)
+            .build()?;
+        Ok(Self {
+            query: query.to_string(),
+            direction: Direction::Forward,
+            wrap: true,
+            regex,
+        })
+    }
+    // what the matches are highlighted by
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+    // the same search the other way
+    pub fn reversed(&self) -> Self {
+        Self {
+            direction: self.direction.reverse(),
+            ..self.clone()
+        }
+    }
+    // going forward the first match starting at `from` or after it, going
+    // backward the last one starting before it
+    pub fn find(&self, document: &Document, from: &Position) -> Option<Match> {
+        let len = document.len();
+        if len == 0 {
+            return None;
+        }
+        let from = Position {
+            x: from.x,
+            y: from.y.min(len),
+        };
+        let found = match self.direction {
+            Direction::Forward => self.forward(document, &from, len),
+            Direction::Backward => self.backward(document, &from, 0),
+        };
+        if found.is_some() || !self.wrap {
+            return found;
+        }
+        let wrapped = match self.direction {
+            Direction::Forward => {
+                self.forward(document, &Position::default(), from.y.saturating_add(1))
+            }
+            Direction::Backward => {
+                let end = Position {
+                    x: usize::MAX,
+                    y: len.saturating_sub(1),
+                };
+                self.backward(document, &end, from.y)
+            }
+        };
+        wrapped.map(|found| Match {
+            wrapped: true,
+            ..found
+        })
+    }
+    // in the rows from `from` up to `until`
+    fn forward(&self, document: &Document, from: &Position, until: usize) -> Option<Match> {
+        let until = until.min(document.len());
+        (from.y..until).find_map(|y| {
+            self.matches(document, y)
+                .into_iter()
+                .find(|(start, _)| y > from.y || *start >= from.x)
+                .map(|(start, end)| found(y, start, end))
+        })
+    }
+    // in the rows from `from` back to `until`
+    fn backward(&self, document: &Document, from: &Position, until: usize) -> Option<Match> {
+        let from_y = from.y.min(document.len().saturating_sub(1));
+        (until..=from_y).rev().find_map(|y| {
+            self.matches(document, y)
+                .into_iter()
+                .rev()
+                .find(|(start, _)| y < from.y || *start < from.x)
+                .map(|(start, end)| found(y, start, end))
+        })
+    }
+    // the grapheme ranges the query matches on row `y`
+    fn matches(&self, document: &Document, y: usize) -> Vec<(usize, usize)> {
+        document.row(y).map_or_else(Vec::new, |row| {
+            row.regex_matches(&self.regex, "")
+                .into_iter()
+                .map(|(range, _)| (range.start, range.end))
+                .collect()
+        })
+    }
+}
+
+// the queries searched for, oldest first and each once, kept a line each
+// in the config directory from one run to the next
+#[derive(Default)]
+pub struct History {
+    queries: Vec<String>,
+}
+
+impl History {
+    // none for a missing or unreadable file
+    pub fn load() -> Self {
+        let text = config::search_history_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .unwrap_or_default();
+        Self {
+            queries: text.lines().map(ToString::to_string).collect(),
+        }
+    }
+    pub fn queries(&self) -> &[String] {
+        &self.queries
+    }
+    // as the newest, moving it up if it was searched for before
+    pub fn push(&mut self, query: &str) {
+        self.queries.retain(|old| old != query);
+        self.queries.push(query.to_string());
+        let over = self.queries.len().saturating_sub(HISTORY_LEN);
+        self.queries.drain(..over);
+    }
+    pub fn save(&self) -> io::Result<()> {
+        let path = config::search_history_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, message!("no_home")))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.queries.join("\n"))
+    }
+}
+
+fn found(y: usize, start: usize, end: usize) -> Match {
+    Match {
+        start: Position { x: start, y },
+        end: Position { x: end, y },
+        wrapped: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document() -> Document {
+        Document::scratch("one fish\ntwo Fish\nred fish\nblue FISH")
+    }
+
+    fn at(x: usize, y: usize) -> Position {
+        Position { x, y }
+    }
+
+    fn search(query: &str) -> SearchState {
+        SearchState::new(query, Case::Smart).expect("valid query")
+    }
+
+    fn starts(state: &SearchState, from: Position) -> Option<(usize, usize, bool)> {
+        let found = state.find(&document(), &from)?;
+        Some((found.start.x, found.start.y, found.wrapped))
+    }
+
+    #[test]
+    fn finds_literal_text_at_or_after_the_cursor() {
+        let state = search("fish");
+        assert_eq!(starts(&state, at(0, 0)), Some((4, 0, false)));
+        assert_eq!(starts(&state, at(4, 0)), Some((4, 0, false)));
+        assert_eq!(starts(&state, at(5, 0)), Some((4, 1, false)));
+    }
+
+    #[test]
+    fn finds_every_match_on_a_row_not_only_the_first() {
+        let document = Document::scratch("ab ab ab");
+        let state = search("ab");
+        let found = state.find(&document, &at(1, 0)).expect("a match");
+        assert_eq!(found.start, at(3, 0));
+        assert_eq!(found.end, at(5, 0));
+    }
+
+    #[test]
+    fn smart_case_ignores_case_for_lowercase_queries_only() {
+        assert_eq!(starts(&search("fish"), at(0, 3)), Some((5, 3, false)));
+        assert_eq!(starts(&search("Fish"), at(0, 2)), Some((4, 1, true)));
+    }
+
+    #[test]
+    fn case_can_be_forced_either_way() {
+        let sensitive = SearchState::new("fish", Case::Sensitive).expect("valid query");
+        assert_eq!(starts(&sensitive, at(0, 1)), Some((4, 2, false)));
+        let insensitive = SearchState::new("FISH", Case::Insensitive).expect("valid query");
+        assert_eq!(starts(&insensitive, at(0, 1)), Some((4, 1, false)));
+    }
+
+    #[test]
+    fn a_leading_slash_makes_a_regex() {
+        assert_eq!(starts(&search("/^[rb]"), at(0, 0)), Some((0, 2, false)));
+        assert_eq!(starts(&search("a.b"), at(0, 0)), None);
+        assert!(SearchState::new("/(", Case::Smart).is_err());
+    }
+
+    #[test]
+    fn literal_text_is_not_a_pattern() {
+        let document = Document::scratch("a.b axb");
+        let found = search("a.b").find(&document, &at(1, 0));
+        assert_eq!(found.map(|found| found.wrapped), Some(true));
+    }
+
+    #[test]
+    fn backward_finds_the_last_match_before_the_cursor() {
+        let state = search("fish").reversed();
+        assert_eq!(starts(&state, at(0, 2)), Some((4, 1, false)));
+        assert_eq!(starts(&state, at(5, 2)), Some((4, 2, false)));
+        assert_eq!(starts(&state, at(4, 2)), Some((4, 1, false)));
+    }
+
+    #[test]
+    fn wraps_around_either_end() {
+        assert_eq!(starts(&search("one"), at(0, 1)), Some((0, 0, true)));
+        assert_eq!(
+            starts(&search("blue").reversed(), at(0, 1)),
+            Some((0, 3, true))
+        );
+    }
+
+    #[test]
+    fn stops_at_the_ends_without_wrap() {
+        let mut state = search("one");
+        state.wrap = false;
+        assert_eq!(starts(&state, at(0, 1)), None);
+        let mut state = search("blue").reversed();
+        state.wrap = false;
+        assert_eq!(starts(&state, at(0, 1)), None);
+    }
+
+    #[test]
+    fn the_only_match_is_found_again_by_wrapping() {
+        assert_eq!(starts(&search("red"), at(1, 2)), Some((0, 2, true)));
+        assert_eq!(
+            starts(&search("red").reversed(), at(0, 2)),
+            Some((0, 2, true))
+        );
+    }
+
+    #[test]
+    fn an_empty_document_has_no_matches() {
+        let state = search("x");
+        assert_eq!(state.find(&Document::default(), &Position::default()), None);
+    }
+
+    #[test]
+    fn history_keeps_each_query_once_newest_last() {
+        let mut history = History::default();
+        for query in ["fish", "/f.sh", "red", "fish"] {
+            history.push(query);
+        }
+        assert_eq!(history.queries(), ["/f.sh", "red", "fish"]);
+        for number in 0..HISTORY_LEN {
+            history.push(&number.to_string());
+        }
+        assert_eq!(history.queries().len(), HISTORY_LEN);
+        assert_eq!(history.queries().first().map(String::as_str), Some("0"));
+    }
+}
@@ -0,0 +1,115 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::messages::message;
+
+// the session written on exit, which --session opens again. Its name
+// can't be given to one, so it doesn't clash with those saved by name.
+const LAST: &str = ".last";
+
+// the open files of a working set, the one on screen first, and the syntax
+// theme. Sessions saved before offsets and the theme were kept go without.
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub buffers: Vec<Entry>,
+    #[serde(default)]
+    pub theme: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    pub file_name: String,
+    pub x: usize,
+    pub y: usize,
+    // the first row and column on screen
+    #[serde(default)]
+    pub offset_x: usize,
+    #[serde(default)]
+    pub offset_y: usize,
+}
+
+pub fn save(name: &str, session: &Session) -> Result<(), String> {
+    write(&path(name)?, session)
+}
+
+pub fn load(name: &str) -> Result<Session, String> {
+    read(&path(name)?, &message!("no_session", name = name))
+}
+
+pub fn save_last(session: &Session) -> Result<(), String> {
+    write(&last_path()?, session)
+}
+
+pub fn load_last() -> Result<Session, String> {
+    read(&last_path()?, &message!("no_last_session"))
+}
+
+fn write(path: &Path, session: &Session) -> Result<(), String> {
+    let text = toml::to_string(session).map_err(|error| error.to_string())?;
+    let write = |path: &Path| -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    };
+    write(path).map_err(|error| message!("cannot_write_file", file = path.display(), error = error))
+}
+
+// `missing` is the error when there is no such file
+fn read(path: &Path, missing: &str) -> Result<Session, String> {
+    let text = fs::read_to_string(path).map_err(|_| missing.to_string())?;
+    toml::from_str(&text).map_err(|error| format!("{}: {}", path.display(), error.message()))
+}
+
+// the sessions saved for this project, by name
+pub fn names() -> Vec<String> {
+    let Some(entries) = dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?.strip_suffix(".toml")?;
+            Some(name.to_string()).filter(|name| name != LAST)
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+fn path(name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(message!("bad_session_name", name = name));
+    }
+    let dir = dir().ok_or_else(|| message!("no_home"))?;
+    Ok(dir.join(format!("{name}.toml")))
+}
+
+fn last_path() -> Result<PathBuf, String> {
+    let dir = dir().ok_or_else(|| message!("no_home"))?;
+    Ok(dir.join(format!("{LAST}.toml")))
+}
+
+// one directory per project, which is the git repository the editor was
+// started in, or the working directory outside of one
+fn dir() -> Option<PathBuf> {
+    let home = env::var_os("HOME")?;
+    let cwd = env::current_dir().ok()?;
+    let project = cwd
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(&cwd);
+    let key = project
+        .to_string_lossy()
+        .replace('%', "%25")
+        .replace('/', "%2F");
+    Some(
+        PathBuf::from(home)
+            .join(".local/share/byron-editor/sessions")
+            .join(key),
+    )
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::config::{self, Config};
+use crate::variables;
+use crate::Position;
+
+// the table of the snippets file for snippets in any file, the others being
+// named by file extension
+const ALL: &str = "all";
+
+// what there is without a snippets file: extension, name and text
+const DEFAULT_SNIPPETS: [(&str, &str, &str); 5] = [
+    ("rs", "fn", "fn ${1:name}(${2}) {\n    $0\n}"),
+    (
+        "rs",
+        "match",
+        "match ${1:value} {\n    ${2:pattern} => ${3:todo!()},\n}$0",
+    ),
+    ("rs", "derive", "#[derive(${1:Debug, Clone})]$0"),
+    ("rs", "impl", "impl ${1:Type} {\n    $0\n}"),
+    ("rs", "test", "#[test]\nfn ${1:name}() {\n    $0\n}"),
+];
+
+// the text of the snippet `name` in a file named `file_name`: the snippets
+// file's for the extension, then its `all` table's, then the built-in ones
+pub fn find(name: &str, file_name: Option<&str>) -> Result<Option<String>, String> {
+    let extension = file_name
+        .and_then(|name| Path::new(name).extension())
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tables = load()?;
+    let found = [extension.as_str(), ALL]
+        .iter()
+        .find_map(|table| tables.get(*table)?.get(name).cloned());
+    let found = found.or_else(|| {
+        DEFAULT_SNIPPETS
+            .iter()
+            .find(|(default_extension, default_name, _)| {
+                *default_extension == extension && *default_name == name
+            })
+            .map(|(_, _, text)| (*text).to_string())
+    });
+    Ok(found)
+}
+
+// read each time, so that a snippet added to the file is there right away
+fn load() -> Result<HashMap<String, HashMap<String, String>>, String> {
+    let Some(path) = config::snippets_path() else {
+        return Ok(HashMap::new());
+    };
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Ok(HashMap::new());
+    };
+    toml::from_str(&text).map_err(|error| format!("{}: {}", path.display(), error.message()))
+}
+
+// a snippet's text with its fields, each from where it starts to where it
+// ends, counted from the start of the text
+pub struct Expansion {
+    pub text: String,
+    fields: Vec<(usize, Position, Position)>,
+}
+
+// `$1` or `${1}` is an empty field and `${1:text}` one filled in, gone
+// through with Tab in the order of the numbers, `$0` last, where the cursor
+// ends up. Without a `$0` that is the end of the text. `${date}` and the
+// other variables are filled in, and `\` takes a `$`, `}` or `\` as it is.
+// The rows after the first start with `indent`.
+pub fn expand(snippet: &str, indent: &str, file_name: Option<&str>, config: &Config) -> Expansion {
+    let mut text = String::new();
+    let mut fields: Vec<(usize, Position, Position)> = Vec::new();
+    let mut chars = snippet.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            '\n' => {
+                text.push('\n');
+                text.push_str(indent);
+            }
+            '$' if chars.peek().is_some_and(char::is_ascii_digit) => {
+                let mut number = String::new();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    number.push(digit);
+                }
+                let at = end_of(&text);
+                fields.push((number.parse().unwrap_or(0), at, at));
+            }
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let mut inside = String::new();
+                while let Some(c) = chars.next_if(|c| *c != '}') {
+                    inside.push(if c == '\\' {
+                        chars.next().unwrap_or(c)
+                    } else {
+                        c
+                    });
+                }
+                chars.next();
+                let (name, default) = inside.split_once(':').unwrap_or((&inside, ""));
+                if let Ok(number) = name.parse() {
+                    let start = end_of(&text);
+                    text.push_str(default);
+                    fields.push((number, start, end_of(&text)));
+                } else if let Some(value) = variables::value(name, file_name, config) {
+                    text.push_str(&value);
+                } else {
+                    text.push_str("${");
+                    text.push_str(&inside);
+                    text.push('}');
+                }
+            }
+            c => text.push(c),
+        }
+    }
+    if !fields.iter().any(|(number, ..)| *number == 0) {
+        let end = end_of(&text);
+        fields.push((0, end, end));
+    }
+    // 0 goes after the others
+    fields.sort_by_key(|(number, ..)| number.checked_sub(1).unwrap_or(usize::MAX));
+    Expansion { text, fields }
+}
+
+// where `text` ends, its last row and the graphemes on it
+fn end_of(text: &str) -> Position {
+    let last = text.rsplit('\n').next().unwrap_or_default();
+    Position {
+        x: last.graphemes(true).count(),
+        y: text.matches('\n').count(),
+    }
+}
+
+impl Expansion {
+    // the fields once the text is at `at`, the last being where the cursor
+    // ends up
+    pub fn fields_at(&self, at: Position) -> Vec<Field> {
+        let place = |position: Position| {
+            if position.y == 0 {
+                Position {
+                    x: at.x.saturating_add(position.x),
+                    y: at.y,
+                }
+            } else {
+                Position {
+                    x: position.x,
+                    y: at.y.saturating_add(position.y),
+                }
+            }
+        };
+        self.fields
+            .iter()
+            .map(|(_, start, end)| Field {
+                start: place(*start),
+                end: place(*end),
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Field {
+    pub start: Position,
+    pub end: Position,
+}
+
+// an expanded snippet whose fields Tab is going through, kept on the text
+// they are in as it is edited
+pub struct Snippet {
+    pub fields: Vec<Field>,
+    pub current: usize,
+}
+
+impl Snippet {
+    // after text from `at` up to `end` went in. Text typed at the end of a
+    // field goes into it, text at its start goes before it.
+    pub fn inserted(&mut self, at: Position, end: Position) {
+        let shift = |position: &mut Position, at_too: bool| {
+            if position.y == at.y && (position.x > at.x || (at_too && position.x == at.x)) {
+                *position = Position {
+                    x: end.x.saturating_add(position.x.saturating_sub(at.x)),
+                    y: end.y,
+                };
+            } else if position.y > at.y {
+                position.y = position.y.saturating_add(end.y.saturating_sub(at.y));
+            }
+        };
+        for field in &mut self.fields {
+            shift(&mut field.start, false);
+            shift(&mut field.end, true);
+        }
+    }
+    // after the text from `at` up to `end` went
+    pub fn deleted(&mut self, at: Position, end: Position) {
+        let shift = |position: &mut Position| {
+            if (position.y, position.x) <= (at.y, at.x) {
+                return;
+            }
+            if (position.y, position.x) <= (end.y, end.x) {
+                *position = at;
+            } else if position.y == end.y {
+                *position = Position {
+                    x: at.x.saturating_add(position.x.saturating_sub(end.x)),
+                    y: at.y,
+                };
+            } else {
+                position.y = position.y.saturating_sub(end.y.saturating_sub(at.y));
+            }
+        };
+        for field in &mut self.fields {
+            shift(&mut field.start);
+            shift(&mut field.end);
+        }
+    }
+    // after whole rows went in or out, as the Change says
+    pub fn shift_rows(&mut self, shift: impl Fn(usize) -> usize) {
+        for field in &mut self.fields {
+            field.start.y = shift(field.start.y);
+            field.end.y = shift(field.end.y);
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::config;
+use crate::language::Comment;
+use crate::messages::message;
+
+// files that are all prose, checked throughout rather than in comments
+const PROSE: [&str; 7] = ["txt", "md", "markdown", "rst", "adoc", "org", "tex"];
+// where a dictionary named like `en_US` is looked for after the config's
+// dictionaries directory, as hunspell and myspell install them
+const SYSTEM_DIRECTORIES: [&str; 2] = ["/usr/share/hunspell", "/usr/share/myspell"];
+// the word list most systems have, for when there is no hunspell dictionary
+const SYSTEM_WORDS: &str = "/usr/share/dict/words";
+const MAX_SUGGESTIONS: usize = 10;
+const LETTERS: &str = "abcdefghijklmnopqrstuvwxyz'";
+
+// one of a hunspell .aff file's prefix or suffix rules: `strip` taken off a
+// stem whose start or end matches `condition`, and `add` put on instead
+struct Affix {
+    flag: String,
+    strip: String,
+    add: String,
+    condition: Option<Regex>,
+}
+
+// the words of a hunspell dictionary with what flags they carry, and the
+// affix rules those flags stand for. A plain word list is a dictionary
+// without flags.
+pub struct Dictionary {
+    words: HashMap<String, Vec<String>>,
+    prefixes: Vec<Affix>,
+    suffixes: Vec<Affix>,
+}
+
+#[derive(Clone, Copy)]
+enum Flags {
+    // a character a flag, hunspell's default
+    Single,
+    // two characters
+    Long,
+    // numbers between commas
+    Numeric,
+}
+
+impl Dictionary {
+    // `name` is a path to a .dic file or a word list, or a name like
+    // `en_US` looked for in the dictionaries directory of the config and then
+    // where the system keeps them
+    pub fn load(name: &str) -> Result<Self, String> {
+        let path = find(name).ok_or_else(|| message!("no_dictionary_found", name = name))?;
+        let read = |path: &Path| {
+            let bytes = fs::read(path).map_err(|error| format!("{}: {error}", path.display()))?;
+            Ok::<String, String>(String::from_utf8_lossy(&bytes).into_owned())
+        };
+        let affixes = path.with_extension("aff");
+        let (flags, prefixes, suffixes) =
+            if path.extension().is_some_and(|extension| extension == "dic") && affixes.is_file() {
+                parse_affixes(&read(&affixes)?)
+            } else {
+                (Flags::Single, Vec::new(), Vec::new())
+            };
+        let text = read(&path)?;
+        let mut lines = text.lines();
+        // a .dic file starts with how many words it has
+        if affixes.is_file() {
+            lines.next();
+        }
+        let words = lines
+            .filter_map(|line| line.split_whitespace().next())
+            .map(|entry| {
+                let (word, flags_text) = entry.split_once('/').unwrap_or((entry, ""));
+                (word.to_string(), split_flags(flags_text, flags))
+            })
+            .collect();
+        Ok(Self {
+            words,
+            prefixes,
+            suffixes,
+        })
+    }
+    // whether `word` is spelled right, taking a capital at the start of a
+    // sentence or all capitals to be the same word
+    pub fn check(&self, word: &str) -> bool {
+        let word = word.trim_matches('\'');
+        let word = word.strip_suffix("'s").unwrap_or(word);
+        let lower = word.to_lowercase();
+        let capitalized = word.chars().next().is_some_and(char::is_uppercase);
+        let upper = word.chars().all(|c| !c.is_lowercase());
+        self.known(word)
+            || ((capitalized || upper) && self.known(&lower))
+            || (upper && self.known(&capitalize(&lower)))
+    }
+    // the words one letter off `word` that are spelled right, capitalized
+    // like it
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let capitalized = word.chars().next().is_some_and(char::is_uppercase);
+        let chars: Vec<char> = lower.chars().collect();
+        let text = |chars: &[char]| chars.iter().collect::<String>();
+        let mut candidates = Vec::new();
+        for index in 0..=chars.len() {
+            let (before, after) = chars.split_at(index);
+            // a letter more
+            for letter in LETTERS.chars() {
+                candidates.push(format!("{}{letter}{}", text(before), text(after)));
+            }
+            let Some((_, rest)) = after.split_first() else {
+                continue;
+            };
+            // a letter less, a letter else, or two swapped
+            candidates.push(format!("{}{}", text(before), text(rest)));
+            for letter in LETTERS.chars() {
+                candidates.push(format!("{}{letter}{}", text(before), text(rest)));
+            }
+            if let Some((next, tail)) = rest.split_first() {
+                candidates.push(format!("{}{next}{}{}", text(before), after[0], text(tail)));
+            }
+        }
+        let mut seen = HashSet::new();
+        candidates
+            .into_iter()
+            .filter(|candidate| *candidate != lower && seen.insert(candidate.clone()))
+            .filter(|candidate| self.check(candidate))
+            .map(|candidate| {
+                if capitalized {
+                    capitalize(&candidate)
+                } else {
+                    candidate
+                }
+            })
+            .take(MAX_SUGGESTIONS)
+            .collect()
+    }
+    // in the dictionary as it is, or as a stem with a prefix or suffix its
+    // flags allow
+    fn known(&self, word: &str) -> bool {
+        if self.words.contains_key(word) {
+            return true;
+        }
+        let has = |stem: &str, affix: &Affix| {
+            self.words
+                .get(stem)
+                .is_some_and(|flags| flags.contains(&affix.flag))
+                && affix
+                    .condition
+                    .as_ref()
+                    .is_none_or(|condition| condition.is_match(stem))
+        };
+        let suffixed = self.suffixes.iter().any(|affix| {
+            word.strip_suffix(affix.add.as_str())
+                .filter(|stem| !stem.is_empty())
+                .is_some_and(|stem| has(&format!("{stem}{}", affix.strip), affix))
+        });
+        suffixed
+            || self.prefixes.iter().any(|affix| {
+                word.strip_prefix(affix.add.as_str())
+                    .filter(|stem| !stem.is_empty())
+                    .is_some_and(|stem| has(&format!("{}{stem}", affix.strip), affix))
+            })
+    }
+}
+
+// where the dictionary `name` is
+fn find(name: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(name);
+    if path.is_file() {
+        return Some(path);
+    }
+    let file = format!("{name}.dic");
+    config::dictionaries_dir()
+        .into_iter()
+        .chain(SYSTEM_DIRECTORIES.iter().map(PathBuf::from))
+        .map(|dir| dir.join(&file))
+        .chain([PathBuf::from(SYSTEM_WORDS)])
+        .find(|path| path.is_file())
+}
+
+// the flag type and the PFX and SFX rules of a .aff file; the rest of what
+// hunspell reads there is left alone
+fn parse_affixes(text: &str) -> (Flags, Vec<Affix>, Vec<Affix>) {
+    let mut flags = Flags::Single;
+    let (mut prefixes, mut suffixes) = (Vec::new(), Vec::new());
+    for line in text.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            ["FLAG", "long", ..] => flags = Flags::Long,
+            ["FLAG", "num", ..] => flags = Flags::Numeric,
+            // the header of a rule's lines has its count where the rules
+            // have what to add
+            [kind @ ("PFX" | "SFX"), flag, strip, add, condition, ..] => {
+                let prefix = *kind == "PFX";
+                let zero = |text: &str| {
+                    if text == "0" {
+                        String::new()
+                    } else {
+                        text.to_string()
+                    }
+                };
+                // what is added may carry flags of its own
+                let add = add.split('/').next().unwrap_or_default();
+                let condition = (*condition != ".")
+                    .then(|| {
+                        let pattern = if prefix {
+                            format!("^(?:{condition})")
+                        } else {
+                            format!("(?:{condition})$")
+                        };
+                        Regex::new(&pattern).ok()
+                    })
+                    .flatten();
+                let affix = Affix {
+                    flag: (*flag).to_string(),
+                    strip: zero(strip),
+                    add: zero(add),
+                    condition,
+                };
+                if prefix {
+                    prefixes.push(affix);
+                } else {
+                    suffixes.push(affix);
+                }
+            }
+            _ => (),
+        }
+    }
+    (flags, prefixes, suffixes)
+}
+
+fn split_flags(text: &str, flags: Flags) -> Vec<String> {
+    match flags {
+        Flags::Single => text.chars().map(String::from).collect(),
+        Flags::Long => {
+            let chars: Vec<char> = text.chars().collect();
+            chars.chunks(2).map(|pair| pair.iter().collect()).collect()
+        }
+        Flags::Numeric => text.split(',').map(str::to_string).collect(),
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+// whether files like `file_name` are prose, a file without a name being
+// taken to be
+pub fn is_prose(file_name: Option<&str>) -> bool {
+    file_name.is_none_or(|name| {
+        Path::new(name)
+            .extension()
+            .is_some_and(|extension| PROSE.contains(&&*extension.to_string_lossy()))
+    })
+}
+
+// the grapheme ranges of the misspelled words of `text`, all of it for
+// prose or only what is in a comment when it is written with `comment`
+pub fn misspelled(
+    dictionary: &Dictionary,
+    text: &str,
+    comment: Option<Comment>,
+) -> Vec<Range<usize>> {
+    let checked = match comment {
+        None => 0..text.len(),
+        Some(comment) => match comment_part(text, comment) {
+            Some(range) => range,
+            None => return Vec::new(),
+        },
+    };
+    let mut ranges = Vec::new();
+    let mut word: Option<usize> = None;
+    let graphemes: Vec<(usize, &str)> = text.grapheme_indices(true).collect();
+    let check = |start: usize, end: usize, ranges: &mut Vec<Range<usize>>| {
+        let token = token(&graphemes, start, end);
+        let begin = graphemes.get(start).map_or(text.len(), |(at, _)| *at);
+        let finish = graphemes.get(end).map_or(text.len(), |(at, _)| *at);
+        let word = &text[begin..finish];
+        if word.chars().count() > 1
+            && looks_like_prose(&text[token.clone()])
+            && !dictionary.check(word)
+        {
+            ranges.push(start..end);
+        }
+    };
+    for (index, &(at, grapheme)) in graphemes.iter().enumerate() {
+        let inside = checked.contains(&at);
+        let letter = inside && grapheme.chars().all(|c| c.is_alphabetic() || c == '\'');
+        match (word, letter) {
+            (None, true) => word = Some(index),
+            (Some(start), false) => {
+                check(start, index, &mut ranges);
+                word = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some(start) = word {
+        check(start, graphemes.len(), &mut ranges);
+    }
+    ranges
+}
+
+// the bytes of the whitespace-separated token the word from grapheme
+// `start` to `end` is in
+fn token(graphemes: &[(usize, &str)], start: usize, end: usize) -> Range<usize> {
+    let space = |&(_, grapheme): &(usize, &str)| grapheme.chars().all(char::is_whitespace);
+    let first = graphemes[..start]
+        .iter()
+        .rposition(space)
+        .map_or(0, |index| index.saturating_add(1));
+    let last = graphemes[end..]
+        .iter()
+        .position(space)
+        .map_or(graphemes.len(), |index| index.saturating_add(end));
+    let at = |index: usize| graphemes.get(index).map(|(at, _)| *at);
+    let begin = at(first).unwrap_or(0);
+    let finish = at(last).unwrap_or_else(|| {
+        graphemes
+            .last()
+            .map_or(0, |(at, grapheme)| at.saturating_add(grapheme.len()))
+    });
+    begin..finish
+}
+
+// words in identifiers, paths, addresses and numbers aren't checked
+fn looks_like_prose(token: &str) -> bool {
+    let inner_dot = token
+        .trim_end_matches(|c: char| !c.is_alphanumeric())
+        .contains('.');
+    !(inner_dot
+        || token.contains(['_', '/', '\\', '@', '<', '>', '=', '('])
+        || token.chars().any(|c| c.is_ascii_digit())
+        || token.chars().skip(1).any(char::is_uppercase) && token.chars().any(char::is_lowercase))
+}
+
+// the bytes of `text` that are a comment: after the leader, up to the
+// closer, or all of a row that goes on with a block comment
+fn comment_part(text: &str, comment: Comment) -> Option<Range<usize>> {
+    let trimmed = text.trim_start();
+    let indent = text.len().saturating_sub(trimmed.len());
+    if trimmed.starts_with('*') {
+        return Some(indent..text.len());
+    }
+    let leaders = if comment.leader == "//" {
+        vec!["//", "/*"]
+    } else {
+        vec![comment.leader]
+    };
+    let mut quoted = false;
+    let start = text.char_indices().find_map(|(at, c)| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        let rest = &text[at..];
+        let leader = leaders.iter().find(|leader| rest.starts_with(**leader))?;
+        (!quoted).then(|| at.saturating_add(leader.len()))
+    })?;
+    let end = if comment.closer.is_empty() {
+        text.len()
+    } else {
+        text[start..]
+            .find(comment.closer)
+            .map_or(text.len(), |end| end.saturating_add(start))
+    };
+    Some(start..end)
+}
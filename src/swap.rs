@@ -0,0 +1,34 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// where a document's unsaved text is kept while it is edited, next to its
+// file as `.<name>.swp`, so an editor that didn't exit cleanly leaves it
+// behind to recover from
+pub fn path(file_name: &str) -> PathBuf {
+    let path = Path::new(file_name);
+    let name = path
+        .file_name()
+        .map_or_else(|| file_name.into(), |name| name.to_string_lossy());
+    path.with_file_name(format!(".{name}.swp"))
+}
+
+pub fn exists(file_name: &str) -> bool {
+    path(file_name).is_file()
+}
+
+// written beside the swap file and renamed over it, so a crash while writing
+// leaves the last complete one
+pub fn write(file_name: &str, contents: &str) -> io::Result<()> {
+    let path = path(file_name);
+    let partial = path.with_extension("swp~");
+    fs::write(&partial, contents)?;
+    fs::rename(&partial, &path)
+}
+
+pub fn remove(file_name: &str) -> io::Result<()> {
+    match fs::remove_file(path(file_name)) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
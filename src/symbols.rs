@@ -0,0 +1,217 @@
+use std::cmp;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::Document;
+
+const RUST: &str = r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:async|const|unsafe|extern\s+"[^"]*")\s+)*(fn\s+\w+|struct\s+\w+|enum\s+\w+|trait\s+\w+|union\s+\w+|mod\s+\w+|impl\b[^{;]*)"#;
+const PYTHON: &str = r"^\s*(?:async\s+)?(def\s+\w+|class\s+\w+)";
+const JAVASCRIPT: &str =
+    r"^\s*(?:export\s+)?(?:default\s+)?(?:async\s+)?(function\*?\s+\w+|class\s+\w+)";
+const GO: &str = r"^\s*(func\s+(?:\([^)]*\)\s*)?\w+|type\s+\w+)";
+const C_LIKE: &str = r"^\s*(?:(?:public|private|protected|internal|static|final|abstract|sealed|export)\s+)*(class\s+\w+|struct\s+\w+|interface\s+\w+|enum\s+\w+|namespace\s+[\w:.]+)";
+
+fn declaration_pattern(language: &str) -> Option<&'static Regex> {
+    static RUST_REGEX: OnceLock<Regex> = OnceLock::new();
+    static PYTHON_REGEX: OnceLock<Regex> = OnceLock::new();
+    static JAVASCRIPT_REGEX: OnceLock<Regex> = OnceLock::new();
+    static GO_REGEX: OnceLock<Regex> = OnceLock::new();
+    static C_LIKE_REGEX: OnceLock<Regex> = OnceLock::new();
+    let (cell, pattern) = match language {
+        "Rust" => (&RUST_REGEX, RUST),
+        "Python" => (&PYTHON_REGEX, PYTHON),
+        "JavaScript" => (&JAVASCRIPT_REGEX, JAVASCRIPT),
+        "Go" => (&GO_REGEX, GO),
+        "C" | "C++" | "C#" | "Java" => (&C_LIKE_REGEX, C_LIKE),
+        _ => return None,
+    };
+    Some(cell.get_or_init(|| Regex::new(pattern).expect("invalid declaration pattern")))
+}
+
+// the symbol declared on `line`, e.g. `fn process_keypress` or `impl Editor`
+pub fn declaration(language: &str, line: &str) -> Option<String> {
+    let captures = declaration_pattern(language)?.captures(line)?;
+    let label = captures.get(1)?.as_str();
+    Some(label.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+// the language of a file of `extension` as `declaration` takes it, for
+// files that aren't open and so have no syntax
+pub fn language_of(extension: &str) -> Option<&'static str> {
+    match extension {
+        "rs" => Some("Rust"),
+        "py" => Some("Python"),
+        "js" | "mjs" | "cjs" | "jsx" => Some("JavaScript"),
+        "go" => Some("Go"),
+        "c" | "h" => Some("C"),
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => Some("C++"),
+        "cs" => Some("C#"),
+        "java" => Some("Java"),
+        _ => None,
+    }
+}
+
+// the name a declaration like `fn process_keypress` declares, None for an
+// `impl`, which declares nothing of its own
+pub fn declared_name(label: &str) -> Option<&str> {
+    if label.starts_with("impl") {
+        return None;
+    }
+    let name = label
+        .rsplit(char::is_whitespace)
+        .next()?
+        .trim_matches(|c: char| !c.is_alphanumeric() && c != '_');
+    (!name.is_empty()).then_some(name)
+}
+
+// the chain of declarations enclosing row `y`, outermost first
+pub fn scope(document: &Document, language: &str, y: usize) -> Vec<String> {
+    if declaration_pattern(language).is_none() || document.is_empty() {
+        return Vec::new();
+    }
+    let last = cmp::min(y, document.len().saturating_sub(1));
+    walk(document, language, last, |_| ())
+}
+
+// a declaration in a file's outline
+pub struct Symbol {
+    pub y: usize,
+    // how many declarations it is inside
+    pub depth: usize,
+    pub label: String,
+}
+
+// the declarations of the whole document, in order
+pub fn outline(document: &Document, language: &str) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+    if declaration_pattern(language).is_some() && !document.is_empty() {
+        walk(
+            document,
+            language,
+            document.len().saturating_sub(1),
+            |symbol| symbols.push(symbol),
+        );
+    }
+    symbols
+}
+
+// goes through the rows up to `last`, handing each declaration to
+// `declared`, and returns the ones still open after it
+fn walk(
+    document: &Document,
+    language: &str,
+    last: usize,
+    declared: impl FnMut(Symbol),
+) -> Vec<String> {
+    if language == "Python" {
+        indented_scope(document, language, last, declared)
+    } else {
+        braced_scope(document, language, last, declared)
+    }
+}
+
+fn braced_scope(
+    document: &Document,
+    language: &str,
+    last: usize,
+    mut declared: impl FnMut(Symbol),
+) -> Vec<String> {
+    // (brace depth inside the declaration's body, label)
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    let mut pending: Option<String> = None;
+    let mut depth: usize = 0;
+    for index in 0..=last {
+        let Some(row) = document.row(index) else {
+            break;
+        };
+        let line = row.as_str();
+        if let Some(label) = declaration(language, line) {
+            declared(Symbol {
+                y: index,
+                depth: stack.len(),
+                label: label.clone(),
+            });
+            pending = Some(label);
+        }
+        for c in code_chars(line) {
+            match c {
+                '{' => {
+                    depth = depth.saturating_add(1);
+                    if let Some(label) = pending.take() {
+                        stack.push((depth, label));
+                    }
+                }
+                '}' => {
+                    while stack.last().is_some_and(|(open, _)| *open >= depth) {
+                        stack.pop();
+                    }
+                    depth = depth.saturating_sub(1);
+                }
+                // a declaration without a body, e.g. `struct Unit;`
+                ';' => pending = None,
+                _ => (),
+            }
+        }
+    }
+    stack.into_iter().map(|(_, label)| label).collect()
+}
+
+fn indented_scope(
+    document: &Document,
+    language: &str,
+    last: usize,
+    mut declared: impl FnMut(Symbol),
+) -> Vec<String> {
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    for index in 0..=last {
+        let Some(row) = document.row(index) else {
+            break;
+        };
+        let line = row.as_str();
+        if line.trim().is_empty() {
+            continue;
+        }
+        let indent = line.len().saturating_sub(line.trim_start().len());
+        while stack.last().is_some_and(|(open, _)| *open >= indent) {
+            stack.pop();
+        }
+        if let Some(label) = declaration(language, line) {
+            declared(Symbol {
+                y: index,
+                depth: stack.len(),
+                label: label.clone(),
+            });
+            stack.push((indent, label));
+        }
+    }
+    stack.into_iter().map(|(_, label)| label).collect()
+}
+
+// the characters of a line outside string and char literals and comments
+fn code_chars(line: &str) -> Vec<char> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut code = Vec::new();
+    let mut in_string = false;
+    let mut index = 0;
+    while let Some(&c) = chars.get(index) {
+        let next = chars.get(index.saturating_add(1)).copied();
+        if in_string {
+            match c {
+                '\\' => index = index.saturating_add(1),
+                '"' => in_string = false,
+                _ => (),
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == '/' && next == Some('/') {
+            break;
+        } else if c == '\'' && chars.get(index.saturating_add(2)) == Some(&'\'') {
+            index = index.saturating_add(2);
+        } else {
+            code.push(c);
+        }
+        index = index.saturating_add(1);
+    }
+    code
+}
@@ -0,0 +1,122 @@
+use std::cmp;
+use std::path::Path;
+
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+use crate::Document;
+
+// a block of another language embedded in the document, e.g. a fenced code
+// block in markdown or a script tag in html
+struct Embedded<'a> {
+    close: String,
+    fence: bool,
+    syntax: &'a SyntaxReference,
+}
+
+impl Embedded<'_> {
+    fn closes(&self, line: &str) -> bool {
+        if self.fence {
+            line.trim_start().starts_with(&self.close)
+        } else {
+            line.to_lowercase().contains(&self.close)
+        }
+    }
+}
+
+pub fn for_document<'a>(document: &Document, ps: &'a SyntaxSet) -> &'a SyntaxReference {
+    document
+        .syntax
+        .as_deref()
+        .and_then(|name| ps.find_syntax_by_name(name))
+        .unwrap_or_else(|| detect(document, ps))
+}
+
+// remembers the syntax of a file so it isn't looked up again on every draw.
+// Unnamed documents are looked at afresh, their first line is still coming.
+pub fn remember(document: &mut Document, ps: &SyntaxSet) {
+    if document.syntax.is_none() && document.file_name.is_some() {
+        document.syntax = Some(detect(document, ps).name.clone());
+    }
+}
+
+// by the file's extension, then by its first line (a shebang, `<?xml` and
+// the like), and plain text when neither is known
+fn detect<'a>(document: &Document, ps: &'a SyntaxSet) -> &'a SyntaxReference {
+    document
+        .file_name
+        .as_ref()
+        .and_then(|name| Path::new(name).extension())
+        .and_then(|extension| extension.to_str())
+        .and_then(|extension| ps.find_syntax_by_extension(extension))
+        .or_else(|| {
+            let first_line = document.row(0)?.as_str();
+            ps.find_syntax_by_first_line(first_line)
+        })
+        .unwrap_or_else(|| ps.find_syntax_plain_text())
+}
+
+// resolves the syntax of the first `rows` rows, switching to the embedded
+// language between its delimiters. The delimiter lines themselves keep the
+// syntax of the document.
+pub fn for_rows<'a>(
+    document: &Document,
+    base: &'a SyntaxReference,
+    ps: &'a SyntaxSet,
+    rows: usize,
+) -> Vec<&'a SyntaxReference> {
+    let mut syntaxes = Vec::new();
+    let mut embedded: Option<Embedded> = None;
+    for index in 0..cmp::min(rows, document.len()) {
+        let line = document.row(index).map_or("", |row| row.as_str());
+        if let Some(block) = &embedded {
+            if block.closes(line) {
+                embedded = None;
+                syntaxes.push(base);
+            } else {
+                syntaxes.push(block.syntax);
+            }
+            continue;
+        }
+        syntaxes.push(base);
+        embedded = match base.name.as_str() {
+            "Markdown" => open_fence(line, ps),
+            "HTML" => open_tag(line, ps),
+            _ => None,
+        };
+    }
+    syntaxes
+}
+
+fn open_fence<'a>(line: &str, ps: &'a SyntaxSet) -> Option<Embedded<'a>> {
+    let line = line.trim_start();
+    let fence = ["```", "~~~"]
+        .into_iter()
+        .find(|fence| line.starts_with(fence))?;
+    let syntax = line
+        .trim_start_matches(&fence[..1])
+        .split_whitespace()
+        .next()
+        .and_then(|token| ps.find_syntax_by_token(token))
+        .unwrap_or_else(|| ps.find_syntax_plain_text());
+    Some(Embedded {
+        close: fence.to_string(),
+        fence: true,
+        syntax,
+    })
+}
+
+fn open_tag<'a>(line: &str, ps: &'a SyntaxSet) -> Option<Embedded<'a>> {
+    let line = line.to_lowercase();
+    for (tag, token) in [("script", "js"), ("style", "css")] {
+        let close = format!("</{tag}");
+        if line.contains(&format!("<{tag}")) && !line.contains(&close) {
+            let syntax = ps.find_syntax_by_token(token)?;
+            return Some(Embedded {
+                close,
+                fence: false,
+                syntax,
+            });
+        }
+    }
+    None
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::messages::message;
+use crate::quickfix::Location;
+
+// a search of the tags lists no more than this many
+pub const MAX_LISTED: usize = 1_000;
+
+// where in its file a tag is, as ctags writes it
+enum Address {
+    Line(usize),
+    // the line the tag is on, `^` and `$` marking where it starts and ends
+    Pattern(String),
+}
+
+pub struct Tag {
+    pub name: String,
+    // from the working directory, or absolute
+    file_name: String,
+    address: Address,
+    // ctags's `line:` field, for when the pattern isn't found any more
+    line: Option<usize>,
+}
+
+// the lines of a tags file, as ctags and gtags in its ctags format write
+// them, and when the file was read
+pub struct Tags {
+    // None for the ones the index found, see Index::tags
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    entries: Vec<Tag>,
+}
+
+// the project's tags file, in the working directory or the nearest one
+// above it, up to the git repository the editor was started in
+fn find_file() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    for dir in cwd.ancestors() {
+        let path = dir.join("tags");
+        if path.is_file() {
+            return Some(path);
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    None
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl Tag {
+    // a tag on line `line` of `file_name`, from the working directory
+    pub fn at_line(name: &str, file_name: &str, line: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            file_name: file_name.to_string(),
+            address: Address::Line(line),
+            line: None,
+        }
+    }
+}
+
+impl Tags {
+    // whether there is a tags file to load
+    pub fn exists() -> bool {
+        find_file().is_some()
+    }
+    pub fn load() -> Result<Self, String> {
+        let path = find_file().ok_or_else(|| message!("no_tags_file"))?;
+        let bytes = fs::read(&path)
+            .map_err(|error| message!("cannot_read", file = path.display(), error = error))?;
+        let cwd = env::current_dir().unwrap_or_default();
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let entries = String::from_utf8_lossy(&bytes)
+            .lines()
+            .filter_map(|line| parse(line, dir, &cwd))
+            .collect();
+        Ok(Self {
+            modified: modified(&path),
+            path: Some(path),
+            entries,
+        })
+    }
+    // declarations found some other way than in a tags file
+    pub fn from_entries(entries: Vec<Tag>) -> Self {
+        Self {
+            path: None,
+            modified: None,
+            entries,
+        }
+    }
+    // the entries of `file_name`, or of the files in it for a directory,
+    // replaced by `entries`
+    pub fn replace_file(&mut self, file_name: &str, entries: Vec<Tag>) {
+        let inside = format!("{file_name}/");
+        self.entries
+            .retain(|tag| tag.file_name != file_name && !tag.file_name.starts_with(&inside));
+        self.entries.extend(entries);
+    }
+    // the file was written again since, or another one is nearer now
+    pub fn is_stale(&self) -> bool {
+        find_file() != self.path || self.path.as_deref().and_then(modified) != self.modified
+    }
+    pub fn named(&self, name: &str) -> Vec<&Tag> {
+        self.entries.iter().filter(|tag| tag.name == name).collect()
+    }
+    // those with `query` in their name, any case
+    pub fn matching(&self, query: &str) -> Vec<&Tag> {
+        let query = query.to_lowercase();
+        self.entries
+            .iter()
+            .filter(|tag| tag.name.to_lowercase().contains(&query))
+            .take(MAX_LISTED)
+            .collect()
+    }
+}
+
+// a line of a tags file: the name, the file and the address, tabs between
+// them, then `;"` and more fields, also on tabs. The address can have tabs
+// of its own.
+fn parse(line: &str, dir: &Path, cwd: &Path) -> Option<Tag> {
+    if line.starts_with("!_TAG_") {
+        return None;
+    }
+    let (name, rest) = line.split_once('\t')?;
+    let (file, rest) = rest.split_once('\t')?;
+    let (address, fields) =
+        if let Some(delimiter) = rest.chars().next().filter(|c| *c == '/' || *c == '?') {
+            let mut pattern = String::new();
+            let mut chars = rest[1..].char_indices();
+            let mut end = rest.len();
+            while let Some((index, c)) = chars.next() {
+                match c {
+                    '\\' => pattern.extend(chars.next().map(|(_, c)| c)),
+                    c if c == delimiter => {
+                        end = index.saturating_add(2);
+                        break;
+                    }
+                    c => pattern.push(c),
+                }
+            }
+            (
+                Address::Pattern(pattern),
+                rest.get(end..).unwrap_or_default(),
+            )
+        } else {
+            let digits = rest
+                .split(|c: char| !c.is_ascii_digit())
+                .next()
+                .unwrap_or_default();
+            (Address::Line(digits.parse().ok()?), &rest[digits.len()..])
+        };
+    let line = fields
+        .split('\t')
+        .find_map(|field| field.strip_prefix("line:")?.parse().ok());
+    let path = dir.join(file);
+    let file_name = path
+        .strip_prefix(cwd)
+        .unwrap_or(&path)
+        .to_string_lossy()
+        .into_owned();
+    Some(Tag {
+        name: name.to_string(),
+        file_name,
+        address,
+        line,
+    })
+}
+
+// where each tag is, with the text of its line, reading each file once
+pub fn locations(tags: &[&Tag]) -> Vec<(Location, String)> {
+    let mut files: HashMap<&str, Vec<String>> = HashMap::new();
+    tags.iter()
+        .map(|tag| {
+            let lines = files.entry(&tag.file_name).or_insert_with(|| {
+                fs::read(&tag.file_name)
+                    .map(|bytes| {
+                        String::from_utf8_lossy(&bytes)
+                            .lines()
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            });
+            let index = match &tag.address {
+                Address::Line(line) => Some(line.saturating_sub(1)),
+                Address::Pattern(pattern) => find_line(lines, pattern),
+            };
+            let index = index
+                .or(tag.line.map(|line| line.saturating_sub(1)))
+                .unwrap_or(0);
+            let text = lines.get(index).cloned().unwrap_or_default();
+            let column = text
+                .find(&tag.name)
+                .map_or(1, |at| text[..at].graphemes(true).count().saturating_add(1));
+            let location = Location {
+                file_name: tag.file_name.clone(),
+                line: index.saturating_add(1),
+                column,
+            };
+            (location, text)
+        })
+        .collect()
+}
+
+// the first line the pattern of a tag is, or starts or ends it where it
+// is anchored only at one end
+fn find_line(lines: &[String], pattern: &str) -> Option<usize> {
+    let (start, pattern) = match pattern.strip_prefix('^') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+    let (end, pattern) = match pattern.strip_suffix('$') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+    lines.iter().position(|line| match (start, end) {
+        (true, true) => line == pattern,
+        (true, false) => line.starts_with(pattern),
+        (false, true) => line.ends_with(pattern),
+        (false, false) => line.contains(pattern),
+    })
+}
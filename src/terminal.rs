@@ -1,76 +1,316 @@
+use std::collections::VecDeque;
+use std::env;
 use std::io::{self, stdout, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
 
 use termion::color;
+use termion::event::{Event, Key, MouseEvent};
+use termion::input::{MouseTerminal, TermRead};
 use termion::raw::{IntoRawMode, RawTerminal};
-use termion::input::TermRead;
-use termion::event::Key;
 
+use crate::messages::message;
+use crate::palette;
 use crate::Position;
 
+// the columns and rows --batch goes by, having no screen to measure
+const HEADLESS_SIZE: (u16, u16) = (80, 24);
+
+// the text's part of the screen, and which bars fit below it
+#[derive(Clone, Copy, PartialEq)]
 pub struct Size {
     pub width: u16,
     pub height: u16,
+    pub status_bar: bool,
+    pub message_bar: bool,
+}
+
+impl Size {
+    // the bars only get rows the text can spare, the message bar before the
+    // status bar as prompts are asked there
+    fn of_screen((width, height): (u16, u16)) -> Self {
+        let message_bar = height >= 2;
+        let status_bar = height >= 3;
+        Self {
+            width,
+            height: height
+                .saturating_sub(u16::from(message_bar))
+                .saturating_sub(u16::from(status_bar)),
+            status_bar,
+            message_bar,
+        }
+    }
+}
+
+// a navigation key pressed together with modifiers, which termion itself
+// doesn't decode
+#[derive(Clone, Copy)]
+pub struct Modified {
+    pub key: Key,
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+}
+
+// how many colors the terminal draws, theme colors are brought down to
+// the nearest it has
+#[derive(Clone, Copy, PartialEq)]
+pub enum Colors {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+#[derive(Clone, Copy)]
+pub enum Input {
+    Key(Key),
+    Modified(Modified),
+    Mouse(MouseEvent),
 }
 
 pub struct Terminal {
     size: Size,
-    _stdout: RawTerminal<std::io::Stdout>,
+    // None for --batch, which draws nothing and reads no keys of its own
+    stdout: Option<MouseTerminal<RawTerminal<std::io::Stdout>>>,
+    inputs: Receiver<Result<Input, std::io::Error>>,
+    // an input read ahead while draining a paste, to take before more
+    pending: VecDeque<Input>,
+    // keys given with `--keys`, taken one at a time as if typed, which a
+    // paste never drains
+    injected: VecDeque<Key>,
 }
 
 impl Terminal {
+    #[allow(clippy::should_implement_trait)]
     pub fn default() -> Result<Self, std::io::Error> {
         let size = termion::terminal_size()?;
+        let stdout = MouseTerminal::from(stdout().into_raw_mode()?);
+        // keys are read on their own thread so the editor can wake up without
+        // a keypress, e.g. to notice that the file changed on disk
+        let (sender, inputs) = mpsc::channel();
+        thread::spawn(move || {
+            for event in io::stdin().events() {
+                let input = match event {
+                    Ok(Event::Key(key)) => Ok(Input::Key(key)),
+                    Ok(Event::Unsupported(bytes)) => match parse_modified(&bytes) {
+                        Some(modified) => Ok(Input::Modified(modified)),
+                        None => continue,
+                    },
+                    Ok(Event::Mouse(event)) => Ok(Input::Mouse(event)),
+                    Err(error) => Err(error),
+                };
+                if sender.send(input).is_err() {
+                    break;
+                }
+            }
+        });
         Ok(Self {
-            size: Size {
-                width: size.0,
-                height: size.1.saturating_sub(2)
-            },
-            _stdout: stdout().into_raw_mode()?,
+            size: Size::of_screen(size),
+            stdout: Some(stdout),
+            inputs,
+            pending: VecDeque::new(),
+            injected: VecDeque::new(),
         })
     }
+    // a screen of the usual size that is never drawn, with no input but the
+    // keys injected
+    pub fn headless() -> Self {
+        let (_, inputs) = mpsc::channel();
+        Self {
+            size: Size::of_screen(HEADLESS_SIZE),
+            stdout: None,
+            inputs,
+            pending: VecDeque::new(),
+            injected: VecDeque::new(),
+        }
+    }
     pub fn size(&self) -> &Size {
         &self.size
     }
+    // takes in a resize of the terminal, true if there was one
+    pub fn refresh_size(&mut self) -> bool {
+        let Ok(size) = termion::terminal_size().map(Size::of_screen) else {
+            return false;
+        };
+        let resized = size != self.size;
+        self.size = size;
+        resized
+    }
     pub fn clear_screen() {
         print!("{}", termion::clear::All);
     }
     pub fn cursor_position(cursor_position: &Position) {
-        let Position {mut x, mut y} = cursor_position;
+        let Position { mut x, mut y } = cursor_position;
         x = x.saturating_add(1);
         y = y.saturating_add(1);
+        #[allow(clippy::cast_possible_truncation)]
         let x = x as u16;
+        #[allow(clippy::cast_possible_truncation)]
         let y = y as u16;
         print!("{}", termion::cursor::Goto(x, y));
     }
     pub fn flush() -> Result<(), std::io::Error> {
         io::stdout().flush()
     }
-    pub fn read_key() -> Result<Key, std::io::Error> {
-        loop {            
-            if let Some(key) = io::stdin().lock().keys().next() {            
-                return key;            
-            }            
+    // waits for a plain key, as the prompts only deal in those
+    pub fn read_key(&mut self) -> Result<Key, std::io::Error> {
+        loop {
+            let input = match self.pending.pop_front() {
+                Some(input) => input,
+                None => match self.injected.pop_front() {
+                    Some(key) => Input::Key(key),
+                    None => self.inputs.recv().map_err(|_| input_closed())??,
+                },
+            };
+            if let Input::Key(key) = input {
+                return Ok(key);
+            }
+        }
+    }
+    pub fn poll_input(&mut self, timeout: Duration) -> Result<Option<Input>, std::io::Error> {
+        if let Some(input) = self.pending.pop_front() {
+            return Ok(Some(input));
+        }
+        if let Some(key) = self.injected.pop_front() {
+            return Ok(Some(Input::Key(key)));
+        }
+        match self.inputs.recv_timeout(timeout) {
+            Ok(input) => input.map(Some),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            // a headless terminal's only input is what was injected
+            Err(RecvTimeoutError::Disconnected) if self.stdout.is_none() => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(input_closed()),
         }
     }
+    // collects the characters that have already arrived, which is what a
+    // paste looks like to a terminal application
+    pub fn read_pending_chars(&mut self) -> Result<String, std::io::Error> {
+        let mut text = String::new();
+        // injected keys are typed one at a time, before what arrived
+        if !self.injected.is_empty() {
+            return Ok(text);
+        }
+        while let Some(input) = self.poll_input(Duration::ZERO)? {
+            if let Input::Key(Key::Char(c)) = input {
+                text.push(c);
+            } else {
+                self.pending.push_front(input);
+                break;
+            }
+        }
+        Ok(text)
+    }
+    // handled as if they were typed, before the keys that are
+    pub fn inject(&mut self, keys: &[Key]) {
+        self.injected.extend(keys);
+    }
+    // keys injected and not yet taken
+    pub fn has_pending(&self) -> bool {
+        !self.injected.is_empty()
+    }
     pub fn cursor_hide() {
-        print!("{}", termion::cursor::Hide)
+        print!("{}", termion::cursor::Hide);
     }
     pub fn cursor_show() {
-        print!("{}", termion::cursor::Show)
+        print!("{}", termion::cursor::Show);
+    }
+    // pushes the window title on xterm's title stack, for restore_title to
+    // put back
+    pub fn save_title() {
+        print!("\x1b[22;0t");
+    }
+    pub fn restore_title() {
+        print!("\x1b[23;0t");
+    }
+    // OSC 0, the window and icon title. Control characters would end the
+    // escape early, a file name can have them.
+    pub fn set_title(title: &str) {
+        let title: String = title.chars().filter(|c| !c.is_control()).collect();
+        print!("\x1b]0;{title}\x07");
     }
     pub fn clear_current_line() {
-        print!("{}", termion::clear::CurrentLine)
+        print!("{}", termion::clear::CurrentLine);
+    }
+    // COLORTERM is how terminals say they draw any color, TERM names the
+    // palette of the rest
+    pub fn colors() -> Colors {
+        static COLORS: OnceLock<Colors> = OnceLock::new();
+        *COLORS.get_or_init(|| {
+            let colorterm = env::var("COLORTERM").unwrap_or_default();
+            let term = env::var("TERM").unwrap_or_default();
+            if matches!(colorterm.as_str(), "truecolor" | "24bit") || term.ends_with("-direct") {
+                Colors::TrueColor
+            } else if term.contains("256color") {
+                Colors::Ansi256
+            } else {
+                Colors::Ansi16
+            }
+        })
+    }
+    pub fn fg(color: color::Rgb) -> String {
+        match Self::colors() {
+            Colors::TrueColor => color::Fg(color).to_string(),
+            Colors::Ansi256 => color::Fg(color::AnsiValue(palette::nearest_256(color))).to_string(),
+            Colors::Ansi16 => ansi_16(30, palette::nearest_16(color)),
+        }
+    }
+    pub fn bg(color: color::Rgb) -> String {
+        match Self::colors() {
+            Colors::TrueColor => color::Bg(color).to_string(),
+            Colors::Ansi256 => color::Bg(color::AnsiValue(palette::nearest_256(color))).to_string(),
+            Colors::Ansi16 => ansi_16(40, palette::nearest_16(color)),
+        }
     }
     pub fn set_bg_color(color: color::Rgb) {
-        print!("{}", color::Bg(color));
+        print!("{}", Self::bg(color));
     }
     pub fn reset_bg_color() {
         print!("{}", color::Bg(color::Reset));
     }
     pub fn set_fg_color(color: color::Rgb) {
-        print!("{}", color::Fg(color));
+        print!("{}", Self::fg(color));
     }
     pub fn reset_fg_color() {
         print!("{}", color::Fg(color::Reset));
     }
-}
\ No newline at end of file
+}
+
+// the escape for one of the 16 colors, whose bright half is 60 codes on
+// from the plain half
+fn ansi_16(base: u8, index: u8) -> String {
+    let code = if index < 8 {
+        base.saturating_add(index)
+    } else {
+        base.saturating_add(60).saturating_add(index - 8)
+    };
+    format!("\x1b[{code}m")
+}
+
+fn input_closed() -> std::io::Error {
+    std::io::Error::new(io::ErrorKind::UnexpectedEof, message!("input_closed"))
+}
+
+// xterm reports modified navigation keys as `ESC [ 1 ; <modifier> <key>`,
+// where the modifier is one more than a bit set of shift, alt and ctrl
+fn parse_modified(bytes: &[u8]) -> Option<Modified> {
+    let sequence = std::str::from_utf8(bytes).ok()?.strip_prefix("\x1b[1;")?;
+    let (modifier, key) = sequence.split_at(sequence.len().checked_sub(1)?);
+    let bits = modifier.parse::<u8>().ok()?.checked_sub(1)?;
+    let key = match key {
+        "A" => Key::Up,
+        "B" => Key::Down,
+        "C" => Key::Right,
+        "D" => Key::Left,
+        "H" => Key::Home,
+        "F" => Key::End,
+        _ => return None,
+    };
+    Some(Modified {
+        key,
+        shift: bits & 1 != 0,
+        alt: bits & 2 != 0,
+        ctrl: bits & 4 != 0,
+    })
+}
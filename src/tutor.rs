@@ -0,0 +1,132 @@
+use crate::config::{Command, Config};
+use crate::messages::message;
+use crate::{Document, Row};
+
+// the tutorial's first line, by which it is told from other documents
+const TITLE: &str = "Welcome to the tutorial!";
+// the exercises, each done once the document holds the line it asks for, or
+// no longer holds the one it asks to remove
+const CHECKPOINTS: [Checkpoint; 4] = [
+    Checkpoint::Present("--> The quick brown fox jumps over the lazy dog."),
+    Checkpoint::Present("--> There is some text missing from this line."),
+    Checkpoint::Absent("--> Remove this whole line."),
+    Checkpoint::Present("--> a dog, another dog, and one more dog"),
+];
+
+enum Checkpoint {
+    Present(&'static str),
+    Absent(&'static str),
+}
+
+// the exercises done so far in the tutorial that `--tutor` opens
+pub struct Tutor {
+    done: [bool; CHECKPOINTS.len()],
+}
+
+impl Tutor {
+    pub fn new() -> Self {
+        Self {
+            done: [false; CHECKPOINTS.len()],
+        }
+    }
+    // looks at the document after an edit, saying so when an exercise was
+    // just done
+    pub fn check(&mut self, document: &Document) -> Option<String> {
+        if document.row(0).map(Row::as_str) != Some(TITLE) {
+            return None;
+        }
+        let mut finished = None;
+        for (number, (checkpoint, done)) in (1..).zip(CHECKPOINTS.iter().zip(&mut self.done)) {
+            if *done || !checkpoint.reached(document) {
+                continue;
+            }
+            *done = true;
+            finished = Some(number);
+        }
+        let number: usize = finished?;
+        let left = self.done.iter().filter(|done| !**done).count();
+        Some(if left == 0 {
+            message!("tutorial_done")
+        } else {
+            message!("exercise_done", number = number, left = left)
+        })
+    }
+}
+
+impl Checkpoint {
+    fn reached(&self, document: &Document) -> bool {
+        let has = |line: &str| {
+            (0..document.len()).any(|y| document.row(y).is_some_and(|row| row.as_str() == line))
+        };
+        match self {
+            Self::Present(line) => has(line),
+            Self::Absent(line) => !has(line),
+        }
+    }
+}
+
+// the tutorial, with the keys as they are bound in `config`
+pub fn text(config: &Config) -> String {
+    let key = |command| config.key_name(command);
+    let typing = if config.modal {
+        message!("tutorial_typing_modal")
+    } else {
+        message!("tutorial_typing")
+    };
+    format!(
+        "\
+{TITLE}
+
+This is a scratch buffer, nothing you do here touches a file. Each exercise
+shows a line starting with --> to change. The message bar tells you when you
+got it right.
+
+Lesson 1: moving around
+
+The arrow keys move the cursor, Page Up and Page Down a screen at a time,
+Home and End to the start and end of a line. Move down to lesson 2.
+
+Lesson 2: deleting characters
+
+Backspace deletes the character before the cursor, Delete the one under it.
+Take out the extra letters:
+
+--> Thhe quick brownn fox jumpss over the lazy dogg.
+
+Lesson 3: inserting text
+
+{typing}
+Add what is missing:
+
+--> There is text misng this .
+
+If you slip, {undo} undoes and {redo} redoes.
+
+Lesson 4: deleting lines
+
+{delete_row} deletes the line the cursor is on. Delete the line below:
+
+--> Remove this whole line.
+
+Lesson 5: find and replace
+
+{find} looks for text, {replace} replaces it. Replace every cat with a dog:
+
+--> a cat, another cat, and one more cat
+
+Lesson 6: saving and quitting
+
+{save} saves a file and {quit} quits, asking first if there are unsaved
+changes. {open} opens a file, and the rest of the keys are listed in the
+message bar when the editor starts.
+",
+        undo = key(Command::Undo),
+        redo = key(Command::Redo),
+        delete_row = key(Command::DeleteRow),
+        find = key(Command::Find),
+        replace = key(Command::Replace),
+        save = key(Command::Save),
+        quit = key(Command::Quit),
+        open = key(Command::Open),
+    )
+}
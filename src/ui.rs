@@ -0,0 +1,147 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use syntect::highlighting::{self, Theme};
+use termion::color;
+
+use crate::config::Rgb;
+use crate::messages::message;
+
+// what picks the colors that follow the syntax theme
+pub const DEFAULT: &str = "default";
+// the UI themes that come with the editor, for low vision and for color
+// blindness, each with a syntax theme of the same name
+pub const HIGH_CONTRAST: &str = "high-contrast";
+pub const COLORBLIND: &str = "colorblind";
+// for themes that don't say what their foreground and background are
+const DEFAULT_STATUS_BG_COLOR: [u8; 3] = [239, 239, 239];
+const DEFAULT_STATUS_FG_COLOR: [u8; 3] = [63, 63, 63];
+
+// the colors of everything around the text, from a TOML file in the
+// ui-themes directory. What it leaves out follows the syntax theme.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiTheme {
+    // the syntax theme's colors, the other way around, unless set
+    pub status_bg: Option<Rgb>,
+    pub status_fg: Option<Rgb>,
+    // the terminal's own colors unless set
+    pub message_bg: Option<Rgb>,
+    pub message_fg: Option<Rgb>,
+    pub gutter_fg: Option<Rgb>,
+    // the text's colors swapped unless set
+    pub selection_bg: Option<Rgb>,
+    pub selection_fg: Option<Rgb>,
+    pub match_bg: Option<Rgb>,
+    // the text's own color unless set
+    pub match_fg: Option<Rgb>,
+    // behind invisible and lookalike characters, orange unless set
+    pub warning_bg: Option<Rgb>,
+    // behind the bracket at the cursor and its pair, gray unless set
+    pub bracket_bg: Option<Rgb>,
+    // the glyphs `show_invisibles` draws, the gutter's color unless set
+    pub invisible_fg: Option<Rgb>,
+    // the underlined misspelled words, red unless set
+    pub misspelled_fg: Option<Rgb>,
+    // the cursor's line isn't marked unless set
+    pub current_line_bg: Option<Rgb>,
+}
+
+impl UiTheme {
+    pub fn load(name: &str) -> Result<Self, String> {
+        if name == DEFAULT {
+            return Ok(Self::default());
+        }
+        if let Some(theme) = built_in(name) {
+            return Ok(theme);
+        }
+        let path = dir()
+            .ok_or_else(|| message!("no_home"))?
+            .join(format!("{name}.toml"));
+        let text =
+            fs::read_to_string(&path).map_err(|_| message!("unknown_ui_theme", name = name))?;
+        toml::from_str(&text).map_err(|error| format!("{}: {}", path.display(), error.message()))
+    }
+    // the status bar's background and foreground under `theme`
+    pub fn status_colors(&self, theme: &Theme) -> (color::Rgb, color::Rgb) {
+        let rgb = |color: Option<highlighting::Color>, default: [u8; 3]| {
+            color.map_or(Rgb::from(default), |color| {
+                Rgb::from([color.r, color.g, color.b])
+            })
+        };
+        let bg = self
+            .status_bg
+            .unwrap_or_else(|| rgb(theme.settings.foreground, DEFAULT_STATUS_BG_COLOR));
+        let fg = self
+            .status_fg
+            .unwrap_or_else(|| rgb(theme.settings.background, DEFAULT_STATUS_FG_COLOR));
+        (bg.0, fg.0)
+    }
+}
+
+// the UI themes there are files for, by name
+pub fn names() -> Vec<String> {
+    let Some(entries) = dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return [COLORBLIND, DEFAULT, HIGH_CONTRAST]
+            .map(ToString::to_string)
+            .to_vec();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?.strip_suffix(".toml")?;
+            Some(name.to_string())
+        })
+        .chain([DEFAULT, HIGH_CONTRAST, COLORBLIND].map(ToString::to_string))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn built_in(name: &str) -> Option<UiTheme> {
+    let rgb = |color: [u8; 3]| Some(Rgb::from(color));
+    match name {
+        HIGH_CONTRAST => Some(UiTheme {
+            status_bg: rgb([255, 255, 255]),
+            status_fg: rgb([0, 0, 0]),
+            message_bg: rgb([0, 0, 0]),
+            message_fg: rgb([255, 255, 255]),
+            gutter_fg: rgb([255, 255, 0]),
+            selection_bg: rgb([255, 255, 255]),
+            selection_fg: rgb([0, 0, 0]),
+            match_bg: rgb([255, 255, 0]),
+            match_fg: rgb([0, 0, 0]),
+            warning_bg: rgb([255, 0, 255]),
+            bracket_bg: rgb([0, 0, 255]),
+            invisible_fg: rgb([0, 255, 255]),
+            misspelled_fg: rgb([255, 90, 90]),
+            current_line_bg: rgb([48, 48, 48]),
+        }),
+        // blue, yellow and vermilion from the Okabe-Ito colors
+        COLORBLIND => Some(UiTheme {
+            status_bg: rgb([0, 114, 178]),
+            status_fg: rgb([255, 255, 255]),
+            message_bg: None,
+            message_fg: None,
+            gutter_fg: rgb([153, 153, 153]),
+            selection_bg: rgb([0, 114, 178]),
+            selection_fg: rgb([255, 255, 255]),
+            match_bg: rgb([240, 228, 66]),
+            match_fg: rgb([0, 0, 0]),
+            warning_bg: rgb([213, 94, 0]),
+            bracket_bg: rgb([86, 180, 233]),
+            invisible_fg: rgb([153, 153, 153]),
+            misspelled_fg: rgb([213, 94, 0]),
+            current_line_bg: None,
+        }),
+        _ => None,
+    }
+}
+
+fn dir() -> Option<PathBuf> {
+    let home = env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(".config/byron-editor/ui-themes"))
+}
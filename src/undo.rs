@@ -0,0 +1,273 @@
+use std::time::{Duration, Instant};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::Position;
+
+// a single reversible change to a document. Text may span rows, so splitting
+// a row is an insert of "\n" and joining two rows is a delete of one.
+#[derive(Clone)]
+pub enum Operation {
+    Insert { at: Position, text: String },
+    Delete { at: Position, text: String },
+    InsertRow { at: usize, text: String },
+    DeleteRow { at: usize, text: String },
+}
+
+impl Operation {
+    pub fn inverse(&self) -> Self {
+        match self {
+            Self::Insert { at, text } => Self::Delete {
+                at: *at,
+                text: text.clone(),
+            },
+            Self::Delete { at, text } => Self::Insert {
+                at: *at,
+                text: text.clone(),
+            },
+            Self::InsertRow { at, text } => Self::DeleteRow {
+                at: *at,
+                text: text.clone(),
+            },
+            Self::DeleteRow { at, text } => Self::InsertRow {
+                at: *at,
+                text: text.clone(),
+            },
+        }
+    }
+    // where the text of an insert or delete starts and ends
+    fn span(&self) -> Option<(Position, Position)> {
+        let (Self::Insert { at, text } | Self::Delete { at, text }) = self else {
+            return None;
+        };
+        let last_line = text.rsplit('\n').next().unwrap_or_default();
+        let newlines = text.matches('\n').count();
+        let x = last_line.graphemes(true).count();
+        let end = if newlines == 0 {
+            Position {
+                x: at.x.saturating_add(x),
+                y: at.y,
+            }
+        } else {
+            Position {
+                x,
+                y: at.y.saturating_add(newlines),
+            }
+        };
+        Some((*at, end))
+    }
+}
+
+// edits made by one command, undone and redone together
+type Step = Vec<Operation>;
+
+// a state of the document, the step that made it from its parent's
+struct Node {
+    parent: usize,
+    step: Step,
+    made: Instant,
+    // the child redo goes to: the one undone from last, or made last
+    redo: Option<usize>,
+}
+
+// the states the document has been in, as a tree: an edit after undoing
+// starts a branch beside the undone steps rather than throwing them away.
+// State 0 is the document as it was opened, the rest are numbered in the
+// order they were made.
+pub struct History {
+    nodes: Vec<Node>,
+    current: usize,
+    // when the last character was typed, and where typing goes on from it
+    typing: Option<(Instant, Position)>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node {
+                parent: 0,
+                step: Vec::new(),
+                made: Instant::now(),
+                redo: None,
+            }],
+            current: 0,
+            typing: None,
+        }
+    }
+}
+
+// a state as the tree is drawn, with how many branches in it is
+pub struct State {
+    pub number: usize,
+    pub parent: usize,
+    pub made: Instant,
+    pub level: usize,
+}
+
+impl History {
+    pub fn record(&mut self, step: Step) {
+        self.typing = None;
+        if step.is_empty() {
+            return;
+        }
+        let state = self.nodes.len();
+        self.nodes.push(Node {
+            parent: self.current,
+            step,
+            made: Instant::now(),
+            redo: None,
+        });
+        self.nodes[self.current].redo = Some(state);
+        self.current = state;
+    }
+    // records a character typed or deleted. It joins the step before it when
+    // that was typed too, less than `window` ago, and this one carries on
+    // where it left off; moving the cursor away in between starts a new step.
+    pub fn record_typed(&mut self, step: Step, window: Duration) {
+        let Some((start, end)) = step.last().and_then(Operation::span) else {
+            self.record(step);
+            return;
+        };
+        let deleted = matches!(step.last(), Some(Operation::Delete { .. }));
+        // a backspace ends where typing would go on, a delete starts there
+        let carries_on = self.typing.is_some_and(|(time, next)| {
+            time.elapsed() < window && (next == start || (deleted && next == end))
+        });
+        // a state with branches after it stays as they started from
+        let last = &mut self.nodes[self.current];
+        if carries_on && self.current != 0 && last.redo.is_none() {
+            last.step.extend(step);
+            last.made = Instant::now();
+        } else {
+            self.record(step);
+        }
+        let next = if deleted { start } else { end };
+        self.typing = Some((Instant::now(), next));
+    }
+    // the operations that revert the last step, in the order to apply them
+    pub fn undo(&mut self) -> Option<Step> {
+        self.typing = None;
+        if self.current == 0 {
+            return None;
+        }
+        let node = &self.nodes[self.current];
+        let inverse = node.step.iter().rev().map(Operation::inverse).collect();
+        let parent = node.parent;
+        self.nodes[parent].redo = Some(self.current);
+        self.current = parent;
+        Some(inverse)
+    }
+    pub fn redo(&mut self) -> Option<Step> {
+        self.typing = None;
+        let child = self.nodes[self.current].redo?;
+        self.current = child;
+        Some(self.nodes[child].step.clone())
+    }
+    // the operations that take the document from the current state to
+    // `state`, undoing up to where their branches meet and redoing down
+    // from there, None for a state there isn't
+    pub fn jump(&mut self, state: usize) -> Option<Step> {
+        if state >= self.nodes.len() {
+            return None;
+        }
+        self.typing = None;
+        let up = self.ancestors(self.current);
+        let down = self.ancestors(state);
+        let meet = *up.iter().find(|node| down.contains(node))?;
+        let mut operations = Vec::new();
+        for &node in up.iter().take_while(|node| **node != meet) {
+            operations.extend(self.nodes[node].step.iter().rev().map(Operation::inverse));
+        }
+        let down: Vec<usize> = down.into_iter().take_while(|node| *node != meet).collect();
+        for &node in down.iter().rev() {
+            operations.extend(self.nodes[node].step.iter().cloned());
+            let parent = self.nodes[node].parent;
+            self.nodes[parent].redo = Some(node);
+        }
+        self.current = state;
+        Some(operations)
+    }
+    // `state` and the ones before it, back to state 0
+    fn ancestors(&self, mut state: usize) -> Vec<usize> {
+        let mut ancestors = vec![state];
+        while state != 0 {
+            state = self.nodes[state].parent;
+            ancestors.push(state);
+        }
+        ancestors
+    }
+    pub fn current(&self) -> usize {
+        self.current
+    }
+    // every state, each followed by the ones made from it, oldest first.
+    // The first state made from another carries on its level, the ones
+    // made from it after that branch off a level further in.
+    pub fn states(&self) -> Vec<State> {
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); self.nodes.len()];
+        for (state, node) in self.nodes.iter().enumerate().skip(1) {
+            children[node.parent].push(state);
+        }
+        let mut states = Vec::new();
+        let mut to_draw = vec![(0, 0)];
+        while let Some((state, level)) = to_draw.pop() {
+            let node = &self.nodes[state];
+            states.push(State {
+                number: state,
+                parent: node.parent,
+                made: node.made,
+                level,
+            });
+            for (index, &child) in children[state].iter().enumerate().rev() {
+                to_draw.push((
+                    child,
+                    if index == 0 {
+                        level
+                    } else {
+                        level.saturating_add(1)
+                    },
+                ));
+            }
+        }
+        states
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(x: usize, text: &str) -> Step {
+        vec![Operation::Insert {
+            at: Position { x, y: 0 },
+            text: text.to_string(),
+        }]
+    }
+
+    #[test]
+    fn an_edit_after_undoing_keeps_the_undone_branch() {
+        let mut history = History::default();
+        history.record(insert(0, "a"));
+        history.record(insert(1, "b"));
+        history.undo();
+        history.record(insert(1, "c"));
+        let states: Vec<(usize, usize)> = history
+            .states()
+            .iter()
+            .map(|state| (state.number, state.level))
+            .collect();
+        assert_eq!(states, [(0, 0), (1, 0), (2, 0), (3, 1)]);
+        // from "ac" over to "ab": undo the c, then redo the b
+        let step = history.jump(2).expect("state 2 is there");
+        assert!(matches!(&step[..], [
+            Operation::Delete { text: c, .. },
+            Operation::Insert { text: b, .. },
+        ] if c == "c" && b == "b"));
+        assert_eq!(history.current(), 2);
+        // redo follows the branch jumped along
+        history.undo();
+        assert!(
+            matches!(&history.redo().expect("a redo")[..], [Operation::Insert { text, .. }] if text == "b")
+        );
+        assert!(history.jump(4).is_none());
+    }
+}
@@ -0,0 +1,73 @@
+use unicode_width::UnicodeWidthStr;
+
+// zero-width characters, byte order marks and bidirectional controls that
+// render as nothing but still change what the compiler sees
+pub fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00ad}'
+            | '\u{180e}'
+            | '\u{200b}'..='\u{200f}'
+            | '\u{202a}'..='\u{202e}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{feff}'
+    )
+}
+
+// characters that look like ascii letters, spaces or punctuation
+pub fn is_confusable(c: char) -> bool {
+    matches!(
+        c,
+        // cyrillic
+        'а' | 'в' | 'е' | 'к' | 'м' | 'н' | 'о' | 'р' | 'с' | 'т' | 'у' | 'х'
+            | 'А' | 'В' | 'Е' | 'К' | 'М' | 'Н' | 'О' | 'Р' | 'С' | 'Т' | 'Х'
+            | 'ѕ' | 'і' | 'ј' | 'ԁ' | 'ԛ' | 'ԝ' | 'Ѕ' | 'І' | 'Ј'
+            // greek
+            | 'Α' | 'Β' | 'Ε' | 'Ζ' | 'Η' | 'Ι' | 'Κ' | 'Μ' | 'Ν' | 'Ο' | 'Ρ' | 'Τ' | 'Υ' | 'Χ'
+            | 'ο' | 'ν' | 'ρ'
+            // unusual spaces
+            | '\u{2000}'..='\u{200a}'
+            | '\u{202f}'
+            | '\u{205f}'
+            | '\u{3000}'
+            // fullwidth ascii
+            | '\u{ff01}'..='\u{ff5e}'
+            // minus sign and greek question mark
+            | '\u{2212}'
+            | '\u{037e}'
+    )
+}
+
+// lookalike letters are only flagged next to ascii ones, so text written in
+// those scripts isn't lit up from end to end
+pub fn is_suspicious(c: char, previous: Option<char>, next: Option<char>) -> bool {
+    if is_invisible(c) {
+        return true;
+    }
+    if !is_confusable(c) {
+        return false;
+    }
+    !c.is_alphabetic()
+        || previous.is_some_and(|c| c.is_ascii_alphabetic())
+        || next.is_some_and(|c| c.is_ascii_alphabetic())
+}
+
+// codepoints, names, utf-8 bytes and display width of a grapheme
+pub fn describe(grapheme: &str) -> String {
+    let codepoints: Vec<String> = grapheme
+        .chars()
+        .map(|c| {
+            let name = unicode_names2::name(c)
+                .map_or_else(|| "<unnamed>".to_string(), |name| name.to_string());
+            format!("U+{:04X} {name}", u32::from(c))
+        })
+        .collect();
+    let bytes: Vec<String> = grapheme.bytes().map(|byte| format!("{byte:02x}")).collect();
+    format!(
+        "{} | UTF-8: {} | width {}",
+        codepoints.join(" + "),
+        bytes.join(" "),
+        UnicodeWidthStr::width(grapheme)
+    )
+}
@@ -0,0 +1,59 @@
+use std::path::Path;
+use std::process::Command;
+
+use uuid::Uuid;
+
+use crate::config::Config;
+
+pub const NAMES: [&str; 5] = ["date", "time", "file", "author", "uuid"];
+
+// the value of a built-in variable, None if it is unknown or has no value
+pub fn value(name: &str, file_name: Option<&str>, config: &Config) -> Option<String> {
+    match name {
+        "date" => date(&config.date_format),
+        "time" => date(&config.time_format),
+        "file" => {
+            let file_name = Path::new(file_name?).file_name()?;
+            Some(file_name.to_string_lossy().into_owned())
+        }
+        "author" => Some(config.author.clone()).filter(|author| !author.is_empty()),
+        "uuid" => Some(Uuid::new_v4().to_string()),
+        _ => None,
+    }
+}
+
+// replaces each `${name}` in `template`, leaving unknown ones as they are
+pub fn expand(template: &str, file_name: Option<&str>, config: &Config) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start.saturating_add(2)..];
+        let Some(end) = after.find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = &after[..end];
+        if let Some(value) = value(name, file_name, config) {
+            result.push_str(&value);
+        } else {
+            result.push_str(&rest[start..start.saturating_add(end).saturating_add(3)]);
+        }
+        rest = &after[end.saturating_add(1)..];
+    }
+    result.push_str(rest);
+    result
+}
+
+// the local date and time in a strftime `format`, as `date` prints it
+fn date(format: &str) -> Option<String> {
+    let output = Command::new("date")
+        .arg(format!("+{format}"))
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    Some(text.trim_end_matches('\n').to_string())
+}
@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+// the files open in buffers, watched through the system's file events. The
+// directories they are in are what is watched, as a file saved by writing a
+// new one and renaming it over the old is a new file to a watch on it.
+pub struct Watch {
+    watcher: RecommendedWatcher,
+    // the paths events came for, from the watcher's thread
+    events: Receiver<PathBuf>,
+    directories: HashSet<PathBuf>,
+}
+
+impl Watch {
+    pub fn start() -> Result<Self, String> {
+        let (sender, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            // reading a file, as the index does, changes nothing
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+            for path in event.paths {
+                sender.send(path).ok();
+            }
+        })
+        .map_err(|error| error.to_string())?;
+        Ok(Self {
+            watcher,
+            events,
+            directories: HashSet::new(),
+        })
+    }
+    // watches the directories of `files` and stops watching the others
+    pub fn follow<'a>(&mut self, files: impl Iterator<Item = &'a str>) -> Result<(), String> {
+        let wanted: HashSet<PathBuf> = files
+            .filter_map(key)
+            .filter_map(|path| path.parent().map(Path::to_path_buf))
+            .collect();
+        for directory in self.directories.difference(&wanted) {
+            self.watcher.unwatch(directory).ok();
+        }
+        self.directories
+            .retain(|directory| wanted.contains(directory));
+        for directory in wanted {
+            if self.directories.contains(&directory) {
+                continue;
+            }
+            self.watcher
+                .watch(&directory, RecursiveMode::NonRecursive)
+                .map_err(|error| format!("{}: {error}", directory.display()))?;
+            self.directories.insert(directory);
+        }
+        Ok(())
+    }
+    // the files something happened to since the last call, as `key` names
+    // them
+    pub fn changed(&mut self) -> HashSet<PathBuf> {
+        self.events.try_iter().collect()
+    }
+}
+
+// the path events come with for `file_name`, its directory's without the
+// links in it, which it still has once the file is gone
+pub fn key(file_name: &str) -> Option<PathBuf> {
+    let path = Path::new(file_name);
+    let directory = match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+    Some(fs::canonicalize(directory).ok()?.join(path.file_name()?))
+}
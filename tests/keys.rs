@@ -0,0 +1,43 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// a directory of its own for each test, standing in for the home directory
+// too so no config of the user's is read
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("editor-keys-{}-{name}", std::process::id()));
+    fs::create_dir_all(&dir).expect("cannot create the test directory");
+    dir
+}
+
+// the text of `file` after `--batch --keys keys` edited it from empty
+fn typed(name: &str, file: &str, keys: &str) -> String {
+    let dir = scratch(name);
+    let path = dir.join(file);
+    fs::write(&path, "").expect("cannot write the test file");
+    let output = Command::new(env!("CARGO_BIN_EXE_editor_app"))
+        .args(["--batch", "--keys", keys])
+        .arg(&path)
+        .env("HOME", &dir)
+        .current_dir(&dir)
+        .output()
+        .expect("cannot run the editor");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let text = fs::read_to_string(&path).expect("cannot read the test file");
+    fs::remove_dir_all(&dir).ok();
+    text
+}
+
+#[test]
+fn injected_keys_are_typed_one_at_a_time() {
+    // as typed, Enter after an open bracket indents the next line, where a
+    // paste would put the text in as it is
+    assert_eq!(
+        typed("indent", "a.rs", "foo(<Enter>x<ctrl-s>"),
+        "foo(\n    x\n"
+    );
+}